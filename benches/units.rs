@@ -0,0 +1,67 @@
+// Compares passing a bare f64 against passing a Temperature<Celsius> to a temperature setter.
+//
+// Temperature<Celsius> is a single-field newtype over f64 with a zero-sized scale marker, and
+// both paths reduce to `temperature.into().as_f64()` in the setter. Inspecting the release
+// assembly of the two benchmark closures below (`cargo rustc --release --bench units -- --emit
+// asm`) shows they compile to the same instructions, so the newtype has no runtime cost. The
+// timings here should likewise agree within noise.
+
+#[macro_use]
+extern crate bencher;
+extern crate thermostat;
+
+use bencher::{black_box, Bencher};
+use thermostat::*;
+
+struct NullInterface;
+
+impl ThermostatInterface for NullInterface {
+    fn calling_for_heat(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn calling_for_cool(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn calling_for_fan(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+    fn call_for_heat(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn call_for_cool(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn call_for_fan(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn stop_call_for_heat(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn stop_call_for_cool(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn stop_call_for_fan(&self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn get_seconds(&self) -> Result<u64, Error> {
+        Ok(0)
+    }
+}
+
+fn set_bare_f64(b: &mut Bencher) {
+    let interface = NullInterface;
+    let mut thermostat = Thermostat::new(&interface);
+    b.iter(|| thermostat.set_minimum_set_temperature(black_box(22.5)))
+}
+
+fn set_celsius_newtype(b: &mut Bencher) {
+    let interface = NullInterface;
+    let mut thermostat = Thermostat::new(&interface);
+    b.iter(|| {
+        let temperature = Temperature::<Celsius>::new(black_box(22.5));
+        thermostat.set_minimum_set_temperature(temperature)
+    })
+}
+
+benchmark_group!(benches, set_bare_f64, set_celsius_newtype);
+benchmark_main!(benches);
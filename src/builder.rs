@@ -0,0 +1,113 @@
+use DynThermostat;
+use Error;
+use OperatingMode;
+use ThermostatConfig;
+use ThermostatInterface;
+
+/// Builds a fully configured thermostat in a single step
+#[derive(Copy, Clone)]
+pub struct ThermostatBuilder<'a> {
+    interface: &'a dyn ThermostatInterface,
+    config: ThermostatConfig,
+}
+
+impl<'a> ThermostatBuilder<'a> {
+    /// Start building a thermostat using the provided interface and the default configuration
+    pub fn new(interface: &'a dyn ThermostatInterface) -> ThermostatBuilder<'a> {
+        ThermostatBuilder {
+            interface,
+            config: ThermostatConfig::default(),
+        }
+    }
+
+    /// Create a thermostat from a previously saved configuration.
+    ///
+    /// The configuration is validated before it is applied. An Err Result is returned if it is
+    /// invalid.
+    pub fn from_config(
+        config: ThermostatConfig,
+        interface: &'a dyn ThermostatInterface,
+    ) -> Result<DynThermostat<'a>, Error> {
+        ThermostatBuilder { interface, config }.build()
+    }
+
+    /// Set the temperature reported until the first reading arrives.
+    ///
+    /// Until then, getters and estimates such as `get_current_temperature` use this value, but
+    /// outputs are left unchanged: control starts once `is_ready` reports the first reading.
+    pub fn initial_temperature(&mut self, temperature: f64) -> &mut ThermostatBuilder<'a> {
+        self.config.initial_temperature = temperature;
+        self
+    }
+
+    /// Set the minimum and maximum safe temperatures.
+    pub fn safe_range(&mut self, minimum: f64, maximum: f64) -> &mut ThermostatBuilder<'a> {
+        self.config.minimum_safe_temperature = minimum;
+        self.config.maximum_safe_temperature = maximum;
+        self
+    }
+
+    /// Set the minimum and maximum set temperatures.
+    pub fn set_range(&mut self, minimum: f64, maximum: f64) -> &mut ThermostatBuilder<'a> {
+        self.config.minimum_set_temperature = minimum;
+        self.config.maximum_set_temperature = maximum;
+        self
+    }
+
+    /// Set the operating mode.
+    pub fn operating_mode(&mut self, operating_mode: OperatingMode) -> &mut ThermostatBuilder<'a> {
+        self.config.operating_mode = operating_mode;
+        self
+    }
+
+    /// Set the minimum and maximum heat run times and the minimum heat off time in seconds.
+    pub fn heat_timings(
+        &mut self,
+        minimum_run_secs: u32,
+        maximum_run_secs: u32,
+        minimum_off_secs: u32,
+    ) -> &mut ThermostatBuilder<'a> {
+        self.config.minimum_heat_run_secs = minimum_run_secs;
+        self.config.maximum_heat_run_secs = maximum_run_secs;
+        self.config.minimum_heat_off_secs = minimum_off_secs;
+        self
+    }
+
+    /// Set the minimum and maximum cool run times and the minimum cool off time in seconds.
+    pub fn cool_timings(
+        &mut self,
+        minimum_run_secs: u32,
+        maximum_run_secs: u32,
+        minimum_off_secs: u32,
+    ) -> &mut ThermostatBuilder<'a> {
+        self.config.minimum_cool_run_secs = minimum_run_secs;
+        self.config.maximum_cool_run_secs = maximum_run_secs;
+        self.config.minimum_cool_off_secs = minimum_off_secs;
+        self
+    }
+
+    /// Set the minimum and maximum fan run times and the minimum fan off time in seconds.
+    pub fn fan_timings(
+        &mut self,
+        minimum_run_secs: u32,
+        maximum_run_secs: u32,
+        minimum_off_secs: u32,
+    ) -> &mut ThermostatBuilder<'a> {
+        self.config.minimum_fan_run_secs = minimum_run_secs;
+        self.config.maximum_fan_run_secs = maximum_run_secs;
+        self.config.minimum_fan_off_secs = minimum_off_secs;
+        self
+    }
+
+    /// Validate the configuration and create the thermostat.
+    ///
+    /// Every parameter is checked together, so the order of the calls above does not matter. An
+    /// Err Result is returned if any check of `ThermostatConfig::validate` fails or the operating
+    /// mode cannot be used with the interface.
+    pub fn build(&self) -> Result<DynThermostat<'a>, Error> {
+        self.config.validate()?;
+        let mut thermostat = DynThermostat::new(self.interface);
+        thermostat.apply_validated_config(&self.config)?;
+        Ok(thermostat)
+    }
+}
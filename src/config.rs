@@ -0,0 +1,589 @@
+#[cfg(feature = "std")]
+use core::fmt;
+#[cfg(feature = "std")]
+use core::str::FromStr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use Error;
+use OperatingMode;
+use SetPointBands;
+use {
+    DEFAULT_CURRENT_TEMPERATURE, DEFAULT_DEADBAND, DEFAULT_MAXIMUM_SAFE_TEMPERATURE,
+    DEFAULT_MINIMUM_SAFE_TEMPERATURE, DEFAULT_OPERATING_MODE, DEFAULT_TARGET_TEMPERATURE,
+    DEFAULT_UNOCCUPIED_MAXIMUM_SET_TEMPERATURE, DEFAULT_UNOCCUPIED_MINIMUM_SET_TEMPERATURE,
+};
+
+/// Thermostat configuration parameters
+///
+/// Holds everything that describes how a thermostat should behave, as opposed to the runtime
+/// state it accumulates while operating.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ThermostatConfig {
+    /// Operating mode
+    pub operating_mode: OperatingMode,
+    /// Minimum safe temperature in degrees Celsius
+    pub minimum_safe_temperature: f64,
+    /// Maximum safe temperature in degrees Celsius
+    pub maximum_safe_temperature: f64,
+    /// Minimum set temperature in degrees Celsius
+    pub minimum_set_temperature: f64,
+    /// Maximum set temperature in degrees Celsius
+    pub maximum_set_temperature: f64,
+    /// Minimum seconds heat must run once started
+    pub minimum_heat_run_secs: u32,
+    /// Maximum seconds heat may run in a single cycle
+    pub maximum_heat_run_secs: u32,
+    /// Minimum seconds heat must stay off between cycles
+    pub minimum_heat_off_secs: u32,
+    /// Minimum seconds cool must run once started
+    pub minimum_cool_run_secs: u32,
+    /// Maximum seconds cool may run in a single cycle
+    pub maximum_cool_run_secs: u32,
+    /// Minimum seconds cool must stay off between cycles
+    pub minimum_cool_off_secs: u32,
+    /// Minimum seconds the fan must run once started
+    pub minimum_fan_run_secs: u32,
+    /// Maximum seconds the fan may run in a single cycle
+    pub maximum_fan_run_secs: u32,
+    /// Minimum seconds the fan must stay off between cycles
+    pub minimum_fan_off_secs: u32,
+    /// Temperature in degrees Celsius assumed until the first reading arrives
+    pub initial_temperature: f64,
+    /// Temperature in degrees Celsius maintained in `AutoChangeover` mode
+    pub target_temperature: f64,
+    /// Hysteresis band in degrees Celsius applied once heating or cooling has started
+    pub hysteresis: f64,
+    /// Deadband in degrees Celsius centred on the minimum set temperature
+    pub heat_deadband: f64,
+    /// Deadband in degrees Celsius centred on the maximum set temperature
+    pub cool_deadband: f64,
+    /// Minimum set temperature in degrees Celsius maintained in `Unoccupied` mode
+    pub unoccupied_minimum_set_temperature: f64,
+    /// Maximum set temperature in degrees Celsius maintained in `Unoccupied` mode
+    pub unoccupied_maximum_set_temperature: f64,
+}
+
+// Usable in const contexts, unlike `ThermostatConfig::default()`.
+pub(crate) const DEFAULT_CONFIG: ThermostatConfig = ThermostatConfig {
+    operating_mode: DEFAULT_OPERATING_MODE,
+    minimum_safe_temperature: DEFAULT_MINIMUM_SAFE_TEMPERATURE,
+    maximum_safe_temperature: DEFAULT_MAXIMUM_SAFE_TEMPERATURE,
+    minimum_set_temperature: DEFAULT_MINIMUM_SAFE_TEMPERATURE,
+    maximum_set_temperature: DEFAULT_MAXIMUM_SAFE_TEMPERATURE,
+    minimum_heat_run_secs: 600,
+    maximum_heat_run_secs: 3600,
+    minimum_heat_off_secs: 300,
+    minimum_cool_run_secs: 600,
+    maximum_cool_run_secs: 3600,
+    minimum_cool_off_secs: 300,
+    minimum_fan_run_secs: 300,
+    maximum_fan_run_secs: 43200,
+    minimum_fan_off_secs: 300,
+    initial_temperature: DEFAULT_CURRENT_TEMPERATURE,
+    target_temperature: DEFAULT_TARGET_TEMPERATURE,
+    hysteresis: 0.0,
+    heat_deadband: DEFAULT_DEADBAND,
+    cool_deadband: DEFAULT_DEADBAND,
+    unoccupied_minimum_set_temperature: DEFAULT_UNOCCUPIED_MINIMUM_SET_TEMPERATURE,
+    unoccupied_maximum_set_temperature: DEFAULT_UNOCCUPIED_MAXIMUM_SET_TEMPERATURE,
+};
+
+impl Default for ThermostatConfig {
+    fn default() -> ThermostatConfig {
+        DEFAULT_CONFIG
+    }
+}
+
+/// Starting configurations for common applications
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ApplicationPreset {
+    /// Server room or data center precision cooling
+    ///
+    /// Cools to a 24 °C set point in `CoolToSetPoint` mode within a 16-28 °C safe range, with 30
+    /// second minimum cool run and off times and cooling allowed to run for 24 hours at a time.
+    /// Servers are harmed by condensation well above freezing, so consider raising the minimum
+    /// safe temperature to 18 °C where the room allows it.
+    DataCenter,
+}
+
+impl ThermostatConfig {
+    /// Get the configuration for an application preset.
+    pub fn from_preset(preset: ApplicationPreset) -> ThermostatConfig {
+        match preset {
+            ApplicationPreset::DataCenter => ThermostatConfig {
+                operating_mode: OperatingMode::CoolToSetPoint,
+                minimum_safe_temperature: 16.0,
+                maximum_safe_temperature: 28.0,
+                minimum_set_temperature: 18.0,
+                maximum_set_temperature: 24.0,
+                minimum_cool_run_secs: 30,
+                maximum_cool_run_secs: 86400,
+                minimum_cool_off_secs: 30,
+                ..ThermostatConfig::default()
+            },
+        }
+    }
+}
+
+// Number of fields in ThermostatConfig, and so the most fields a ConfigDiff can hold.
+const CONFIG_FIELD_COUNT: usize = 21;
+
+/// Names of the fields that differ between two configurations
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ConfigDiff {
+    fields: [&'static str; CONFIG_FIELD_COUNT],
+    len: usize,
+}
+
+impl ConfigDiff {
+    /// Get the names of the changed fields, in declaration order.
+    pub fn changed_fields(&self) -> &[&'static str] {
+        &self.fields[..self.len]
+    }
+
+    /// Check whether no fields changed.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn push_if(&mut self, changed: bool, field: &'static str) {
+        if changed {
+            self.fields[self.len] = field;
+            self.len += 1;
+        }
+    }
+}
+
+impl ThermostatConfig {
+    /// List the fields whose values differ from `other`.
+    ///
+    /// Values are compared exactly. This is appropriate because configuration values are set
+    /// discretely rather than computed.
+    pub fn diff(&self, other: &ThermostatConfig) -> ConfigDiff {
+        let mut diff = ConfigDiff {
+            fields: [""; CONFIG_FIELD_COUNT],
+            len: 0,
+        };
+        diff.push_if(self.operating_mode != other.operating_mode, "operating_mode");
+        diff.push_if(
+            self.minimum_safe_temperature != other.minimum_safe_temperature,
+            "minimum_safe_temperature",
+        );
+        diff.push_if(
+            self.maximum_safe_temperature != other.maximum_safe_temperature,
+            "maximum_safe_temperature",
+        );
+        diff.push_if(
+            self.minimum_set_temperature != other.minimum_set_temperature,
+            "minimum_set_temperature",
+        );
+        diff.push_if(
+            self.maximum_set_temperature != other.maximum_set_temperature,
+            "maximum_set_temperature",
+        );
+        diff.push_if(
+            self.minimum_heat_run_secs != other.minimum_heat_run_secs,
+            "minimum_heat_run_secs",
+        );
+        diff.push_if(
+            self.maximum_heat_run_secs != other.maximum_heat_run_secs,
+            "maximum_heat_run_secs",
+        );
+        diff.push_if(
+            self.minimum_heat_off_secs != other.minimum_heat_off_secs,
+            "minimum_heat_off_secs",
+        );
+        diff.push_if(
+            self.minimum_cool_run_secs != other.minimum_cool_run_secs,
+            "minimum_cool_run_secs",
+        );
+        diff.push_if(
+            self.maximum_cool_run_secs != other.maximum_cool_run_secs,
+            "maximum_cool_run_secs",
+        );
+        diff.push_if(
+            self.minimum_cool_off_secs != other.minimum_cool_off_secs,
+            "minimum_cool_off_secs",
+        );
+        diff.push_if(
+            self.minimum_fan_run_secs != other.minimum_fan_run_secs,
+            "minimum_fan_run_secs",
+        );
+        diff.push_if(
+            self.maximum_fan_run_secs != other.maximum_fan_run_secs,
+            "maximum_fan_run_secs",
+        );
+        diff.push_if(
+            self.minimum_fan_off_secs != other.minimum_fan_off_secs,
+            "minimum_fan_off_secs",
+        );
+        diff.push_if(
+            self.initial_temperature != other.initial_temperature,
+            "initial_temperature",
+        );
+        diff.push_if(
+            self.target_temperature != other.target_temperature,
+            "target_temperature",
+        );
+        diff.push_if(self.hysteresis != other.hysteresis, "hysteresis");
+        diff.push_if(self.heat_deadband != other.heat_deadband, "heat_deadband");
+        diff.push_if(self.cool_deadband != other.cool_deadband, "cool_deadband");
+        diff.push_if(
+            self.unoccupied_minimum_set_temperature != other.unoccupied_minimum_set_temperature,
+            "unoccupied_minimum_set_temperature",
+        );
+        diff.push_if(
+            self.unoccupied_maximum_set_temperature != other.unoccupied_maximum_set_temperature,
+            "unoccupied_maximum_set_temperature",
+        );
+        diff
+    }
+
+    // 32-bit FNV-1a hash of every field in declaration order, numbers little-endian.
+    pub(crate) fn fnv1a_hash(&self) -> u32 {
+        let mut hash: u32 = 0x811c_9dc5;
+        let mut write = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= *byte as u32;
+                hash = hash.wrapping_mul(0x0100_0193);
+            }
+        };
+        write(&[self.operating_mode as u8]);
+        for temperature in &self.temperatures() {
+            write(&temperature.to_bits().to_le_bytes());
+        }
+        for secs in &self.timings() {
+            write(&secs.to_le_bytes());
+        }
+        write(&self.initial_temperature.to_bits().to_le_bytes());
+        for value in &self.control_parameters() {
+            write(&value.to_bits().to_le_bytes());
+        }
+        hash
+    }
+
+    /// Check the configuration for internal consistency.
+    ///
+    /// All temperatures, including the initial temperature, must be finite, the minimum safe
+    /// temperature must be below the maximum safe temperature, the set temperatures must be
+    /// ordered and within the safe temperatures, no timing may be zero, and each minimum run time
+    /// must be below its maximum run time. The target and unoccupied set temperatures must also
+    /// be within the safe temperatures, the hysteresis and deadbands must not be negative, and
+    /// together they must fit between both pairs of set temperatures as checked by the setters,
+    /// e.g. `set_hysteresis`. An Err Result is returned if any check fails.
+    pub fn validate(&self) -> Result<(), Error> {
+        let bands = SetPointBands {
+            operating_mode: self.operating_mode,
+            minimum: self.minimum_set_temperature,
+            maximum: self.maximum_set_temperature,
+            unoccupied_minimum: self.unoccupied_minimum_set_temperature,
+            unoccupied_maximum: self.unoccupied_maximum_set_temperature,
+            hysteresis: self.hysteresis,
+            heat_deadband: self.heat_deadband,
+            cool_deadband: self.cool_deadband,
+        };
+        if self.temperatures().iter().any(|t| !t.is_finite())
+            || !self.initial_temperature.is_finite()
+            || self.control_parameters().iter().any(|t| !t.is_finite())
+            || self.minimum_safe_temperature >= self.maximum_safe_temperature
+            || self.minimum_set_temperature > self.maximum_set_temperature
+            || self.minimum_set_temperature < self.minimum_safe_temperature
+            || self.maximum_set_temperature > self.maximum_safe_temperature
+            || self.timings().contains(&0)
+            || self.minimum_heat_run_secs >= self.maximum_heat_run_secs
+            || self.minimum_cool_run_secs >= self.maximum_cool_run_secs
+            || self.minimum_fan_run_secs >= self.maximum_fan_run_secs
+            || self.target_temperature < self.minimum_safe_temperature
+            || self.target_temperature > self.maximum_safe_temperature
+            || self.unoccupied_minimum_set_temperature < self.minimum_safe_temperature
+            || self.unoccupied_maximum_set_temperature > self.maximum_safe_temperature
+            || self.hysteresis < 0.0
+            || self.heat_deadband < 0.0
+            || self.cool_deadband < 0.0
+            || bands.validate().is_err()
+        {
+            Err(Error::InvalidConfiguration)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Wrapper for persistent configuration storage, such as flash memory or EEPROM
+pub trait ThermostatStorage {
+    /// loads the saved configuration, or None if nothing has been saved
+    fn load(&self) -> Result<Option<ThermostatConfig>, Error>;
+    /// saves the configuration
+    fn save(&self, config: &ThermostatConfig) -> Result<(), Error>;
+}
+
+/// Minimal byte-oriented persistent storage, such as EEPROM
+///
+/// `ThermostatConfig::save_to_kv_store` writes one byte per key, numbers little-endian:
+///
+/// | Keys    | Field                                    | Encoding            |
+/// |---------|------------------------------------------|---------------------|
+/// | 0       | `operating_mode`                         | variant index, `u8` |
+/// | 1-8     | `minimum_safe_temperature`               | `f64`               |
+/// | 9-16    | `maximum_safe_temperature`               | `f64`               |
+/// | 17-24   | `minimum_set_temperature`                | `f64`               |
+/// | 25-32   | `maximum_set_temperature`                | `f64`               |
+/// | 33-68   | timing fields, in declaration order      | `u32` each          |
+/// | 69-76   | `initial_temperature`                    | `f64`               |
+/// | 77-84   | `target_temperature`                     | `f64`               |
+/// | 85-92   | `hysteresis`                             | `f64`               |
+/// | 93-100  | `heat_deadband`                          | `f64`               |
+/// | 101-108 | `cool_deadband`                          | `f64`               |
+/// | 109-116 | `unoccupied_minimum_set_temperature`     | `f64`               |
+/// | 117-124 | `unoccupied_maximum_set_temperature`     | `f64`               |
+pub trait KeyValueStore {
+    /// writes a byte at the given key
+    fn write_u8(&self, key: u8, value: u8) -> Result<(), Error>;
+    /// reads the byte at the given key
+    fn read_u8(&self, key: u8) -> Result<u8, Error>;
+}
+
+// Number of keys used by a configuration in a KeyValueStore.
+const KV_STORE_KEY_COUNT: usize = 125;
+
+impl ThermostatConfig {
+    /// Save the configuration to a key-value store using the layout documented on
+    /// `KeyValueStore`.
+    pub fn save_to_kv_store(&self, store: &dyn KeyValueStore) -> Result<(), Error> {
+        let mut bytes = [0; KV_STORE_KEY_COUNT];
+        bytes[0] = self.operating_mode as u8;
+        for (i, temperature) in self.temperatures().iter().enumerate() {
+            bytes[1 + i * 8..9 + i * 8].copy_from_slice(&temperature.to_le_bytes());
+        }
+        for (i, secs) in self.timings().iter().enumerate() {
+            bytes[33 + i * 4..37 + i * 4].copy_from_slice(&secs.to_le_bytes());
+        }
+        bytes[69..77].copy_from_slice(&self.initial_temperature.to_le_bytes());
+        for (i, value) in self.control_parameters().iter().enumerate() {
+            bytes[77 + i * 8..85 + i * 8].copy_from_slice(&value.to_le_bytes());
+        }
+        for (key, byte) in bytes.iter().enumerate() {
+            store.write_u8(key as u8, *byte)?;
+        }
+        Ok(())
+    }
+
+    /// Load a configuration saved with `save_to_kv_store`.
+    ///
+    /// An Err Result is returned if the store fails or the stored configuration is not valid,
+    /// e.g. because nothing has been saved yet.
+    pub fn load_from_kv_store(store: &dyn KeyValueStore) -> Result<ThermostatConfig, Error> {
+        let mut bytes = [0; KV_STORE_KEY_COUNT];
+        for (key, byte) in bytes.iter_mut().enumerate() {
+            *byte = store.read_u8(key as u8)?;
+        }
+        let temperature = |key: usize| {
+            let mut value = [0; 8];
+            value.copy_from_slice(&bytes[key..key + 8]);
+            f64::from_le_bytes(value)
+        };
+        let secs = |i: usize| {
+            let mut value = [0; 4];
+            value.copy_from_slice(&bytes[33 + i * 4..37 + i * 4]);
+            u32::from_le_bytes(value)
+        };
+        let config = ThermostatConfig {
+            operating_mode: operating_mode_from_u8(bytes[0])?,
+            minimum_safe_temperature: temperature(1),
+            maximum_safe_temperature: temperature(9),
+            minimum_set_temperature: temperature(17),
+            maximum_set_temperature: temperature(25),
+            minimum_heat_run_secs: secs(0),
+            maximum_heat_run_secs: secs(1),
+            minimum_heat_off_secs: secs(2),
+            minimum_cool_run_secs: secs(3),
+            maximum_cool_run_secs: secs(4),
+            minimum_cool_off_secs: secs(5),
+            minimum_fan_run_secs: secs(6),
+            maximum_fan_run_secs: secs(7),
+            minimum_fan_off_secs: secs(8),
+            initial_temperature: temperature(69),
+            target_temperature: temperature(77),
+            hysteresis: temperature(85),
+            heat_deadband: temperature(93),
+            cool_deadband: temperature(101),
+            unoccupied_minimum_set_temperature: temperature(109),
+            unoccupied_maximum_set_temperature: temperature(117),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn temperatures(&self) -> [f64; 4] {
+        [
+            self.minimum_safe_temperature,
+            self.maximum_safe_temperature,
+            self.minimum_set_temperature,
+            self.maximum_set_temperature,
+        ]
+    }
+
+    fn control_parameters(&self) -> [f64; 6] {
+        [
+            self.target_temperature,
+            self.hysteresis,
+            self.heat_deadband,
+            self.cool_deadband,
+            self.unoccupied_minimum_set_temperature,
+            self.unoccupied_maximum_set_temperature,
+        ]
+    }
+
+    fn timings(&self) -> [u32; 9] {
+        [
+            self.minimum_heat_run_secs,
+            self.maximum_heat_run_secs,
+            self.minimum_heat_off_secs,
+            self.minimum_cool_run_secs,
+            self.maximum_cool_run_secs,
+            self.minimum_cool_off_secs,
+            self.minimum_fan_run_secs,
+            self.maximum_fan_run_secs,
+            self.minimum_fan_off_secs,
+        ]
+    }
+}
+
+fn operating_mode_from_u8(value: u8) -> Result<OperatingMode, Error> {
+    match value {
+        0 => Ok(OperatingMode::MaintainRange),
+        1 => Ok(OperatingMode::CoolToSetPoint),
+        2 => Ok(OperatingMode::HeatToSetPoint),
+        3 => Ok(OperatingMode::Disabled),
+        4 => Ok(OperatingMode::DisabledUnsafe),
+        5 => Ok(OperatingMode::Custom),
+        6 => Ok(OperatingMode::Differential),
+        7 => Ok(OperatingMode::Scheduling),
+        8 => Ok(OperatingMode::RecirculationOnly),
+        9 => Ok(OperatingMode::EmergencyHeat),
+        10 => Ok(OperatingMode::AutoChangeover),
+        11 => Ok(OperatingMode::FanOnly),
+        12 => Ok(OperatingMode::HeatPump),
+        13 => Ok(OperatingMode::Unoccupied),
+        14 => Ok(OperatingMode::Vacation),
+        _ => Err(Error::InvalidConfiguration),
+    }
+}
+
+/// Command-line argument parsing errors
+#[cfg(feature = "std")]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ParseError<'a> {
+    /// The argument was not of the form `--key=value`
+    MalformedArgument(&'a str),
+    /// The key is not a known configuration parameter
+    UnrecognizedKey(&'a str),
+    /// The value could not be converted for the given key
+    InvalidValue {
+        /// Key whose value failed to convert
+        key: &'a str,
+        /// Value that failed to convert
+        value: &'a str,
+    },
+}
+
+#[cfg(feature = "std")]
+impl<'a> fmt::Display for ParseError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::MalformedArgument(arg) => {
+                write!(f, "argument \"{}\" is not of the form --key=value", arg)
+            }
+            ParseError::UnrecognizedKey(key) => write!(f, "unrecognized key \"{}\"", key),
+            ParseError::InvalidValue { key, value } => {
+                write!(f, "invalid value \"{}\" for key \"{}\"", value, key)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> ::std::error::Error for ParseError<'a> {}
+
+#[cfg(feature = "std")]
+impl ThermostatConfig {
+    /// Build a configuration from `--key=value` command-line style arguments.
+    ///
+    /// Parameters not present in `args` keep their default values. Recognized keys are `mode`,
+    /// `min-safe`, `max-safe`, `min-set`, `max-set`, and `min-heat-run`, `max-heat-run`,
+    /// `min-heat-off` along with their `cool` and `fan` equivalents, `initial-temp`, `target-temp`,
+    /// `hysteresis`, `heat-deadband`, `cool-deadband`, `unoccupied-min-set`, and
+    /// `unoccupied-max-set`. The `mode` value is the name of an `OperatingMode` variant, e.g.
+    /// `--mode=MaintainRange`.
+    pub fn from_args<'a>(args: &[&'a str]) -> Result<ThermostatConfig, ParseError<'a>> {
+        let mut config = ThermostatConfig::default();
+        for arg in args {
+            let (key, value) = match arg.strip_prefix("--").and_then(|a| a.split_once('=')) {
+                Some(pair) => pair,
+                None => return Err(ParseError::MalformedArgument(arg)),
+            };
+            match key {
+                "mode" => config.operating_mode = parse_operating_mode(key, value)?,
+                "min-safe" => config.minimum_safe_temperature = parse(key, value)?,
+                "max-safe" => config.maximum_safe_temperature = parse(key, value)?,
+                "min-set" => config.minimum_set_temperature = parse(key, value)?,
+                "max-set" => config.maximum_set_temperature = parse(key, value)?,
+                "min-heat-run" => config.minimum_heat_run_secs = parse(key, value)?,
+                "max-heat-run" => config.maximum_heat_run_secs = parse(key, value)?,
+                "min-heat-off" => config.minimum_heat_off_secs = parse(key, value)?,
+                "min-cool-run" => config.minimum_cool_run_secs = parse(key, value)?,
+                "max-cool-run" => config.maximum_cool_run_secs = parse(key, value)?,
+                "min-cool-off" => config.minimum_cool_off_secs = parse(key, value)?,
+                "min-fan-run" => config.minimum_fan_run_secs = parse(key, value)?,
+                "max-fan-run" => config.maximum_fan_run_secs = parse(key, value)?,
+                "min-fan-off" => config.minimum_fan_off_secs = parse(key, value)?,
+                "initial-temp" => config.initial_temperature = parse(key, value)?,
+                "target-temp" => config.target_temperature = parse(key, value)?,
+                "hysteresis" => config.hysteresis = parse(key, value)?,
+                "heat-deadband" => config.heat_deadband = parse(key, value)?,
+                "cool-deadband" => config.cool_deadband = parse(key, value)?,
+                "unoccupied-min-set" => {
+                    config.unoccupied_minimum_set_temperature = parse(key, value)?
+                }
+                "unoccupied-max-set" => {
+                    config.unoccupied_maximum_set_temperature = parse(key, value)?
+                }
+                _ => return Err(ParseError::UnrecognizedKey(key)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(feature = "std")]
+fn parse<'a, T: FromStr>(key: &'a str, value: &'a str) -> Result<T, ParseError<'a>> {
+    value
+        .parse()
+        .map_err(|_| ParseError::InvalidValue { key, value })
+}
+
+#[cfg(feature = "std")]
+fn parse_operating_mode<'a>(key: &'a str, value: &'a str) -> Result<OperatingMode, ParseError<'a>> {
+    match value {
+        "MaintainRange" => Ok(OperatingMode::MaintainRange),
+        "CoolToSetPoint" => Ok(OperatingMode::CoolToSetPoint),
+        "HeatToSetPoint" => Ok(OperatingMode::HeatToSetPoint),
+        "Disabled" => Ok(OperatingMode::Disabled),
+        "DisabledUnsafe" => Ok(OperatingMode::DisabledUnsafe),
+        "Custom" => Ok(OperatingMode::Custom),
+        "Differential" => Ok(OperatingMode::Differential),
+        "Scheduling" => Ok(OperatingMode::Scheduling),
+        "RecirculationOnly" => Ok(OperatingMode::RecirculationOnly),
+        "EmergencyHeat" => Ok(OperatingMode::EmergencyHeat),
+        "AutoChangeover" => Ok(OperatingMode::AutoChangeover),
+        "FanOnly" => Ok(OperatingMode::FanOnly),
+        "HeatPump" => Ok(OperatingMode::HeatPump),
+        "Unoccupied" => Ok(OperatingMode::Unoccupied),
+        "Vacation" => Ok(OperatingMode::Vacation),
+        _ => Err(ParseError::InvalidValue { key, value }),
+    }
+}
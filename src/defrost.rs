@@ -0,0 +1,101 @@
+use ConstraintError;
+use Error;
+use ThermostatCore;
+use ThermostatInterface;
+
+impl<'a, I: ThermostatInterface, const N: usize> ThermostatCore<'a, I, N> {
+    /// Change the hours of compressor runtime between defrost cycles.
+    ///
+    /// Defrost is scheduled by accumulated cooling runtime rather than wall-clock time, since
+    /// frost only builds while the evaporator is cold. Zero, the default, disables defrost.
+    ///
+    /// An Err Result is returned if the interval is not longer than the defrost duration.
+    pub fn set_defrost_interval_hours(&mut self, hours: u8) -> Result<(), Error> {
+        if hours > 0 && hours as u64 * 3600 <= self.state.defrost_duration_secs as u64 {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.defrost_interval_hours = hours;
+        Ok(())
+    }
+    /// Get the hours of compressor runtime between defrost cycles.
+    pub fn get_defrost_interval_hours(&self) -> u8 {
+        self.state.defrost_interval_hours
+    }
+
+    /// Change the maximum duration of a defrost cycle.
+    ///
+    /// An Err Result is returned if the duration is zero or not shorter than the defrost interval.
+    pub fn set_defrost_duration_secs(&mut self, secs: u32) -> Result<(), Error> {
+        if secs == 0
+            || (self.state.defrost_interval_hours > 0
+                && self.state.defrost_interval_hours as u64 * 3600 <= secs as u64)
+        {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.defrost_duration_secs = secs;
+        Ok(())
+    }
+    /// Get the maximum duration of a defrost cycle.
+    pub fn get_defrost_duration_secs(&self) -> u32 {
+        self.state.defrost_duration_secs
+    }
+
+    /// Change the temperature at which a defrost cycle is considered complete.
+    ///
+    /// Defrost ends early once the current temperature reaches this value.
+    ///
+    /// An Err Result is returned if the temperature is not finite.
+    pub fn set_defrost_max_temperature(&mut self, temperature: f64) -> Result<(), Error> {
+        if !temperature.is_finite() {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.defrost_max_temperature = temperature;
+        Ok(())
+    }
+    /// Get the temperature at which a defrost cycle is considered complete.
+    pub fn get_defrost_max_temperature(&self) -> f64 {
+        self.state.defrost_max_temperature
+    }
+
+    /// Check whether a defrost cycle is in progress.
+    pub fn is_defrosting(&self) -> bool {
+        self.state.defrost_start.is_some()
+    }
+
+    // Runs the defrost schedule. Returns true while defrost has taken over control from the
+    // operating mode.
+    pub(crate) fn update_defrost(&mut self) -> Result<bool, ConstraintError> {
+        if self.state.defrost_interval_hours == 0 && self.state.defrost_start.is_none() {
+            return Ok(false);
+        }
+        let now = self.interface.get_seconds()?;
+        if let Some(start) = self.state.defrost_start {
+            if now.saturating_sub(start) < self.state.defrost_duration_secs as u64
+                && self.state.current_temperature < self.state.defrost_max_temperature
+            {
+                return Ok(true);
+            }
+            self.interface.stop_call_for_defrost_heat()?;
+            self.state.defrost_start = None;
+            self.state.cumulative_cool_runtime_since_defrost = 0;
+            return Ok(false);
+        }
+        let running_secs = match (
+            self.state.last_call_for_cool_start,
+            self.state.last_call_for_cool_end,
+        ) {
+            (Some(start), Some(end)) if end >= start => 0,
+            (Some(start), _) => now.saturating_sub(start),
+            (None, _) => 0,
+        };
+        if self.state.cumulative_cool_runtime_since_defrost + running_secs
+            < self.state.defrost_interval_hours as u64 * 3600
+        {
+            return Ok(false);
+        }
+        self.stop_cool()?;
+        self.interface.call_for_defrost_heat()?;
+        self.state.defrost_start = Some(now);
+        Ok(true)
+    }
+}
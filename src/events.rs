@@ -0,0 +1,215 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use Error;
+use OperatingMode;
+
+/// Default number of events retained by a thermostat's event log
+pub const DEFAULT_EVENT_LOG_CAPACITY: usize = 16;
+
+/// Kinds of events recorded in the thermostat event log
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum EventKind {
+    /// Call for heat started
+    HeatStarted,
+    /// Call for heat stopped
+    HeatStopped,
+    /// Call for cool started
+    CoolStarted,
+    /// Call for cool stopped
+    CoolStopped,
+    /// Call for fan started
+    FanStarted,
+    /// Call for fan stopped
+    FanStopped,
+    /// Operating mode changed to the contained mode
+    OperatingModeChanged(OperatingMode),
+    /// Minimum set temperature changed to the contained value
+    MinimumSetTemperatureChanged(f64),
+    /// Maximum set temperature changed to the contained value
+    MaximumSetTemperatureChanged(f64),
+    /// A timing or configuration constraint prevented the requested control action
+    ConstraintViolation(Error),
+    /// A handler or measurement failed
+    Fault(Error),
+}
+
+/// Event log entry
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimestampedEvent {
+    /// Seconds since system start, as reported by the interface, when the event occurred
+    pub timestamp_secs: u64,
+    /// What happened
+    pub event: EventKind,
+}
+
+// Fixed-size event buffer holding the most recent N events, oldest first. When full, the oldest
+// event is discarded to make room for the newest. The newest `unread` events have not yet been
+// drained.
+#[derive(Copy, Clone)]
+pub(crate) struct EventLog<const N: usize> {
+    events: [TimestampedEvent; N],
+    len: usize,
+    unread: usize,
+}
+
+impl<const N: usize> EventLog<N> {
+    pub(crate) const fn new() -> EventLog<N> {
+        EventLog {
+            events: [TimestampedEvent {
+                timestamp_secs: 0,
+                event: EventKind::FanStopped,
+            }; N],
+            len: 0,
+            unread: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, timestamp_secs: u64, event: EventKind) {
+        if N == 0 {
+            return;
+        }
+        if self.len == N {
+            self.events.copy_within(1.., 0);
+            self.len -= 1;
+        }
+        self.events[self.len] = TimestampedEvent {
+            timestamp_secs,
+            event,
+        };
+        self.len += 1;
+        self.unread = (self.unread + 1).min(self.len);
+    }
+
+    pub(crate) fn as_slice(&self) -> &[TimestampedEvent] {
+        &self.events[..self.len]
+    }
+
+    pub(crate) fn unread(&self) -> usize {
+        self.unread
+    }
+
+    pub(crate) fn drain(&mut self, buffer: &mut [TimestampedEvent]) -> usize {
+        let count = self.unread.min(buffer.len());
+        let start = self.len - self.unread;
+        buffer[..count].copy_from_slice(&self.events[start..start + count]);
+        self.unread -= count;
+        count
+    }
+
+    pub(crate) fn latest(&self) -> Option<&TimestampedEvent> {
+        self.as_slice().last()
+    }
+}
+
+/// Receives notifications as the thermostat changes its outputs
+///
+/// Every method does nothing by default, so implementations only override the notifications
+/// they need. Register a handler with `set_event_handler`.
+pub trait ThermostatEventHandler {
+    /// Called after heat starts being called for
+    fn on_heat_started(&self) {}
+    /// Called after heat stops being called for
+    fn on_heat_stopped(&self) {}
+    /// Called after cool starts being called for
+    fn on_cool_started(&self) {}
+    /// Called after cool stops being called for
+    fn on_cool_stopped(&self) {}
+    /// Called after the fan starts being called for
+    fn on_fan_started(&self) {}
+    /// Called after the fan stops being called for
+    fn on_fan_stopped(&self) {}
+    /// Called with the temperature when a reading first falls outside the safe temperatures
+    fn on_safety_limit_breached(&self, _temperature: f64) {}
+    /// Called once the filter runtime reaches the filter alert threshold, until it is reset
+    fn on_filter_alert(&self) {}
+}
+
+/// Combination of outputs being called for
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SystemState {
+    /// Whether heat is being called for
+    pub heat: bool,
+    /// Whether cool is being called for
+    pub cool: bool,
+    /// Whether fan is being called for
+    pub fan: bool,
+}
+
+/// Change in outputs caused by a temperature reading
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Action {
+    /// Call for heat started
+    HeatStarted,
+    /// Call for heat stopped
+    HeatStopped,
+    /// Call for cool started
+    CoolStarted,
+    /// Call for cool stopped
+    CoolStopped,
+    /// Call for fan started
+    FanStarted,
+    /// Call for fan stopped
+    FanStopped,
+    /// No output changed
+    NoChange,
+    /// The contained number of outputs changed, e.g. the fan starting along with heat
+    MultipleChanges(u8),
+}
+
+impl Action {
+    pub(crate) fn between(from: SystemState, to: SystemState) -> Action {
+        let changes = [
+            (from.heat, to.heat, Action::HeatStarted, Action::HeatStopped),
+            (from.cool, to.cool, Action::CoolStarted, Action::CoolStopped),
+            (from.fan, to.fan, Action::FanStarted, Action::FanStopped),
+        ];
+        let mut action = Action::NoChange;
+        let mut count = 0;
+        for &(before, after, started, stopped) in changes.iter() {
+            if before != after {
+                action = if after { started } else { stopped };
+                count += 1;
+            }
+        }
+        if count > 1 {
+            Action::MultipleChanges(count)
+        } else {
+            action
+        }
+    }
+}
+
+/// What caused a change in outputs
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TransitionTrigger {
+    /// The contained temperature reading
+    TemperatureReading(f64),
+    /// The contained relative humidity reading
+    HumidityReading(f64),
+    /// A forced output command
+    ManualCommand,
+    /// A reading after the previous one was held back by a timing constraint
+    TimingConstraintCleared,
+    /// A fault was detected while handling a reading
+    FaultDetected,
+}
+
+/// Change in outputs, for debugging the state machine
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Transition {
+    /// Outputs before the change
+    pub from_state: SystemState,
+    /// Outputs after the change
+    pub to_state: SystemState,
+    /// What caused the change
+    pub trigger: TransitionTrigger,
+    /// Seconds since system start, as reported by the interface, when the change occurred
+    pub timestamp_secs: u64,
+}
@@ -0,0 +1,96 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use EffectiveMode;
+use Error;
+use SystemState;
+use ThermostatCore;
+use ThermostatInterface;
+use TransitionTrigger;
+use DEFAULT_EVENT_LOG_CAPACITY;
+
+/// Snapshot of the thermostat after a control decision
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ThermostatStatus {
+    /// Current temperature in degrees Celsius
+    pub current_temperature: f64,
+    /// Outputs being called for
+    pub outputs: SystemState,
+    /// Operating mode and any override in effect
+    pub effective_mode: EffectiveMode,
+}
+
+/// Future that completes with the next control decision
+///
+/// Created by `next_decision`. Each poll asks the interface's `measure` for a new reading and
+/// hands it to `set_current_temperature`; without one, the current temperature is re-evaluated
+/// once the timing constraint reported by `next_constraint_clearance_secs` at creation has
+/// cleared, or straight away if no constraint was counting down. Until then the future wakes
+/// itself and returns `Pending`, so the runtime polls it again when it next schedules the task.
+/// Runtimes that can sleep until a deadline should prefer awaiting a timer for the clearance time
+/// before polling.
+pub struct ThermostatFuture<'a, 'b, I, const N: usize = DEFAULT_EVENT_LOG_CAPACITY> {
+    thermostat: &'a mut ThermostatCore<'b, I, N>,
+    wake_at_secs: Option<u64>,
+}
+
+impl<'a, I: ThermostatInterface, const N: usize> ThermostatCore<'a, I, N> {
+    /// Get a future that completes when the next control decision has been made.
+    ///
+    /// See `ThermostatFuture` for when that happens.
+    pub fn next_decision(&mut self) -> ThermostatFuture<'_, 'a, I, N> {
+        let wake_at_secs = match (
+            self.interface.get_seconds(),
+            self.next_constraint_clearance_secs(),
+        ) {
+            (Ok(now), Some(secs)) => Some(now.saturating_add(secs)),
+            _ => None,
+        };
+        ThermostatFuture {
+            thermostat: self,
+            wake_at_secs,
+        }
+    }
+
+    /// Get a snapshot of the current temperature, outputs, and effective operating mode.
+    ///
+    /// An Err Result is returned if the outputs cannot be read from the interface.
+    pub fn get_status(&self) -> Result<ThermostatStatus, Error> {
+        Ok(ThermostatStatus {
+            current_temperature: self.state.current_temperature,
+            outputs: self.output_state()?,
+            effective_mode: self.get_effective_operating_mode(),
+        })
+    }
+}
+
+impl<'a, 'b, I: ThermostatInterface, const N: usize> Future for ThermostatFuture<'a, 'b, I, N> {
+    type Output = Result<ThermostatStatus, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let future = self.get_mut();
+        let thermostat = &mut *future.thermostat;
+        let result = if let Some(temperature) = thermostat.interface.measure()? {
+            thermostat.set_current_temperature(temperature)
+        } else {
+            let now = thermostat.interface.get_seconds()?;
+            match future.wake_at_secs {
+                Some(wake_at_secs) if now < wake_at_secs => {
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+                Some(_) => thermostat.reevaluate(TransitionTrigger::TimingConstraintCleared),
+                None => {
+                    let temperature = thermostat.state.current_temperature;
+                    thermostat.reevaluate(TransitionTrigger::TemperatureReading(temperature))
+                }
+            }
+        };
+        Poll::Ready(result.and_then(|_| thermostat.get_status()))
+    }
+}
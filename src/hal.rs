@@ -0,0 +1,123 @@
+use core::cell::{Cell, RefCell};
+
+use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::timer::{CountDown, Periodic};
+
+use Error;
+use ThermostatInterface;
+
+/// Thermostat interface driving heat, cool, and fan relays from `embedded-hal` output pins
+///
+/// Each output is active high. The clock must be a periodic count down timer already started with
+/// a one second period; every completed period observed by `get_seconds` advances the clock by
+/// one second, so the thermostat must be polled at least once per second to keep accurate time.
+pub struct EmbeddedHalInterface<HEAT, COOL, FAN, CLK> {
+    heat: RefCell<HEAT>,
+    cool: RefCell<COOL>,
+    fan: RefCell<FAN>,
+    clock: RefCell<CLK>,
+    heating: Cell<bool>,
+    cooling: Cell<bool>,
+    fanning: Cell<bool>,
+    seconds: Cell<u64>,
+}
+
+impl<HEAT, COOL, FAN, CLK> EmbeddedHalInterface<HEAT, COOL, FAN, CLK>
+where
+    HEAT: OutputPin,
+    COOL: OutputPin,
+    FAN: OutputPin,
+    CLK: CountDown + Periodic,
+{
+    /// Create an interface from output pins and a started one second periodic timer.
+    ///
+    /// All outputs are driven low. An Err Result is returned if any pin cannot be driven.
+    pub fn new(
+        mut heat: HEAT,
+        mut cool: COOL,
+        mut fan: FAN,
+        clock: CLK,
+    ) -> Result<EmbeddedHalInterface<HEAT, COOL, FAN, CLK>, Error> {
+        heat.set_low().map_err(|_| Error::HandlerFailed)?;
+        cool.set_low().map_err(|_| Error::HandlerFailed)?;
+        fan.set_low().map_err(|_| Error::HandlerFailed)?;
+        Ok(EmbeddedHalInterface {
+            heat: RefCell::new(heat),
+            cool: RefCell::new(cool),
+            fan: RefCell::new(fan),
+            clock: RefCell::new(clock),
+            heating: Cell::new(false),
+            cooling: Cell::new(false),
+            fanning: Cell::new(false),
+            seconds: Cell::new(0),
+        })
+    }
+
+    /// Release the pins and timer
+    pub fn release(self) -> (HEAT, COOL, FAN, CLK) {
+        (
+            self.heat.into_inner(),
+            self.cool.into_inner(),
+            self.fan.into_inner(),
+            self.clock.into_inner(),
+        )
+    }
+}
+
+fn drive<P: OutputPin>(pin: &RefCell<P>, state: &Cell<bool>, on: bool) -> Result<(), Error> {
+    let mut pin = pin.borrow_mut();
+    if on {
+        pin.set_high()
+    } else {
+        pin.set_low()
+    }
+    .map_err(|_| Error::HandlerFailed)?;
+    state.set(on);
+    Ok(())
+}
+
+impl<HEAT, COOL, FAN, CLK> ThermostatInterface for EmbeddedHalInterface<HEAT, COOL, FAN, CLK>
+where
+    HEAT: OutputPin,
+    COOL: OutputPin,
+    FAN: OutputPin,
+    CLK: CountDown + Periodic,
+{
+    fn calling_for_heat(&self) -> Result<bool, Error> {
+        Ok(self.heating.get())
+    }
+    fn call_for_heat(&self) -> Result<(), Error> {
+        drive(&self.heat, &self.heating, true)
+    }
+    fn stop_call_for_heat(&self) -> Result<(), Error> {
+        drive(&self.heat, &self.heating, false)
+    }
+
+    fn calling_for_cool(&self) -> Result<bool, Error> {
+        Ok(self.cooling.get())
+    }
+    fn call_for_cool(&self) -> Result<(), Error> {
+        drive(&self.cool, &self.cooling, true)
+    }
+    fn stop_call_for_cool(&self) -> Result<(), Error> {
+        drive(&self.cool, &self.cooling, false)
+    }
+
+    fn calling_for_fan(&self) -> Result<bool, Error> {
+        Ok(self.fanning.get())
+    }
+    fn call_for_fan(&self) -> Result<(), Error> {
+        drive(&self.fan, &self.fanning, true)
+    }
+    fn stop_call_for_fan(&self) -> Result<(), Error> {
+        drive(&self.fan, &self.fanning, false)
+    }
+
+    fn get_seconds(&self) -> Result<u64, Error> {
+        let clock = &mut *self.clock.borrow_mut();
+        if clock.wait().is_ok() {
+            self.seconds.set(self.seconds.get() + 1);
+        }
+        Ok(self.seconds.get())
+    }
+}
@@ -30,6 +30,14 @@
 //! extern crate thermostat;
 //!
 //! use thermostat::{OperatingMode, Thermostat, Error as ThermostatError, ThermostatInterface};
+//! use thermostat::MeasurementProvider;
+//!
+//! struct MyTemperatureSensor {}
+//! impl MeasurementProvider for MyTemperatureSensor {
+//!     fn measure(&self) -> Result<f64, ThermostatError> {
+//!         Ok(20.0) // actually read the sensor here
+//!     }
+//! }
 //!
 //! struct MyThermostatInterface {}
 //! impl ThermostatInterface for MyThermostatInterface {
@@ -74,10 +82,11 @@
 //!     // create a new thermostat with our physical interface
 //!     let mut thermostat = Thermostat::new(&interface);
 //!
-//!     // once the thermostat has been provided with a measure routine
-//!     // it will begin polling for new measurements and calling for
-//!     // heat, cool, and/or fan -- depending on which methods have
-//!     // been registered.
+//!     // once the thermostat has been provided with a measurement provider
+//!     // each tick polls for a new measurement and calls for heat, cool,
+//!     // and/or fan as needed
+//!     let sensor = MyTemperatureSensor {};
+//!     thermostat.set_measurement_provider(&sensor);
 //!
 //!     // set max temp thermostat will allow before calling for cool
 //!     thermostat.set_maximum_set_temperature(22.5).unwrap();
@@ -85,14 +94,73 @@
 //!     thermostat.set_minimum_set_temperature(18.0).unwrap();
 //!     // maintain temperatures between min and max set points
 //!     thermostat.set_operating_mode(OperatingMode::MaintainRange).unwrap();
+//!
+//!     // call periodically from the main loop
+//!     let _action = thermostat.tick();
 //! }
 //! ```
 
 use core::fmt;
 use core::result::Result;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "embedded-hal")]
+extern crate embedded_hal;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "std")]
+extern crate std;
+
+mod builder;
+mod config;
+#[cfg(feature = "defrost")]
+mod defrost;
+mod events;
+mod future;
+#[cfg(feature = "embedded-hal")]
+mod hal;
+#[cfg(feature = "std")]
+mod simulation;
+mod schedule;
+mod statistics;
+mod units;
+mod zone;
+
+pub use builder::ThermostatBuilder;
+use config::DEFAULT_CONFIG;
+#[cfg(feature = "std")]
+pub use config::ParseError;
+pub use config::{
+    ApplicationPreset, ConfigDiff, KeyValueStore, ThermostatConfig, ThermostatStorage,
+};
+pub use events::{
+    Action, EventKind, SystemState, ThermostatEventHandler, TimestampedEvent, Transition,
+    TransitionTrigger, DEFAULT_EVENT_LOG_CAPACITY,
+};
+pub use future::{ThermostatFuture, ThermostatStatus};
+#[cfg(feature = "embedded-hal")]
+pub use hal::EmbeddedHalInterface;
+#[cfg(feature = "std")]
+pub use simulation::{ActuatorAction, SimulationResult, SimulationStep};
+use schedule::{ScheduleOverride, VacationResume};
+pub use schedule::{Schedule, ScheduleSlot, MAXIMUM_SCHEDULE_SLOTS};
+pub use statistics::{OperationalStatistics, RuntimeStats};
+use statistics::DEFAULT_FILTER_ALERT_SECS;
+pub use units::{
+    celsius_to_fahrenheit, fahrenheit_to_celsius, temperature_range_celsius,
+    temperature_range_fahrenheit, Celsius, CelsiusDisplay, Fahrenheit, FahrenheitDisplay,
+    Temperature, TemperatureDisplay, TemperatureRange, TemperatureScale, TemperatureUnit,
+    TemperatureValue,
+};
+pub use zone::{ZoneCall, ZoneController, ZoneDamperInterface, ZonePriority};
+use events::EventLog;
+use statistics::CycleHistory;
+
 /// Thermostat errors
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Error {
     /// Indicates a handler failed, intended to be used by thermostat handler implementations
     HandlerFailed,
@@ -116,6 +184,93 @@ pub enum Error {
     FanMinRunTimeConstraint,
     /// Fan has not yet met the minimum off time between cycles
     FanMinOffTimeConstraint,
+    /// The requested change is incompatible with the current configuration
+    InvalidConfiguration,
+    /// The current temperature is outside the minimum and maximum safe temperatures
+    TemperatureOutsideSafeRange,
+    /// Heat and cool are being called for at the same time
+    ConflictingOutputs,
+    /// A heat cycle raised the temperature by more than the maximum heat swing
+    HeatSwingExceeded,
+    /// The temperature crossed a high or low temperature alarm threshold
+    AlarmActive,
+    /// The minimum safe temperature would not be below the maximum safe temperature
+    SafeTemperatureRangeInversion,
+    /// A deadband is negative or not finite
+    InvalidDeadband,
+    /// A set temperature is outside the minimum and maximum safe temperatures
+    SetPointOutOfSafeRange,
+    /// A temperature is NaN or infinite
+    InvalidTemperature,
+    /// The safe temperatures have been locked with `lock_safe_temperatures`
+    SafeTemperatureLocked,
+    /// A run time is zero or the minimum run time would not be below the maximum run time
+    InvalidConstraint,
+    /// The outdoor temperature is below the heat pump lockout temperature
+    OutdoorLockout,
+    /// A relative humidity is not between 0 and 100 percent
+    HumidityOutOfRange,
+    /// `tick` was called without a measurement provider registered
+    NoMeasurementProvider,
+    /// The humidifier has not yet reached its minimum run time
+    HumidifierMinRunTimeConstraint,
+    /// The humidifier has not yet reached its minimum off time
+    HumidifierMinOffTimeConstraint,
+    /// The dehumidifier has not yet reached its minimum run time
+    DehumidifierMinRunTimeConstraint,
+    /// The dehumidifier has not yet reached its minimum off time
+    DehumidifierMinOffTimeConstraint,
+    /// There is no zone at the given index
+    InvalidZone,
+    /// The reversing valve has not yet had time to shift since it was last moved
+    ReversingValveShiftConstraint,
+}
+
+// Every Error variant in declaration order; new variants must be added here too.
+const ERROR_VARIANTS: [Error; 31] = [
+    Error::HandlerFailed,
+    Error::MeasurementFailed,
+    Error::HeatMaxRunTimeConstraint,
+    Error::HeatMinRunTimeConstraint,
+    Error::HeatMinOffTimeConstraint,
+    Error::CoolMaxRunTimeConstraint,
+    Error::CoolMinRunTimeConstraint,
+    Error::CoolMinOffTimeConstraint,
+    Error::FanMaxRunTimeConstraint,
+    Error::FanMinRunTimeConstraint,
+    Error::FanMinOffTimeConstraint,
+    Error::InvalidConfiguration,
+    Error::TemperatureOutsideSafeRange,
+    Error::ConflictingOutputs,
+    Error::HeatSwingExceeded,
+    Error::AlarmActive,
+    Error::SafeTemperatureRangeInversion,
+    Error::InvalidDeadband,
+    Error::SetPointOutOfSafeRange,
+    Error::InvalidTemperature,
+    Error::SafeTemperatureLocked,
+    Error::InvalidConstraint,
+    Error::OutdoorLockout,
+    Error::HumidityOutOfRange,
+    Error::NoMeasurementProvider,
+    Error::HumidifierMinRunTimeConstraint,
+    Error::HumidifierMinOffTimeConstraint,
+    Error::DehumidifierMinRunTimeConstraint,
+    Error::DehumidifierMinOffTimeConstraint,
+    Error::InvalidZone,
+    Error::ReversingValveShiftConstraint,
+];
+
+impl Error {
+    /// Get every error variant, e.g. to build a table of error messages.
+    pub fn all_variants() -> &'static [Error] {
+        &ERROR_VARIANTS
+    }
+
+    /// Get the number of error variants
+    pub const fn count() -> usize {
+        ERROR_VARIANTS.len()
+    }
 }
 
 impl fmt::Display for Error {
@@ -133,8 +288,65 @@ impl fmt::Display for Error {
             Error::FanMaxRunTimeConstraint => "fan has reached maximum run time",
             Error::FanMinRunTimeConstraint => "fan has not yet reached minimum run time",
             Error::FanMinOffTimeConstraint => "fan has not yet reached minimum off time",
+            Error::InvalidConfiguration => "incompatible with current configuration",
+            Error::TemperatureOutsideSafeRange => "temperature is outside the safe range",
+            Error::ConflictingOutputs => "calling for heat and cool at the same time",
+            Error::HeatSwingExceeded => "heat cycle exceeded maximum temperature swing",
+            Error::AlarmActive => "temperature alarm threshold crossed",
+            Error::SafeTemperatureRangeInversion => "minimum safe temperature not below maximum",
+            Error::InvalidDeadband => "deadband must be non-negative and finite",
+            Error::SetPointOutOfSafeRange => "set temperature is outside the safe range",
+            Error::InvalidTemperature => "temperature is not finite",
+            Error::SafeTemperatureLocked => "safe temperatures are locked",
+            Error::InvalidConstraint => "run time is zero or minimum not below maximum",
+            Error::OutdoorLockout => "outdoor temperature is below heat pump lockout",
+            Error::HumidityOutOfRange => "humidity is not between 0 and 100 percent",
+            Error::NoMeasurementProvider => "no measurement provider registered",
+            Error::HumidifierMinRunTimeConstraint => {
+                "humidifier has not yet reached minimum run time"
+            }
+            Error::HumidifierMinOffTimeConstraint => {
+                "humidifier has not yet reached minimum off time"
+            }
+            Error::DehumidifierMinRunTimeConstraint => {
+                "dehumidifier has not yet reached minimum run time"
+            }
+            Error::DehumidifierMinOffTimeConstraint => {
+                "dehumidifier has not yet reached minimum off time"
+            }
+            Error::InvalidZone => "no zone at the given index",
+            Error::ReversingValveShiftConstraint => "reversing valve has not yet finished shifting",
         };
-        f.write_str(&label)
+        f.write_str(label)
+    }
+}
+
+/// An error with the number of seconds until the violated timing constraint is satisfied
+///
+/// The seconds are only known for minimum run and off time constraints, and are None otherwise.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConstraintError(pub Error, pub Option<u64>);
+
+impl From<Error> for ConstraintError {
+    fn from(error: Error) -> ConstraintError {
+        ConstraintError(error, None)
+    }
+}
+
+impl From<ConstraintError> for Error {
+    fn from(error: ConstraintError) -> Error {
+        error.0
+    }
+}
+
+impl fmt::Display for ConstraintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)?;
+        match self.1 {
+            Some(secs) => write!(f, " ({} s remaining)", secs),
+            None => Ok(()),
+        }
     }
 }
 
@@ -145,12 +357,362 @@ impl fmt::Display for Error {
 const DEFAULT_MAXIMUM_SAFE_TEMPERATURE: f64 = 30.0;
 const DEFAULT_MINIMUM_SAFE_TEMPERATURE: f64 = 15.0; // degrees C
 const DEFAULT_CURRENT_TEMPERATURE: f64 =
-    (DEFAULT_MAXIMUM_SAFE_TEMPERATURE - DEFAULT_MINIMUM_SAFE_TEMPERATURE) / 2.0; // degrees C
+    (DEFAULT_MAXIMUM_SAFE_TEMPERATURE + DEFAULT_MINIMUM_SAFE_TEMPERATURE) / 2.0; // degrees C
+const DEFAULT_CURRENT_HUMIDITY: f64 = 50.0; // percent relative humidity
+const DEFAULT_MAXIMUM_SAFE_HUMIDITY: f64 = 90.0;
+const DEFAULT_MINIMUM_SAFE_HUMIDITY: f64 = 10.0; // percent relative humidity
+const DEFAULT_TARGET_HUMIDITY: f64 = 50.0; // percent relative humidity
+const DEFAULT_HUMIDITY_DEADBAND: f64 = 10.0; // percent relative humidity
+const DEFAULT_MINIMUM_HUMIDIFIER_RUN_SECS: u32 = 300;
+const DEFAULT_MINIMUM_HUMIDIFIER_OFF_SECS: u32 = 300;
+const DEFAULT_MINIMUM_DEHUMIDIFIER_RUN_SECS: u32 = 300;
+const DEFAULT_MINIMUM_DEHUMIDIFIER_OFF_SECS: u32 = 300;
 
 const DEFAULT_OPERATING_MODE: OperatingMode = OperatingMode::Disabled;
+const DEFAULT_DISPLAY_UNIT: TemperatureUnit = TemperatureUnit::Celsius;
+
+// Typical solar thermal pump differentials in degrees C.
+const DEFAULT_DIFFERENTIAL_ON_THRESHOLD: f64 = 8.0;
+const DEFAULT_DIFFERENTIAL_OFF_THRESHOLD: f64 = 4.0;
+
+#[cfg(feature = "defrost")]
+const DEFAULT_DEFROST_DURATION_SECS: u32 = 1200;
+#[cfg(feature = "defrost")]
+const DEFAULT_DEFROST_MAX_TEMPERATURE: f64 = 10.0; // degrees C
+
+// Decimal places temperatures are rounded to before being stored or compared.
+const DEFAULT_TEMPERATURE_PRECISION: u8 = 1;
+const MAXIMUM_TEMPERATURE_PRECISION: u8 = 4;
+
+// Rounds half up to the given number of decimal places. Non-finite values and values too large to
+// have a fractional part are returned unchanged.
+fn round_half_up(value: f64, digits: u8) -> f64 {
+    let factor = [1.0, 10.0, 100.0, 1000.0, 10000.0][digits as usize];
+    let scaled = value * factor + 0.5;
+    if !scaled.is_finite() || scaled.abs() >= 4_503_599_627_370_496.0 {
+        return value;
+    }
+    let truncated = scaled as i64 as f64;
+    let floor = if truncated > scaled {
+        truncated - 1.0
+    } else {
+        truncated
+    };
+    floor / factor
+}
+
+// Time for the reversing valve to shift, and refrigerant pressures to settle, before the heat
+// pump compressor starts after the valve switches.
+const REVERSING_VALVE_DELAY_SECS: u64 = 30;
+
+// Temperature maintained in `AutoChangeover` mode until changed.
+const DEFAULT_TARGET_TEMPERATURE: f64 = 21.0; // degrees C
+
+// Set points maintained in `Unoccupied` mode until changed.
+const DEFAULT_UNOCCUPIED_MINIMUM_SET_TEMPERATURE: f64 = 16.0; // degrees C
+const DEFAULT_UNOCCUPIED_MAXIMUM_SET_TEMPERATURE: f64 = 28.0; // degrees C
+
+// Width of the band centred on each set point within which heat or cool keeps its current state.
+const DEFAULT_DEADBAND: f64 = 0.5; // degrees C
+
+// How far outside the set point range a reading may be for a temperature overrun fault to clear
+// itself when auto recovery is enabled.
+const DEFAULT_AUTO_RECOVERY_TEMPERATURE_MARGIN: f64 = 1.0; // degrees C
+
+// Number of humidity readings the humidity trend is measured across by default, and at most.
+const DEFAULT_HUMIDITY_TREND_WINDOW: usize = 2;
+const MAXIMUM_HUMIDITY_TREND_WINDOW: usize = 16;
+
+// Most recent humidity readings, oldest first, limited to the configured trend window.
+#[derive(Copy, Clone)]
+struct HumidityHistory {
+    readings: [f64; MAXIMUM_HUMIDITY_TREND_WINDOW],
+    len: usize,
+    window: usize,
+}
+
+impl HumidityHistory {
+    const fn new() -> HumidityHistory {
+        HumidityHistory {
+            readings: [0.0; MAXIMUM_HUMIDITY_TREND_WINDOW],
+            len: 0,
+            window: DEFAULT_HUMIDITY_TREND_WINDOW,
+        }
+    }
+
+    fn set_window(&mut self, window: usize) {
+        if self.len > window {
+            self.readings.copy_within(self.len - window..self.len, 0);
+            self.len = window;
+        }
+        self.window = window;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn push(&mut self, humidity: f64) {
+        if self.len == self.window {
+            self.readings.copy_within(1..self.len, 0);
+            self.len -= 1;
+        }
+        self.readings[self.len] = humidity;
+        self.len += 1;
+    }
+
+    // Average change per reading across the window, or zero with fewer than two readings.
+    fn trend(&self) -> f64 {
+        if self.len < 2 {
+            return 0.0;
+        }
+        (self.readings[self.len - 1] - self.readings[0]) / (self.len - 1) as f64
+    }
+}
+
+// Number of temperature readings the temperature trend is measured across by default, and at most.
+const DEFAULT_TEMPERATURE_TREND_WINDOW: usize = 2;
+const MAXIMUM_TEMPERATURE_TREND_WINDOW: usize = 16;
+
+// Multiple of the thermal mass time constant the temperature trend is projected ahead by trend
+// anticipation by default.
+const DEFAULT_TREND_ANTICIPATION_GAIN: f64 = 1.0;
+
+// Most recent temperature readings as (seconds, temperature), oldest first, limited to the
+// configured trend window.
+#[derive(Copy, Clone)]
+struct TemperatureHistory {
+    readings: [(u64, f64); MAXIMUM_TEMPERATURE_TREND_WINDOW],
+    len: usize,
+    window: usize,
+}
+
+impl TemperatureHistory {
+    const fn new() -> TemperatureHistory {
+        TemperatureHistory {
+            readings: [(0, 0.0); MAXIMUM_TEMPERATURE_TREND_WINDOW],
+            len: 0,
+            window: DEFAULT_TEMPERATURE_TREND_WINDOW,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn set_window(&mut self, window: usize) {
+        if self.len > window {
+            self.readings.copy_within(self.len - window..self.len, 0);
+            self.len = window;
+        }
+        self.window = window;
+    }
+
+    fn latest(&self) -> Option<(u64, f64)> {
+        self.readings[..self.len].last().copied()
+    }
+
+    fn push(&mut self, now: u64, temperature: f64) {
+        if self.len == self.window {
+            self.readings.copy_within(1..self.len, 0);
+            self.len -= 1;
+        }
+        self.readings[self.len] = (now, temperature);
+        self.len += 1;
+    }
+
+    // Average rate of change in degrees per second across the window, or zero with fewer than two
+    // readings.
+    fn trend(&self) -> f64 {
+        if self.len < 2 {
+            return 0.0;
+        }
+        let (then, first) = self.readings[0];
+        let (now, last) = self.readings[self.len - 1];
+        (last - first) / (now - then) as f64
+    }
+}
+
+// Weight given to the latest heat cycle in the moving average of heat lead times.
+const ADAPTIVE_START_SMOOTHING: f64 = 0.25;
+
+// Most temperature readings averaged by the temperature filter.
+const MAXIMUM_TEMPERATURE_FILTER_DEPTH: usize = 64;
+
+// Moving average of the most recent temperature readings, limited to the configured depth.
+#[derive(Copy, Clone)]
+struct TemperatureFilter {
+    readings: [f64; MAXIMUM_TEMPERATURE_FILTER_DEPTH],
+    len: usize,
+    next: usize,
+    depth: usize,
+}
+
+impl TemperatureFilter {
+    const fn new() -> TemperatureFilter {
+        TemperatureFilter {
+            readings: [0.0; MAXIMUM_TEMPERATURE_FILTER_DEPTH],
+            len: 0,
+            next: 0,
+            depth: 1,
+        }
+    }
+
+    // Starts averaging afresh over the given depth.
+    fn set_depth(&mut self, depth: usize) {
+        self.len = 0;
+        self.next = 0;
+        self.depth = depth;
+    }
+
+    // Adds a reading and returns the mean of the readings held.
+    fn push(&mut self, temperature: f64) -> f64 {
+        self.readings[self.next] = temperature;
+        self.next = (self.next + 1) % self.depth;
+        self.len = (self.len + 1).min(self.depth);
+        self.readings[..self.len].iter().sum::<f64>() / self.len as f64
+    }
+}
+
+// Set point ranges and the bands around them, which are only valid in combination, so every
+// setter of one of them checks the combination it would produce.
+#[derive(Copy, Clone)]
+struct SetPointBands {
+    operating_mode: OperatingMode,
+    minimum: f64,
+    maximum: f64,
+    unoccupied_minimum: f64,
+    unoccupied_maximum: f64,
+    hysteresis: f64,
+    heat_deadband: f64,
+    cool_deadband: f64,
+}
+
+impl SetPointBands {
+    // The minimum set point must not be above the maximum in any mode, matching
+    // ThermostatConfig::validate. The hysteresis must be narrower than the set point range,
+    // otherwise neither band could be left without entering the other. In MaintainRange mode
+    // heat must also stop before cool stops, or both would be called for in between. The
+    // unoccupied set points are always maintained as a range, so they are held to the
+    // MaintainRange checks whatever the mode.
+    fn validate(&self) -> Result<(), Error> {
+        let maintain_range = self.operating_mode == OperatingMode::MaintainRange;
+        if self.minimum > self.maximum
+            || !self.fits(self.minimum, self.maximum, maintain_range)
+            || self.unoccupied_minimum >= self.unoccupied_maximum
+            || !self.fits(self.unoccupied_minimum, self.unoccupied_maximum, true)
+        {
+            return Err(Error::InvalidConfiguration);
+        }
+        Ok(())
+    }
+
+    // Whether the bands fit between a pair of set points, with heat stopping before cool stops
+    // if both are maintained.
+    fn fits(&self, minimum: f64, maximum: f64, heat_and_cool: bool) -> bool {
+        let heat_stop = minimum + self.hysteresis.max(self.heat_deadband / 2.0);
+        let cool_stop = maximum - self.hysteresis.max(self.cool_deadband / 2.0);
+        (self.hysteresis <= 0.0 || self.hysteresis < maximum - minimum)
+            && (!heat_and_cool || heat_stop <= cool_stop)
+    }
+}
+
+// Maximum number of predicted temperatures accepted by set_temperature_forecast.
+const MAXIMUM_FORECAST_LENGTH: usize = 24;
+
+// Predicted temperatures spaced interval_secs apart, the first one interval after set_at.
+#[derive(Copy, Clone)]
+struct Forecast {
+    temperatures: [f64; MAXIMUM_FORECAST_LENGTH],
+    len: usize,
+    interval_secs: u64,
+    set_at: u64,
+}
+
+impl Forecast {
+    const fn new() -> Forecast {
+        Forecast {
+            temperatures: [0.0; MAXIMUM_FORECAST_LENGTH],
+            len: 0,
+            interval_secs: 0,
+            set_at: 0,
+        }
+    }
+
+    // The next prediction still ahead of now along with the seconds until it, or None once every
+    // prediction has passed.
+    fn next(&self, now: u64) -> Option<(f64, u64)> {
+        if self.len == 0 {
+            return None;
+        }
+        let elapsed = now.saturating_sub(self.set_at);
+        let index = (elapsed / self.interval_secs) as usize;
+        if index >= self.len {
+            return None;
+        }
+        let remaining = (index as u64 + 1) * self.interval_secs - elapsed;
+        Some((self.temperatures[index], remaining))
+    }
+}
+
+// Width of the band below the minimum set temperature (or above the maximum set temperature) over
+// which modulating equipment ramps from 0% to 100% output.
+const DEFAULT_PROPORTIONAL_BAND: f64 = 2.0; // degrees C
+
+// Lowest output requested from modulating equipment while it is being called on.
+const MINIMUM_MODULATION_OUTPUT: f64 = 10.0; // percent
+
+fn is_timing_constraint(error: Error) -> bool {
+    matches!(
+        error,
+        Error::HeatMaxRunTimeConstraint
+            | Error::HeatMinRunTimeConstraint
+            | Error::HeatMinOffTimeConstraint
+            | Error::CoolMaxRunTimeConstraint
+            | Error::CoolMinRunTimeConstraint
+            | Error::CoolMinOffTimeConstraint
+            | Error::FanMaxRunTimeConstraint
+            | Error::FanMinRunTimeConstraint
+            | Error::FanMinOffTimeConstraint
+            | Error::HumidifierMinRunTimeConstraint
+            | Error::HumidifierMinOffTimeConstraint
+            | Error::DehumidifierMinRunTimeConstraint
+            | Error::DehumidifierMinOffTimeConstraint
+            | Error::ReversingValveShiftConstraint
+    )
+}
+
+fn write_timestamp<W: fmt::Write>(timestamp_secs: u64, w: &mut W) -> fmt::Result {
+    write!(
+        w,
+        "T+{}h{}m{}s",
+        timestamp_secs / 3600,
+        timestamp_secs / 60 % 60,
+        timestamp_secs % 60
+    )
+}
+
+fn format_optional_timestamp<W: fmt::Write>(timestamp_secs: Option<u64>, w: &mut W) -> fmt::Result {
+    match timestamp_secs {
+        Some(secs) => write_timestamp(secs, w),
+        None => w.write_str("never"),
+    }
+}
+
+fn proportional_output(error: f64) -> f64 {
+    if error <= 0.0 {
+        0.0
+    } else if error >= DEFAULT_PROPORTIONAL_BAND {
+        100.0
+    } else {
+        error / DEFAULT_PROPORTIONAL_BAND * 100.0
+    }
+}
 
 /// Various thermostat operating modes
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(u8)]
 pub enum OperatingMode {
     /// Maintain temperature between min and max set points
@@ -163,6 +725,45 @@ pub enum OperatingMode {
     Disabled,
     /// Ignore safety set points -- do nothing except measure
     DisabledUnsafe,
+    /// Delegate control decisions to the function registered with `set_custom_control_fn`, within
+    /// the min and max safety set points
+    Custom,
+    /// Run the fan, e.g. a solar collector circulation pump, while the source temperature exceeds
+    /// the current temperature by the differential thresholds, within the min and max safety set
+    /// points
+    Differential,
+    /// Maintain temperature between the set points returned by the interface's
+    /// `get_schedule_entry`, falling back to the min and max set points when it returns None
+    Scheduling,
+    /// Run only the fan while recirculation is requested with `set_recirculation_demand`, e.g. for
+    /// heat or energy recovery ventilators. Heat and cool are only called for outside the safety
+    /// set points.
+    RecirculationOnly,
+    /// Maintain temperature above the min set point using only auxiliary heat, e.g. resistive
+    /// backup heat when a heat pump has failed. The primary heat is never called for in this mode,
+    /// even below the min safety set point, and cool is only called for above the max safety set
+    /// point. Auxiliary heat ignores the primary heat's lockouts but applies the heat run and off
+    /// times to its own cycles.
+    EmergencyHeat,
+    /// Maintain the single temperature set with `set_target_temperature`, switching between heat
+    /// and cool as needed. Heat starts once the temperature falls below the target by half the
+    /// heat deadband and cool once it rises above the target by half the cool deadband; either
+    /// stops once the target is reached.
+    AutoChangeover,
+    /// Run only the fan to circulate air, subject to the fan timing constraints. Heat and cool are
+    /// only called for outside the safety set points.
+    FanOnly,
+    /// Maintain temperature between min and max set points with a heat pump, disengaging the
+    /// reversing valve to heat and engaging it to cool. The compressor is not started until the
+    /// valve has had time to shift after switching.
+    HeatPump,
+    /// Maintain temperature between the unoccupied min and max set points, typically wider than
+    /// the normal set points to save energy while no one is present, within the min and max
+    /// safety set points
+    Unoccupied,
+    /// Maintain temperature between the vacation set points given to `enter_vacation_mode` until
+    /// `exit_vacation_mode` restores the previous mode and set points
+    Vacation,
 }
 
 impl fmt::Display for OperatingMode {
@@ -173,26 +774,239 @@ impl fmt::Display for OperatingMode {
             OperatingMode::HeatToSetPoint => "Heat to Set Point",
             OperatingMode::Disabled => "Disabled",
             OperatingMode::DisabledUnsafe => "Disabled (Unsafe)",
+            OperatingMode::Custom => "Custom",
+            OperatingMode::Differential => "Differential",
+            OperatingMode::Scheduling => "Scheduling",
+            OperatingMode::RecirculationOnly => "Recirculation Only",
+            OperatingMode::EmergencyHeat => "Emergency Heat",
+            OperatingMode::AutoChangeover => "Auto Changeover",
+            OperatingMode::FanOnly => "Fan Only",
+            OperatingMode::HeatPump => "Heat Pump",
+            OperatingMode::Unoccupied => "Unoccupied",
+            OperatingMode::Vacation => "Vacation",
         })
     }
 }
 
-/// Thermostat state machine
+/// How quickly a building responds to heating and cooling
+///
+/// Each profile encodes a time constant in seconds. While trend anticipation is enabled with
+/// `set_trend_anticipation_enabled`, the thermostat projects the measured temperature trend this
+/// far ahead, scaled by the trend anticipation gain, and starts heating or cooling as soon as the
+/// projection leaves the set point range, so buildings with more thermal mass start earlier.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ThermalMassProfile {
+    /// Lightweight construction, 600 second time constant
+    Low,
+    /// Typical construction, 1800 second time constant
+    Medium,
+    /// Heavy masonry construction, 5400 second time constant
+    High,
+    /// Custom time constant in seconds
+    Custom(f64),
+}
+
+impl ThermalMassProfile {
+    /// Get the time constant in seconds
+    pub fn time_constant_secs(&self) -> f64 {
+        match *self {
+            ThermalMassProfile::Low => 600.0,
+            ThermalMassProfile::Medium => 1800.0,
+            ThermalMassProfile::High => 5400.0,
+            ThermalMassProfile::Custom(secs) => secs,
+        }
+    }
+}
+
+/// Reasons the thermostat may be overriding its configured operating mode
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OverrideReason {
+    /// Heating because the temperature is below the minimum safe temperature
+    FreezeProtection,
+    /// Cooling because the temperature is above the maximum safe temperature
+    HighTempProtection,
+    /// Running the fan because humidity is above the maximum safe humidity
+    MoldPrevention,
+    /// Humidifying because humidity is below the minimum safe humidity
+    DryAirProtection,
+    /// A maintenance cycle such as defrost has taken over control
+    MaintenanceMode,
+    /// Outputs are locked out by an active fault
+    EmergencyStop,
+}
+
+/// Configured operating mode together with any override currently in effect
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EffectiveMode {
+    /// Operating mode as configured
+    pub base_mode: OperatingMode,
+    /// Why the configured mode is being overridden, or None when it is not
+    pub override_reason: Option<OverrideReason>,
+}
+
+/// What the thermostat is currently doing, for display
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ThermostatState {
+    /// Heat is being called for
+    Heating,
+    /// Cool is being called for
+    Cooling,
+    /// Only the fan is being called for
+    FanOnly,
+    /// Nothing is being called for
+    Idle,
+    /// A heat timing constraint held back the last update
+    HeatConstrained,
+    /// A cool timing constraint held back the last update
+    CoolConstrained,
+}
+
+/// Geographic location of the installation
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LocationInfo {
+    /// Latitude in degrees, positive north of the equator
+    pub latitude_degrees: f32,
+    /// Longitude in degrees, positive east of the prime meridian
+    pub longitude_degrees: f32,
+    /// Altitude above sea level in meters
+    pub altitude_meters: f32,
+}
+
+/// Set points provided by a schedule for `OperatingMode::Scheduling`
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScheduleEntry {
+    /// Temperature in degrees Celsius below which heat is called for
+    pub heat_set_point: f64,
+    /// Temperature in degrees Celsius above which cool is called for
+    pub cool_set_point: f64,
+}
+
+/// Outputs requested by a custom control function
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ControlDecision {
+    /// Whether heat should be called for
+    pub call_heat: bool,
+    /// Whether cool should be called for
+    pub call_cool: bool,
+    /// Whether fan should be called for
+    pub call_fan: bool,
+}
+
+/// Possible misconfiguration of a timing constraint
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConstraintWarning {
+    /// Constraint that appears misconfigured
+    pub constraint: Error,
+    /// Human-readable explanation of the concern
+    pub description: &'static str,
+    /// Recommended minimum value for the constraint, in seconds
+    pub recommended_minimum_secs: u32,
+}
+
+/// Actuator change expected next
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ExpectedEventType {
+    /// Heat will be called for
+    HeatWillStart,
+    /// Heat will no longer be called for
+    HeatWillStop,
+    /// Cool will be called for
+    CoolWillStart,
+    /// Cool will no longer be called for
+    CoolWillStop,
+}
+
+/// Estimate of the next actuator change
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NextEventEstimate {
+    /// Seconds until the change, if the temperature trend is heading toward it
+    pub estimated_secs_from_now: Option<u64>,
+    /// Change expected next, if any
+    pub event_type: Option<ExpectedEventType>,
+}
+
+// Compressors short-cycle and wear quickly unless pressures equalize between cycles.
+const RECOMMENDED_MINIMUM_COMPRESSOR_OFF_SECS: u32 = 180;
+// Burners and heat exchangers condense and corrode when run for very short cycles.
+const RECOMMENDED_MINIMUM_HEAT_RUN_SECS: u32 = 120;
+
+/// Thermostat state machine borrowing its interface
+///
+/// The const parameter `N` sets how many events are retained in the event log. `I` is the
+/// interface reference, a trait object by default. `Thermostat::new_static(&interface)` holds a
+/// reference to the concrete interface type instead, so interface calls are statically dispatched
+/// and can be inlined.
+pub type Thermostat<
+    'a,
+    const N: usize = DEFAULT_EVENT_LOG_CAPACITY,
+    I = &'a dyn ThermostatInterface,
+> = ThermostatCore<'a, I, N>;
+
+/// Thermostat state machine calling its interface through a trait object
+///
+/// Useful where the interface type is not known statically, or to avoid monomorphizing the
+/// thermostat for each interface type. The const parameter `N` sets how many events are retained
+/// in the event log.
+pub type DynThermostat<'a, const N: usize = DEFAULT_EVENT_LOG_CAPACITY> =
+    ThermostatCore<'a, &'a dyn ThermostatInterface, N>;
+
+/// Thermostat state machine owning its interface
+///
+/// Useful when the thermostat is stored alongside its interface, e.g. in a struct or a static,
+/// where borrowing the interface would make the struct self-referential. The lifetime `'a` only
+/// bounds optional persistent storage and event handler.
+pub type OwnedThermostat<'a, I, const N: usize = DEFAULT_EVENT_LOG_CAPACITY> =
+    ThermostatCore<'a, I, N>;
+
+/// State machine shared by `Thermostat`, `DynThermostat`, and `OwnedThermostat`
+///
+/// `I` is how the interface is held: a reference to it for `Thermostat`, a
+/// `&dyn ThermostatInterface` for `DynThermostat`, or the interface itself for
+/// `OwnedThermostat`. The const parameter `N` sets how many events are retained in the event log.
+#[derive(Copy, Clone)]
+pub struct ThermostatCore<'a, I, const N: usize = DEFAULT_EVENT_LOG_CAPACITY> {
+    state: CoreState<'a, N>,
+    interface: I,
+    post_update_hook: Option<fn(&ThermostatCore<'a, I, N>)>,
+}
+
+// Everything but the interface and the post-update hook typed by it, so `simulate` can copy a
+// thermostat onto its own interface.
 #[derive(Copy, Clone)]
-pub struct Thermostat<'a> {
+struct CoreState<'a, const N: usize> {
     operating_mode: OperatingMode,
     minimum_safe_temperature: f64,
     maximum_safe_temperature: f64,
     minimum_set_temperature: f64,
     maximum_set_temperature: f64,
+    target_temperature: f64,
+    unoccupied_minimum_set_temperature: f64,
+    unoccupied_maximum_set_temperature: f64,
+    // mode to return to when the space is occupied again after `set_occupancy(false)`
+    occupied_operating_mode: Option<OperatingMode>,
     current_temperature: f64,
-    interface: &'a ThermostatInterface,
+    last_raw_temperature: f64,
+    temperature_filter: TemperatureFilter,
+    initial_temperature: f64,
+    current_humidity: f64,
     last_call_for_heat_start: Option<u64>,
     last_call_for_heat_end: Option<u64>,
     last_call_for_cool_start: Option<u64>,
     last_call_for_cool_end: Option<u64>,
     last_call_for_fan_start: Option<u64>,
     last_call_for_fan_end: Option<u64>,
+    last_call_for_aux_heat_start: Option<u64>,
+    last_call_for_aux_heat_end: Option<u64>,
     minimum_heat_run_secs: u32,
     maximum_heat_run_secs: u32,
     minimum_heat_off_secs: u32,
@@ -202,6 +1016,106 @@ pub struct Thermostat<'a> {
     minimum_fan_run_secs: u32,
     maximum_fan_run_secs: u32,
     minimum_fan_off_secs: u32,
+    event_log: EventLog<N>,
+    custom_control_fn: Option<fn(f64, f64) -> ControlDecision>,
+    forecast: Forecast,
+    display_unit: TemperatureUnit,
+    initialization_time: u64,
+    location: Option<LocationInfo>,
+    config_hash: Option<u32>,
+    temperature_precision: u8,
+    reset_hardware_before_fault_clear: bool,
+    safe_temperatures_locked: bool,
+    auto_recovery_enabled: bool,
+    auto_recovery_temperature_margin: f64,
+    hysteresis: f64,
+    heat_deadband: f64,
+    cool_deadband: f64,
+    high_temperature_alarm: Option<f64>,
+    low_temperature_alarm: Option<f64>,
+    alarm_active: bool,
+    safety_limit_breached: bool,
+    statistics: OperationalStatistics,
+    runtime_stats: RuntimeStats,
+    filter_runtime_secs: u64,
+    filter_reset_at: u64,
+    filter_alert_secs: u64,
+    filter_alert_notified: bool,
+    heat_cycle_history: CycleHistory,
+    cool_cycle_history: CycleHistory,
+    rated_heat_power_kw: f64,
+    rated_cool_power_kw: f64,
+    thermal_capacity_kj_per_degree: Option<f64>,
+    outdoor_temperature: Option<f64>,
+    heat_pump_lockout_temperature: Option<f64>,
+    cool_start_temperature: f64,
+    total_cool_temperature_drop: f64,
+    last_transition: Option<Transition>,
+    timing_constrained: bool,
+    last_error_detail: Option<ConstraintError>,
+    last_error: Option<Error>,
+    storage: Option<&'a dyn ThermostatStorage>,
+    event_handler: Option<&'a dyn ThermostatEventHandler>,
+    measurement_provider: Option<&'a dyn MeasurementProvider>,
+    reversing_valve_switched_at: Option<u64>,
+    temperature_history: TemperatureHistory,
+    temperature_trend: f64,
+    humidity_history: HumidityHistory,
+    fault: Option<Error>,
+    maximum_heat_swing: f64,
+    heat_start_temperature: f64,
+    thermal_mass: ThermalMassProfile,
+    trend_anticipation_enabled: bool,
+    trend_anticipation_gain: f64,
+    temperature_ramp_rate: f64,
+    adaptive_start_enabled: bool,
+    adaptive_heat_lead_secs: u64,
+    adaptive_heat_cycle: Option<u64>,
+    schedule: Option<Schedule>,
+    applied_schedule_slot: Option<usize>,
+    schedule_override: Option<ScheduleOverride>,
+    vacation_resume: Option<VacationResume>,
+    ramped_minimum_set_temperature: Option<f64>,
+    ramped_maximum_set_temperature: Option<f64>,
+    set_point_ramp_updated_at: Option<u64>,
+    readings_received: u32,
+    last_reading_timestamp: Option<u64>,
+    occupancy_detected: bool,
+    #[cfg(feature = "testing")]
+    injected_temperature: Option<(f64, u64)>,
+    source_temperature: f64,
+    recirculation_demand: bool,
+    minimum_safe_humidity: f64,
+    maximum_safe_humidity: f64,
+    humidity_control_enabled: bool,
+    target_humidity: f64,
+    humidity_deadband: f64,
+    minimum_humidifier_run_secs: u32,
+    minimum_humidifier_off_secs: u32,
+    minimum_dehumidifier_run_secs: u32,
+    minimum_dehumidifier_off_secs: u32,
+    last_call_for_humidifier_start: Option<u64>,
+    last_call_for_humidifier_end: Option<u64>,
+    last_call_for_dehumidifier_start: Option<u64>,
+    last_call_for_dehumidifier_end: Option<u64>,
+    differential_on_threshold: f64,
+    differential_off_threshold: f64,
+    #[cfg(feature = "defrost")]
+    defrost_interval_hours: u8,
+    #[cfg(feature = "defrost")]
+    defrost_duration_secs: u32,
+    #[cfg(feature = "defrost")]
+    defrost_max_temperature: f64,
+    #[cfg(feature = "defrost")]
+    defrost_start: Option<u64>,
+    #[cfg(feature = "defrost")]
+    cumulative_cool_runtime_since_defrost: u64,
+}
+
+/// Source of temperature readings polled by `tick`
+pub trait MeasurementProvider {
+    /// takes a new temperature reading in degrees Celsius
+    fn measure(&self) -> Result<f64, Error>;
 }
 
 /// Wrapper for physical interface controls
@@ -228,252 +1142,3600 @@ pub trait ThermostatInterface {
     fn stop_call_for_fan(&self) -> Result<(), Error>;
     /// gets seconds since system start
     fn get_seconds(&self) -> Result<u64, Error>;
-}
 
-impl<'a> Thermostat<'a> {
-    /// Create a new thermostat using the provided interface
-    pub fn new(interface: &ThermostatInterface) -> Thermostat {
-        Thermostat {
-            operating_mode: DEFAULT_OPERATING_MODE,
-            minimum_safe_temperature: DEFAULT_MINIMUM_SAFE_TEMPERATURE,
-            maximum_safe_temperature: DEFAULT_MAXIMUM_SAFE_TEMPERATURE,
-            minimum_set_temperature: DEFAULT_MINIMUM_SAFE_TEMPERATURE,
-            maximum_set_temperature: DEFAULT_MAXIMUM_SAFE_TEMPERATURE,
-            current_temperature: DEFAULT_CURRENT_TEMPERATURE,
-            interface,
-            last_call_for_heat_start: None,
-            last_call_for_heat_end: None,
-            last_call_for_cool_start: None,
-            last_call_for_cool_end: None,
-            last_call_for_fan_start: None,
-            last_call_for_fan_end: None,
-            minimum_heat_run_secs: 600,
-            maximum_heat_run_secs: 3600,
-            minimum_heat_off_secs: 300,
-            minimum_cool_run_secs: 600,
-            maximum_cool_run_secs: 3600,
-            minimum_cool_off_secs: 300,
-            minimum_fan_run_secs: 300,
-            maximum_fan_run_secs: 43200,
-            minimum_fan_off_secs: 300,
-        }
+    /// sets heat output as a percentage of full capacity for modulating equipment
+    ///
+    /// Called after each call for heat, and with zero after each stop. An Err result marks the
+    /// output as binary, leaving on/off control only. The default implementation returns an Err
+    /// result.
+    fn set_heat_modulation(&self, _percent: f64) -> Result<(), Error> {
+        Err(Error::HandlerFailed)
+    }
+    /// sets cool output as a percentage of full capacity for modulating equipment
+    ///
+    /// Called after each call for cool, and with zero after each stop. An Err result marks the
+    /// output as binary, leaving on/off control only. The default implementation returns an Err
+    /// result.
+    fn set_cool_modulation(&self, _percent: f64) -> Result<(), Error> {
+        Err(Error::HandlerFailed)
     }
 
-    /// Change the current operating mode.
+    /// checks whether a fan output is connected
     ///
-    /// Will return an Err result if the specified operating mode is incompatible with the current
-    /// configuration.
-    pub fn set_operating_mode(&mut self, operating_mode: OperatingMode) -> Result<(), Error> {
-        self.operating_mode = operating_mode;
-        Ok(())
+    /// The default implementation returns true.
+    fn has_fan(&self) -> bool {
+        true
     }
-    /// Get the current operating mode.
-    pub fn get_operating_mode(&self) -> OperatingMode {
-        self.operating_mode
+
+    /// gets the scheduled set points for a time of day
+    ///
+    /// Used by `OperatingMode::Scheduling` on every update. The hour and minute are derived from
+    /// `get_seconds` modulo one day, so clocks counting from midnight give wall-clock times. The
+    /// default implementation returns Ok(None), keeping the configured set points.
+    fn get_schedule_entry(&self, _hour: u8, _minute: u8) -> Result<Option<ScheduleEntry>, Error> {
+        Ok(None)
     }
 
-    /// Change the minimum safe temperature.
+    /// resets the hardware, e.g. by toggling power or reinitializing buses, to recover from a fault
+    ///
+    /// Called when the thermostat enters a fault state and, unless disabled, before an active
+    /// fault is cleared. The default implementation returns an Err result.
+    fn reset_hardware(&self) -> Result<(), Error> {
+        Err(Error::HandlerFailed)
+    }
+
+    /// sounds an external alarm, e.g. a buzzer or notification relay
     ///
-    /// If the maximum set temperature is higher than the specified maximum safe temperature, the
-    /// maximum set temperature will be automatically adjusted to match.
+    /// Called when the temperature crosses an alarm threshold. The default implementation returns
+    /// an Err result.
+    fn trigger_alarm(&self) -> Result<(), Error> {
+        Err(Error::HandlerFailed)
+    }
+    /// silences the external alarm
     ///
-    /// An Err Result is returned if the specified temperature is not within the bounds of the
-    /// minimum and maximum safe temperatures.
-    pub fn set_maximum_safe_temperature(&mut self, temperature: f64) -> Result<(), Error> {
-        self.maximum_safe_temperature = temperature;
-        Ok(())
+    /// Called when the temperature returns within the alarm thresholds. The default implementation
+    /// returns an Err result.
+    fn clear_alarm(&self) -> Result<(), Error> {
+        Err(Error::HandlerFailed)
     }
-    /// Get the current maximum safe temperature.
-    pub fn get_maximum_safe_temperature(&self) -> f64 {
-        self.maximum_safe_temperature
+
+    /// gets the geographic location of the installation
+    ///
+    /// Queried once when the thermostat is created. The location is not yet used by the control
+    /// logic. The default implementation returns Ok(None).
+    fn get_location_info(&self) -> Result<Option<LocationInfo>, Error> {
+        Ok(None)
     }
 
-    /// Change the minimum safe temperature.
+    /// returns true if we are currently calling for auxiliary heat
     ///
-    /// If the minimum set temperature is lower than the specified minimum safe temperature, the
-    /// minimum set temperature will be automatically adjusted to match.
+    /// The default implementation returns Ok(false).
+    fn calling_for_aux_heat(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+    /// calls for auxiliary heat, e.g. resistive backup heat strips
     ///
-    /// An Err Result is returned if the specified temperature is not within the bounds of the
-    /// minimum and maximum safe temperatures.
-    pub fn set_minimum_safe_temperature(&mut self, temperature: f64) -> Result<(), Error> {
-        self.minimum_safe_temperature = temperature;
-        Ok(())
+    /// The default implementation returns an Err result. Implement this to use
+    /// `OperatingMode::EmergencyHeat`.
+    fn call_for_aux_heat(&self) -> Result<(), Error> {
+        Err(Error::HandlerFailed)
     }
-    /// Get the current minimum safe temperature
-    pub fn get_minimum_safe_temperature(&self) -> f64 {
-        self.minimum_safe_temperature
+    /// stops call for auxiliary heat
+    ///
+    /// The default implementation returns an Err result. Implement this to use
+    /// `OperatingMode::EmergencyHeat`.
+    fn stop_call_for_aux_heat(&self) -> Result<(), Error> {
+        Err(Error::HandlerFailed)
     }
 
-    /// Change the maximum set temperature.
+    /// returns true if we are currently calling for the humidifier
     ///
-    /// If the minimum set temperature is higher than the specified maximum set temperature, the
-    /// minimum set temperature will be automatically adjusted to match.
+    /// The default implementation returns Ok(false).
+    fn calling_for_humidifier(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+    /// calls for the humidifier, e.g. on the supply plenum
     ///
-    /// An Err Result is returned if the specified temperature is not within the bounds of the
-    /// minimum and maximum safe temperatures.
-    pub fn set_maximum_set_temperature(&mut self, temperature: f64) -> Result<(), Error> {
-        self.maximum_set_temperature = temperature;
+    /// The default implementation does nothing.
+    fn call_for_humidifier(&self) -> Result<(), Error> {
         Ok(())
     }
-    /// Get the current maximum set temperature.
-    pub fn get_maximum_set_temperature(&self) -> f64 {
-        self.maximum_set_temperature
+    /// stops call for the humidifier
+    ///
+    /// The default implementation does nothing.
+    fn stop_call_for_humidifier(&self) -> Result<(), Error> {
+        Ok(())
     }
 
-    /// Change the minimum set temperature.
+    /// checks whether a dedicated dehumidifier output is connected
+    ///
+    /// Humidity control dehumidifies with the dehumidifier output if so, and by calling for cool
+    /// otherwise. The default implementation returns false.
+    fn has_dehumidifier(&self) -> bool {
+        false
+    }
+    /// returns true if we are currently calling for the dehumidifier
     ///
-    /// If the minimum set temperature is higher than the specified maximum set temperature, the
-    /// maximum set temperature will be automatically adjusted to match.
+    /// The default implementation returns Ok(false).
+    fn calling_for_dehumidifier(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+    /// calls for the dehumidifier
     ///
-    /// An Err Result is returned if the specified temperature is not within the bounds of the
-    /// minimum and maximum safe temperatures.
-    pub fn set_minimum_set_temperature(&mut self, temperature: f64) -> Result<(), Error> {
-        self.minimum_set_temperature = temperature;
+    /// The default implementation does nothing.
+    fn call_for_dehumidifier(&self) -> Result<(), Error> {
         Ok(())
     }
-    /// Get the current minimum set temperature.
-    pub fn get_minimum_set_temperature(&self) -> f64 {
-        self.minimum_set_temperature
+    /// stops call for the dehumidifier
+    ///
+    /// The default implementation does nothing.
+    fn stop_call_for_dehumidifier(&self) -> Result<(), Error> {
+        Ok(())
     }
 
-    /// Get the current temperature as known to the thermostat
-    pub fn get_current_temperature(&self) -> f64 {
-        self.current_temperature
+    /// returns true if the heat pump reversing valve is engaged for cooling
+    ///
+    /// The default implementation returns Ok(false).
+    fn calling_for_reversing_valve(&self) -> Result<bool, Error> {
+        Ok(false)
     }
-
-    fn start_heat(&mut self) -> Result<(), Error> {
-        if !self.interface.calling_for_heat()? {
-            let now = self.interface.get_seconds()?;
-            if now - self.last_call_for_heat_end.unwrap_or(0) >= self.minimum_heat_off_secs as u64 {
-                self.interface.call_for_heat()?; // we have been off long enough to start
-                self.last_call_for_heat_start = Some(now);
-                Ok(())
-            } else {
-                Err(Error::HeatMinOffTimeConstraint) // we haven't been off long enough
-            }
-        } else {
-            Ok(()) // we're already heating
-        }
+    /// engages the heat pump reversing valve for cooling
+    ///
+    /// The default implementation returns an Err result. Implement this to use
+    /// `OperatingMode::HeatPump`.
+    fn engage_reversing_valve(&self) -> Result<(), Error> {
+        Err(Error::HandlerFailed)
+    }
+    /// disengages the heat pump reversing valve for heating
+    ///
+    /// The default implementation returns an Err result. Implement this to use
+    /// `OperatingMode::HeatPump`.
+    fn disengage_reversing_valve(&self) -> Result<(), Error> {
+        Err(Error::HandlerFailed)
     }
 
-    fn stop_heat(&mut self) -> Result<(), Error> {
-        if self.interface.calling_for_heat()? {
-            let now = self.interface.get_seconds()?;
-            if now - self.last_call_for_heat_start.unwrap_or(0) >= self.minimum_heat_run_secs as u64
-            {
-                self.interface.stop_call_for_heat()?; // we have been running long enough to shut down
-                self.last_call_for_heat_end = Some(now);
-                Ok(())
-            } else {
-                Err(Error::HeatMinRunTimeConstraint) // we haven't been running long enough
-            }
-        } else {
-            Ok(()) // no current call for heat
-        }
+    /// takes a new temperature reading, if one is available
+    ///
+    /// Polled by `ThermostatFuture`. The default implementation returns Ok(None), for applications
+    /// that pass readings to `set_current_temperature` themselves.
+    fn measure(&self) -> Result<Option<f64>, Error> {
+        Ok(None)
     }
 
-    fn start_cool(&mut self) -> Result<(), Error> {
-        if !self.interface.calling_for_cool()? {
-            let now = self.interface.get_seconds()?;
-            if now - self.last_call_for_cool_end.unwrap_or(0) >= self.minimum_cool_off_secs as u64 {
-                self.interface.call_for_cool()?; // we have been off long enough to start
-                self.last_call_for_cool_start = Some(now);
-                Ok(())
-            } else {
-                Err(Error::CoolMinOffTimeConstraint) // we haven't been off long enough
-            }
-        } else {
-            Ok(()) // we're already cooling
-        }
+    /// reads the building occupancy input, e.g. a PIR sensor or building automation contact
+    ///
+    /// Polled with every temperature reading, and a change is passed to
+    /// `ThermostatCore::set_occupancy` to set back to the `Unoccupied` mode and return. The
+    /// default implementation returns Ok(true), so the space is always considered occupied.
+    fn get_occupancy_input(&self) -> Result<bool, Error> {
+        Ok(true)
     }
 
-    fn stop_cool(&mut self) -> Result<(), Error> {
-        if self.interface.calling_for_cool()? {
+    /// calls for defrost heat on the evaporator coil
+    ///
+    /// The default implementation returns an Err result. Implement this to use defrost cycles.
+    #[cfg(feature = "defrost")]
+    fn call_for_defrost_heat(&self) -> Result<(), Error> {
+        Err(Error::HandlerFailed)
+    }
+    /// stops call for defrost heat on the evaporator coil
+    ///
+    /// The default implementation returns an Err result. Implement this to use defrost cycles.
+    #[cfg(feature = "defrost")]
+    fn stop_call_for_defrost_heat(&self) -> Result<(), Error> {
+        Err(Error::HandlerFailed)
+    }
+}
+
+impl<T: ThermostatInterface + ?Sized> ThermostatInterface for &T {
+    fn calling_for_heat(&self) -> Result<bool, Error> {
+        (**self).calling_for_heat()
+    }
+    fn call_for_heat(&self) -> Result<(), Error> {
+        (**self).call_for_heat()
+    }
+    fn stop_call_for_heat(&self) -> Result<(), Error> {
+        (**self).stop_call_for_heat()
+    }
+    fn calling_for_cool(&self) -> Result<bool, Error> {
+        (**self).calling_for_cool()
+    }
+    fn call_for_cool(&self) -> Result<(), Error> {
+        (**self).call_for_cool()
+    }
+    fn stop_call_for_cool(&self) -> Result<(), Error> {
+        (**self).stop_call_for_cool()
+    }
+    fn calling_for_fan(&self) -> Result<bool, Error> {
+        (**self).calling_for_fan()
+    }
+    fn call_for_fan(&self) -> Result<(), Error> {
+        (**self).call_for_fan()
+    }
+    fn stop_call_for_fan(&self) -> Result<(), Error> {
+        (**self).stop_call_for_fan()
+    }
+    fn get_seconds(&self) -> Result<u64, Error> {
+        (**self).get_seconds()
+    }
+    fn set_heat_modulation(&self, percent: f64) -> Result<(), Error> {
+        (**self).set_heat_modulation(percent)
+    }
+    fn set_cool_modulation(&self, percent: f64) -> Result<(), Error> {
+        (**self).set_cool_modulation(percent)
+    }
+    fn has_fan(&self) -> bool {
+        (**self).has_fan()
+    }
+    fn get_schedule_entry(&self, hour: u8, minute: u8) -> Result<Option<ScheduleEntry>, Error> {
+        (**self).get_schedule_entry(hour, minute)
+    }
+    fn reset_hardware(&self) -> Result<(), Error> {
+        (**self).reset_hardware()
+    }
+    fn trigger_alarm(&self) -> Result<(), Error> {
+        (**self).trigger_alarm()
+    }
+    fn clear_alarm(&self) -> Result<(), Error> {
+        (**self).clear_alarm()
+    }
+    fn get_location_info(&self) -> Result<Option<LocationInfo>, Error> {
+        (**self).get_location_info()
+    }
+    fn calling_for_aux_heat(&self) -> Result<bool, Error> {
+        (**self).calling_for_aux_heat()
+    }
+    fn call_for_aux_heat(&self) -> Result<(), Error> {
+        (**self).call_for_aux_heat()
+    }
+    fn stop_call_for_aux_heat(&self) -> Result<(), Error> {
+        (**self).stop_call_for_aux_heat()
+    }
+    fn calling_for_humidifier(&self) -> Result<bool, Error> {
+        (**self).calling_for_humidifier()
+    }
+    fn call_for_humidifier(&self) -> Result<(), Error> {
+        (**self).call_for_humidifier()
+    }
+    fn stop_call_for_humidifier(&self) -> Result<(), Error> {
+        (**self).stop_call_for_humidifier()
+    }
+    fn has_dehumidifier(&self) -> bool {
+        (**self).has_dehumidifier()
+    }
+    fn calling_for_dehumidifier(&self) -> Result<bool, Error> {
+        (**self).calling_for_dehumidifier()
+    }
+    fn call_for_dehumidifier(&self) -> Result<(), Error> {
+        (**self).call_for_dehumidifier()
+    }
+    fn stop_call_for_dehumidifier(&self) -> Result<(), Error> {
+        (**self).stop_call_for_dehumidifier()
+    }
+    fn calling_for_reversing_valve(&self) -> Result<bool, Error> {
+        (**self).calling_for_reversing_valve()
+    }
+    fn engage_reversing_valve(&self) -> Result<(), Error> {
+        (**self).engage_reversing_valve()
+    }
+    fn disengage_reversing_valve(&self) -> Result<(), Error> {
+        (**self).disengage_reversing_valve()
+    }
+    fn measure(&self) -> Result<Option<f64>, Error> {
+        (**self).measure()
+    }
+    fn get_occupancy_input(&self) -> Result<bool, Error> {
+        (**self).get_occupancy_input()
+    }
+    #[cfg(feature = "defrost")]
+    fn call_for_defrost_heat(&self) -> Result<(), Error> {
+        (**self).call_for_defrost_heat()
+    }
+    #[cfg(feature = "defrost")]
+    fn stop_call_for_defrost_heat(&self) -> Result<(), Error> {
+        (**self).stop_call_for_defrost_heat()
+    }
+}
+
+impl<'a> DynThermostat<'a> {
+    /// Write a timestamp in seconds since system start as `T+{hours}h{minutes}m{seconds}s`.
+    pub fn format_timestamp<W: fmt::Write>(timestamp_secs: u64, w: &mut W) -> fmt::Result {
+        write_timestamp(timestamp_secs, w)
+    }
+
+    /// Create a new thermostat using the provided interface
+    pub fn new(interface: &'a dyn ThermostatInterface) -> DynThermostat<'a> {
+        ThermostatCore::with_event_log_capacity(interface)
+    }
+
+    /// Create a new thermostat using the provided interface and persistent storage
+    ///
+    /// Any configuration saved in storage is validated and applied. An Err Result is returned if
+    /// loading fails or the saved configuration is invalid.
+    pub fn new_with_storage(
+        interface: &'a dyn ThermostatInterface,
+        storage: &'a dyn ThermostatStorage,
+    ) -> Result<DynThermostat<'a>, Error> {
+        let mut thermostat = DynThermostat::new(interface);
+        thermostat.state.storage = Some(storage);
+        if let Some(config) = storage.load()? {
+            config.validate()?;
+            thermostat.apply_validated_config(&config)?;
+        }
+        Ok(thermostat)
+    }
+}
+
+impl<'a, T: ThermostatInterface + ?Sized> Thermostat<'a, DEFAULT_EVENT_LOG_CAPACITY, &'a T> {
+    /// Create a new thermostat holding a reference to the concrete interface type
+    ///
+    /// Unlike `new`, interface calls are statically dispatched and can be inlined.
+    pub fn new_static(interface: &'a T) -> Thermostat<'a, DEFAULT_EVENT_LOG_CAPACITY, &'a T> {
+        ThermostatCore::with_event_log_capacity(interface)
+    }
+}
+
+impl<'a, I: ThermostatInterface> OwnedThermostat<'a, I> {
+    /// Create a new thermostat taking ownership of the provided interface
+    pub fn new_owned(interface: I) -> OwnedThermostat<'a, I> {
+        ThermostatCore::with_event_log_capacity(interface)
+    }
+}
+
+impl<'a, I: ThermostatInterface, const N: usize> ThermostatCore<'a, I, N> {
+    /// Create a new thermostat using the provided interface, retaining the last `N` events in its
+    /// event log
+    ///
+    /// The interface clock is read once to record the initialization time used by
+    /// `get_uptime_secs`. If the clock is unavailable, system start is assumed.
+    pub fn with_event_log_capacity(interface: I) -> ThermostatCore<'a, I, N> {
+        let initialization_time = interface.get_seconds().unwrap_or(0);
+        let location = interface.get_location_info().unwrap_or(None);
+        let mut thermostat = ThermostatCore::const_new(interface);
+        thermostat.state.initialization_time = initialization_time;
+        thermostat.state.location = location;
+        thermostat
+    }
+
+    /// Create a new thermostat with the default configuration in a const context, e.g. to
+    /// initialize a `static`
+    ///
+    /// Unlike `with_event_log_capacity`, the interface is not consulted: system start is assumed
+    /// as the initialization time, and no location is known.
+    pub const fn const_new(interface: I) -> ThermostatCore<'a, I, N> {
+        let config = DEFAULT_CONFIG;
+        ThermostatCore {
+            state: CoreState {
+                operating_mode: config.operating_mode,
+                minimum_safe_temperature: config.minimum_safe_temperature,
+                maximum_safe_temperature: config.maximum_safe_temperature,
+                minimum_set_temperature: config.minimum_set_temperature,
+                maximum_set_temperature: config.maximum_set_temperature,
+                target_temperature: config.target_temperature,
+                unoccupied_minimum_set_temperature: config.unoccupied_minimum_set_temperature,
+                unoccupied_maximum_set_temperature: config.unoccupied_maximum_set_temperature,
+                occupied_operating_mode: None,
+                current_temperature: config.initial_temperature,
+                last_raw_temperature: config.initial_temperature,
+                temperature_filter: TemperatureFilter::new(),
+                initial_temperature: config.initial_temperature,
+                current_humidity: DEFAULT_CURRENT_HUMIDITY,
+                last_call_for_heat_start: None,
+                last_call_for_heat_end: None,
+                last_call_for_cool_start: None,
+                last_call_for_cool_end: None,
+                last_call_for_fan_start: None,
+                last_call_for_fan_end: None,
+                last_call_for_aux_heat_start: None,
+                last_call_for_aux_heat_end: None,
+                minimum_heat_run_secs: config.minimum_heat_run_secs,
+                maximum_heat_run_secs: config.maximum_heat_run_secs,
+                minimum_heat_off_secs: config.minimum_heat_off_secs,
+                minimum_cool_run_secs: config.minimum_cool_run_secs,
+                maximum_cool_run_secs: config.maximum_cool_run_secs,
+                minimum_cool_off_secs: config.minimum_cool_off_secs,
+                minimum_fan_run_secs: config.minimum_fan_run_secs,
+                maximum_fan_run_secs: config.maximum_fan_run_secs,
+                minimum_fan_off_secs: config.minimum_fan_off_secs,
+                event_log: EventLog::new(),
+                custom_control_fn: None,
+                forecast: Forecast::new(),
+                display_unit: DEFAULT_DISPLAY_UNIT,
+                initialization_time: 0,
+                location: None,
+                config_hash: None,
+                temperature_precision: DEFAULT_TEMPERATURE_PRECISION,
+                reset_hardware_before_fault_clear: true,
+                safe_temperatures_locked: false,
+                auto_recovery_enabled: false,
+                auto_recovery_temperature_margin: DEFAULT_AUTO_RECOVERY_TEMPERATURE_MARGIN,
+                hysteresis: config.hysteresis,
+                heat_deadband: config.heat_deadband,
+                cool_deadband: config.cool_deadband,
+                high_temperature_alarm: None,
+                low_temperature_alarm: None,
+                alarm_active: false,
+                safety_limit_breached: false,
+                statistics: OperationalStatistics::new(),
+                runtime_stats: RuntimeStats::new(),
+                filter_runtime_secs: 0,
+                filter_reset_at: 0,
+                filter_alert_secs: DEFAULT_FILTER_ALERT_SECS,
+                filter_alert_notified: false,
+                heat_cycle_history: CycleHistory::new(),
+                cool_cycle_history: CycleHistory::new(),
+                rated_heat_power_kw: 0.0,
+                rated_cool_power_kw: 0.0,
+                thermal_capacity_kj_per_degree: None,
+                outdoor_temperature: None,
+                heat_pump_lockout_temperature: None,
+                cool_start_temperature: DEFAULT_CURRENT_TEMPERATURE,
+                total_cool_temperature_drop: 0.0,
+                last_transition: None,
+                timing_constrained: false,
+                last_error_detail: None,
+                last_error: None,
+                storage: None,
+                event_handler: None,
+                measurement_provider: None,
+                reversing_valve_switched_at: None,
+                temperature_history: TemperatureHistory::new(),
+                temperature_trend: 0.0,
+                humidity_history: HumidityHistory::new(),
+                fault: None,
+                maximum_heat_swing: f64::INFINITY,
+                heat_start_temperature: DEFAULT_CURRENT_TEMPERATURE,
+                thermal_mass: ThermalMassProfile::Medium,
+                trend_anticipation_enabled: false,
+                trend_anticipation_gain: DEFAULT_TREND_ANTICIPATION_GAIN,
+                temperature_ramp_rate: 0.0,
+                adaptive_start_enabled: false,
+                adaptive_heat_lead_secs: 0,
+                adaptive_heat_cycle: None,
+                schedule: None,
+                applied_schedule_slot: None,
+                schedule_override: None,
+                vacation_resume: None,
+                ramped_minimum_set_temperature: None,
+                ramped_maximum_set_temperature: None,
+                set_point_ramp_updated_at: None,
+                readings_received: 0,
+                last_reading_timestamp: None,
+                occupancy_detected: true,
+                #[cfg(feature = "testing")]
+                injected_temperature: None,
+                source_temperature: DEFAULT_CURRENT_TEMPERATURE,
+                recirculation_demand: false,
+                minimum_safe_humidity: DEFAULT_MINIMUM_SAFE_HUMIDITY,
+                maximum_safe_humidity: DEFAULT_MAXIMUM_SAFE_HUMIDITY,
+                humidity_control_enabled: false,
+                target_humidity: DEFAULT_TARGET_HUMIDITY,
+                humidity_deadband: DEFAULT_HUMIDITY_DEADBAND,
+                minimum_humidifier_run_secs: DEFAULT_MINIMUM_HUMIDIFIER_RUN_SECS,
+                minimum_humidifier_off_secs: DEFAULT_MINIMUM_HUMIDIFIER_OFF_SECS,
+                minimum_dehumidifier_run_secs: DEFAULT_MINIMUM_DEHUMIDIFIER_RUN_SECS,
+                minimum_dehumidifier_off_secs: DEFAULT_MINIMUM_DEHUMIDIFIER_OFF_SECS,
+                last_call_for_humidifier_start: None,
+                last_call_for_humidifier_end: None,
+                last_call_for_dehumidifier_start: None,
+                last_call_for_dehumidifier_end: None,
+                differential_on_threshold: DEFAULT_DIFFERENTIAL_ON_THRESHOLD,
+                differential_off_threshold: DEFAULT_DIFFERENTIAL_OFF_THRESHOLD,
+                #[cfg(feature = "defrost")]
+                defrost_interval_hours: 0,
+                #[cfg(feature = "defrost")]
+                defrost_duration_secs: DEFAULT_DEFROST_DURATION_SECS,
+                #[cfg(feature = "defrost")]
+                defrost_max_temperature: DEFAULT_DEFROST_MAX_TEMPERATURE,
+                #[cfg(feature = "defrost")]
+                defrost_start: None,
+                #[cfg(feature = "defrost")]
+                cumulative_cool_runtime_since_defrost: 0,
+            },
+            interface,
+            post_update_hook: None,
+        }
+    }
+
+    /// Save the current configuration to persistent storage.
+    ///
+    /// An Err Result is returned if the thermostat was not created with storage or saving fails.
+    pub fn save_config(&self) -> Result<(), Error> {
+        match self.state.storage {
+            Some(storage) => storage.save(&self.get_config()),
+            None => Err(Error::InvalidConfiguration),
+        }
+    }
+
+    /// Get the current configuration, e.g. to persist it and restore it with `apply_config` after
+    /// a power cycle. In `Vacation` mode it holds the operating mode and set points that
+    /// `exit_vacation_mode` would restore.
+    pub fn get_config(&self) -> ThermostatConfig {
+        let (operating_mode, minimum_set_temperature, maximum_set_temperature) =
+            self.regular_settings();
+        ThermostatConfig {
+            operating_mode,
+            minimum_safe_temperature: self.state.minimum_safe_temperature,
+            maximum_safe_temperature: self.state.maximum_safe_temperature,
+            minimum_set_temperature,
+            maximum_set_temperature,
+            minimum_heat_run_secs: self.state.minimum_heat_run_secs,
+            maximum_heat_run_secs: self.state.maximum_heat_run_secs,
+            minimum_heat_off_secs: self.state.minimum_heat_off_secs,
+            minimum_cool_run_secs: self.state.minimum_cool_run_secs,
+            maximum_cool_run_secs: self.state.maximum_cool_run_secs,
+            minimum_cool_off_secs: self.state.minimum_cool_off_secs,
+            minimum_fan_run_secs: self.state.minimum_fan_run_secs,
+            maximum_fan_run_secs: self.state.maximum_fan_run_secs,
+            minimum_fan_off_secs: self.state.minimum_fan_off_secs,
+            initial_temperature: self.state.initial_temperature,
+            target_temperature: self.state.target_temperature,
+            hysteresis: self.state.hysteresis,
+            heat_deadband: self.state.heat_deadband,
+            cool_deadband: self.state.cool_deadband,
+            unoccupied_minimum_set_temperature: self.state.unoccupied_minimum_set_temperature,
+            unoccupied_maximum_set_temperature: self.state.unoccupied_maximum_set_temperature,
+        }
+    }
+
+    fn check_operating_mode(&self, operating_mode: OperatingMode) -> Result<(), Error> {
+        if (operating_mode == OperatingMode::Custom && self.state.custom_control_fn.is_none())
+            || (operating_mode == OperatingMode::RecirculationOnly && !self.interface.has_fan())
+            || ((operating_mode == OperatingMode::Vacation) != self.state.vacation_resume.is_some())
+        {
+            return Err(Error::InvalidConfiguration);
+        }
+        Ok(())
+    }
+
+    /// Replace the current configuration.
+    ///
+    /// The configuration is validated before it is applied. An Err Result is returned, and
+    /// nothing is changed, if it is invalid, its operating mode is incompatible with the
+    /// thermostat, or it changes locked safe temperatures.
+    pub fn apply_config(&mut self, config: &ThermostatConfig) -> Result<(), Error> {
+        config.validate()?;
+        self.apply_validated_config(config)
+    }
+
+    // Assumes config has already passed ThermostatConfig::validate.
+    fn apply_validated_config(&mut self, config: &ThermostatConfig) -> Result<(), Error> {
+        self.check_operating_mode(config.operating_mode)?;
+        self.check_safe_temperatures_unchanged(
+            config.minimum_safe_temperature,
+            config.maximum_safe_temperature,
+        )?;
+        self.state.operating_mode = config.operating_mode;
+        self.state.minimum_safe_temperature = config.minimum_safe_temperature;
+        self.state.maximum_safe_temperature = config.maximum_safe_temperature;
+        self.state.minimum_set_temperature = config.minimum_set_temperature;
+        self.state.maximum_set_temperature = config.maximum_set_temperature;
+        self.state.minimum_heat_run_secs = config.minimum_heat_run_secs;
+        self.state.maximum_heat_run_secs = config.maximum_heat_run_secs;
+        self.state.minimum_heat_off_secs = config.minimum_heat_off_secs;
+        self.state.minimum_cool_run_secs = config.minimum_cool_run_secs;
+        self.state.maximum_cool_run_secs = config.maximum_cool_run_secs;
+        self.state.minimum_cool_off_secs = config.minimum_cool_off_secs;
+        self.state.minimum_fan_run_secs = config.minimum_fan_run_secs;
+        self.state.maximum_fan_run_secs = config.maximum_fan_run_secs;
+        self.state.minimum_fan_off_secs = config.minimum_fan_off_secs;
+        self.state.initial_temperature = config.initial_temperature;
+        self.state.target_temperature = config.target_temperature;
+        self.state.hysteresis = config.hysteresis;
+        self.state.heat_deadband = config.heat_deadband;
+        self.state.cool_deadband = config.cool_deadband;
+        self.state.unoccupied_minimum_set_temperature = config.unoccupied_minimum_set_temperature;
+        self.state.unoccupied_maximum_set_temperature = config.unoccupied_maximum_set_temperature;
+        if !self.is_ready() {
+            self.state.current_temperature = config.initial_temperature;
+        }
+        self.state.config_hash = None;
+        Ok(())
+    }
+
+    /// Change the current operating mode.
+    ///
+    /// While an override set with `set_override` is active, the new mode becomes part of the
+    /// override and lasts until it expires. Changing to `Unoccupied` remembers the mode being left
+    /// for `set_occupancy` to return to.
+    ///
+    /// Will return an Err result if the specified operating mode is incompatible with the current
+    /// configuration, such as `Custom` without a control function or `RecirculationOnly` without a
+    /// fan, or if leaving `EmergencyHeat` before auxiliary heat has reached its minimum run time.
+    /// `Vacation` is only entered with `enter_vacation_mode`, and no other mode can be changed to
+    /// until `exit_vacation_mode`.
+    pub fn set_operating_mode(&mut self, operating_mode: OperatingMode) -> Result<(), Error> {
+        self.check_operating_mode(operating_mode)?;
+        SetPointBands {
+            operating_mode,
+            ..self.set_point_bands()
+        }
+        .validate()?;
+        self.commit_operating_mode(operating_mode)
+    }
+
+    // Changes the operating mode once it has been checked against the configuration and the set
+    // points, shutting down equipment the new mode does not use.
+    fn commit_operating_mode(&mut self, operating_mode: OperatingMode) -> Result<(), Error> {
+        if operating_mode == OperatingMode::EmergencyHeat && self.interface.calling_for_heat()? {
+            // shut the heat pump down now rather than after its minimum run time
+            let minimum_heat_run_secs = self.state.minimum_heat_run_secs;
+            self.state.minimum_heat_run_secs = 0;
+            let result = self.stop_heat();
+            self.state.minimum_heat_run_secs = minimum_heat_run_secs;
+            result?;
+        } else if operating_mode != OperatingMode::EmergencyHeat {
+            self.stop_aux_heat()?;
+        }
+        if operating_mode == OperatingMode::Unoccupied
+            && self.state.operating_mode != OperatingMode::Unoccupied
+        {
+            self.state.occupied_operating_mode = Some(self.state.operating_mode);
+        }
+        if operating_mode != OperatingMode::HeatPump {
+            // the valve delay only holds back a compressor controlled by the HeatPump mode
+            self.state.reversing_valve_switched_at = None;
+        }
+        self.state.operating_mode = operating_mode;
+        self.state.config_hash = None;
+        self.log_event(EventKind::OperatingModeChanged(operating_mode));
+        Ok(())
+    }
+
+    /// Switch to the `Unoccupied` mode when the space is vacated and back to the mode it replaced
+    /// once occupied again.
+    ///
+    /// Does nothing if already in `Unoccupied` mode when vacated, or in any other mode when
+    /// occupied, or if `Unoccupied` was entered from the configuration so there is no mode to
+    /// return to. An Err Result is returned if the mode cannot be changed, as for
+    /// `set_operating_mode`.
+    pub fn set_occupancy(&mut self, occupied: bool) -> Result<(), Error> {
+        let unoccupied = self.state.operating_mode == OperatingMode::Unoccupied;
+        if !occupied && !unoccupied {
+            return self.set_operating_mode(OperatingMode::Unoccupied);
+        }
+        match self.state.occupied_operating_mode {
+            Some(operating_mode) if occupied && unoccupied => {
+                self.set_operating_mode(operating_mode)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get a hash of the current configuration for change detection.
+    ///
+    /// The hash is the 32-bit FNV-1a hash of every `ThermostatConfig` field in declaration order,
+    /// with numbers encoded little-endian, so it is the same across reboots and platforms for the
+    /// same configuration. It is cached until the configuration next changes.
+    pub fn get_config_hash(&mut self) -> u32 {
+        match self.state.config_hash {
+            Some(hash) => hash,
+            None => {
+                let hash = self.get_config().fnv1a_hash();
+                self.state.config_hash = Some(hash);
+                hash
+            }
+        }
+    }
+
+    /// Get the current operating mode.
+    pub fn get_operating_mode(&self) -> OperatingMode {
+        self.state.operating_mode
+    }
+
+    /// Check whether the thermostat is in the `EmergencyHeat` operating mode.
+    pub fn is_in_emergency_heat(&self) -> bool {
+        self.state.operating_mode == OperatingMode::EmergencyHeat
+    }
+
+    /// Get the configured operating mode along with any override currently in effect.
+    ///
+    /// Overrides are reported by precedence: an active fault, then a defrost cycle, then the safe
+    /// temperature limits, and finally the safe humidity limits in the `Disabled` mode. The safe
+    /// limits never override the `DisabledUnsafe` mode.
+    pub fn get_effective_operating_mode(&self) -> EffectiveMode {
+        let temperature = self.state.current_temperature;
+        let unprotected = self.state.operating_mode == OperatingMode::DisabledUnsafe;
+        #[cfg(feature = "defrost")]
+        let defrosting = self.is_defrosting();
+        #[cfg(not(feature = "defrost"))]
+        let defrosting = false;
+        let override_reason = if self.state.fault.is_some() {
+            Some(OverrideReason::EmergencyStop)
+        } else if defrosting {
+            Some(OverrideReason::MaintenanceMode)
+        } else if temperature < self.state.minimum_safe_temperature && !unprotected {
+            Some(OverrideReason::FreezeProtection)
+        } else if temperature > self.state.maximum_safe_temperature && !unprotected {
+            Some(OverrideReason::HighTempProtection)
+        } else if self.state.current_humidity > self.state.maximum_safe_humidity
+            && self.state.operating_mode == OperatingMode::Disabled
+        {
+            Some(OverrideReason::MoldPrevention)
+        } else if self.state.current_humidity < self.state.minimum_safe_humidity
+            && self.state.operating_mode == OperatingMode::Disabled
+        {
+            Some(OverrideReason::DryAirProtection)
+        } else {
+            None
+        };
+        EffectiveMode {
+            base_mode: self.state.operating_mode,
+            override_reason,
+        }
+    }
+
+    /// Get what the thermostat is currently doing.
+    ///
+    /// A heat or cool timing constraint that held back the last update takes precedence over the
+    /// outputs being called for. The thermostat is reported as idle if the outputs cannot be read
+    /// from the interface.
+    pub fn get_state(&self) -> ThermostatState {
+        if self.state.timing_constrained {
+            match self.state.last_error {
+                Some(Error::HeatMaxRunTimeConstraint)
+                | Some(Error::HeatMinRunTimeConstraint)
+                | Some(Error::HeatMinOffTimeConstraint) => return ThermostatState::HeatConstrained,
+                Some(Error::CoolMaxRunTimeConstraint)
+                | Some(Error::CoolMinRunTimeConstraint)
+                | Some(Error::CoolMinOffTimeConstraint) => return ThermostatState::CoolConstrained,
+                // the valve is engaged for cooling and disengaged for heating
+                Some(Error::ReversingValveShiftConstraint) => {
+                    return match self.interface.calling_for_reversing_valve() {
+                        Ok(true) => ThermostatState::CoolConstrained,
+                        _ => ThermostatState::HeatConstrained,
+                    };
+                }
+                _ => {}
+            }
+        }
+        match self.output_state() {
+            Ok(SystemState { heat: true, .. }) => ThermostatState::Heating,
+            Ok(SystemState { cool: true, .. }) => ThermostatState::Cooling,
+            Ok(SystemState { fan: true, .. }) => ThermostatState::FanOnly,
+            _ => ThermostatState::Idle,
+        }
+    }
+
+    /// Register the control function used in the `Custom` operating mode.
+    ///
+    /// On every update the function receives the current temperature and humidity and returns
+    /// which outputs should be called for. Safety set points and timing constraints are still
+    /// enforced, and the function is not consulted while the temperature is outside the safe range.
+    pub fn set_custom_control_fn(&mut self, control: fn(f64, f64) -> ControlDecision) {
+        self.state.custom_control_fn = Some(control);
+    }
+
+    /// Register a handler notified as outputs start and stop and safety limits are breached.
+    ///
+    /// The handler is called synchronously from within the update that caused the change.
+    pub fn set_event_handler(&mut self, handler: &'a dyn ThermostatEventHandler) {
+        self.state.event_handler = Some(handler);
+    }
+
+    /// Register the source of temperature readings polled by `tick`.
+    pub fn set_measurement_provider(&mut self, provider: &'a dyn MeasurementProvider) {
+        self.state.measurement_provider = Some(provider);
+    }
+
+    /// Take a reading from the measurement provider and act on it.
+    ///
+    /// Any weekly schedule slot that has taken effect is applied first; a slot that cannot be
+    /// applied is logged as the last error, and the current settings are kept until it can be. The
+    /// reading is then passed to `set_current_temperature` and the resulting change in outputs is
+    /// returned. Call this periodically from the application's main loop.
+    ///
+    /// An Err Result is returned if no measurement provider is registered, the reading fails, or
+    /// `set_current_temperature` fails.
+    pub fn tick(&mut self) -> Result<Action, Error> {
+        let provider = self
+            .state
+            .measurement_provider
+            .ok_or(Error::NoMeasurementProvider)?;
+        if let Err(error) = self.apply_schedule() {
+            // keep controlling to the current settings rather than stopping on a bad slot
+            self.log_error(error.into());
+        }
+        let temperature = provider.measure()?;
+        self.set_current_temperature(temperature)
+    }
+
+    /// Register a function called at the end of every `set_current_temperature`.
+    ///
+    /// The hook receives the thermostat after the reading has been processed, e.g. to log the
+    /// decision or refresh a display. Registering a hook replaces any previous one. Simulations
+    /// run without the hook.
+    pub fn set_post_update_hook(&mut self, hook: fn(&ThermostatCore<'a, I, N>)) {
+        self.post_update_hook = Some(hook);
+    }
+
+    /// Permanently lock the safe temperatures, e.g. after calibration during manufacturing.
+    ///
+    /// Once locked, the safe temperature setters return an Err Result, and temperature profiles and
+    /// configurations are only applied if they keep the current safe temperatures. There is no way
+    /// to unlock them; a new thermostat must be created.
+    pub fn lock_safe_temperatures(&mut self) -> Result<(), Error> {
+        self.state.safe_temperatures_locked = true;
+        Ok(())
+    }
+
+    // Fails if the safe temperatures are locked and would change.
+    fn check_safe_temperatures_unchanged(&self, minimum: f64, maximum: f64) -> Result<(), Error> {
+        if self.state.safe_temperatures_locked
+            && (minimum != self.state.minimum_safe_temperature
+                || maximum != self.state.maximum_safe_temperature)
+        {
+            return Err(Error::SafeTemperatureLocked);
+        }
+        Ok(())
+    }
+
+    /// Change the maximum safe temperature.
+    ///
+    /// Any set temperature above the specified maximum safe temperature, including the target,
+    /// unoccupied, and saved vacation and override set temperatures, is lowered to match.
+    ///
+    /// An Err Result is returned, and nothing changes, if the specified temperature is not finite
+    /// or not above the minimum safe temperature, if the safe temperatures are locked, or if the
+    /// hysteresis or deadbands would no longer fit between the lowered set temperatures.
+    pub fn set_maximum_safe_temperature<T: Into<Temperature<Celsius>>>(
+        &mut self,
+        temperature: T,
+    ) -> Result<(), Error> {
+        let temperature = temperature.into().as_f64();
+        if !temperature.is_finite() {
+            return Err(Error::InvalidTemperature);
+        }
+        if self.state.safe_temperatures_locked {
+            return Err(Error::SafeTemperatureLocked);
+        }
+        let temperature = self.round_temperature(temperature);
+        if temperature <= self.state.minimum_safe_temperature {
+            return Err(Error::SafeTemperatureRangeInversion);
+        }
+        self.clamp_set_points(self.state.minimum_safe_temperature, temperature)?;
+        self.state.maximum_safe_temperature = temperature;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the current maximum safe temperature.
+    pub fn get_maximum_safe_temperature(&self) -> f64 {
+        self.state.maximum_safe_temperature
+    }
+
+    /// Change the minimum safe temperature.
+    ///
+    /// Any set temperature below the specified minimum safe temperature, including the target,
+    /// unoccupied, and saved vacation and override set temperatures, is raised to match.
+    ///
+    /// An Err Result is returned, and nothing changes, if the specified temperature is not finite
+    /// or not below the maximum safe temperature, if the safe temperatures are locked, or if the
+    /// hysteresis or deadbands would no longer fit between the raised set temperatures.
+    pub fn set_minimum_safe_temperature<T: Into<Temperature<Celsius>>>(
+        &mut self,
+        temperature: T,
+    ) -> Result<(), Error> {
+        let temperature = temperature.into().as_f64();
+        if !temperature.is_finite() {
+            return Err(Error::InvalidTemperature);
+        }
+        if self.state.safe_temperatures_locked {
+            return Err(Error::SafeTemperatureLocked);
+        }
+        let temperature = self.round_temperature(temperature);
+        if temperature >= self.state.maximum_safe_temperature {
+            return Err(Error::SafeTemperatureRangeInversion);
+        }
+        self.clamp_set_points(temperature, self.state.maximum_safe_temperature)?;
+        self.state.minimum_safe_temperature = temperature;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the current minimum safe temperature
+    pub fn get_minimum_safe_temperature(&self) -> f64 {
+        self.state.minimum_safe_temperature
+    }
+
+    // Moves every set temperature into a new safe range, failing without changing anything if
+    // the bands would no longer fit between the moved set temperatures.
+    fn clamp_set_points(&mut self, minimum: f64, maximum: f64) -> Result<(), Error> {
+        let clamp = |temperature: f64| temperature.max(minimum).min(maximum);
+        let bands = SetPointBands {
+            minimum: clamp(self.state.minimum_set_temperature),
+            maximum: clamp(self.state.maximum_set_temperature),
+            unoccupied_minimum: clamp(self.state.unoccupied_minimum_set_temperature),
+            unoccupied_maximum: clamp(self.state.unoccupied_maximum_set_temperature),
+            ..self.set_point_bands()
+        };
+        bands.validate()?;
+        if bands.minimum != self.state.minimum_set_temperature {
+            self.state.minimum_set_temperature = bands.minimum;
+            self.log_event(EventKind::MinimumSetTemperatureChanged(bands.minimum));
+        }
+        if bands.maximum != self.state.maximum_set_temperature {
+            self.state.maximum_set_temperature = bands.maximum;
+            self.log_event(EventKind::MaximumSetTemperatureChanged(bands.maximum));
+        }
+        self.state.unoccupied_minimum_set_temperature = bands.unoccupied_minimum;
+        self.state.unoccupied_maximum_set_temperature = bands.unoccupied_maximum;
+        self.state.target_temperature = clamp(self.state.target_temperature);
+        self.state.ramped_minimum_set_temperature =
+            self.state.ramped_minimum_set_temperature.map(clamp);
+        self.state.ramped_maximum_set_temperature =
+            self.state.ramped_maximum_set_temperature.map(clamp);
+        self.clamp_resume_set_points(clamp);
+        Ok(())
+    }
+
+    /// Change the maximum set temperature.
+    ///
+    /// The minimum set temperature is left as it is, so a maximum below it is rejected rather than
+    /// moving the minimum down to match. In `Vacation` mode this changes the vacation set point,
+    /// leaving the one restored on exit untouched.
+    ///
+    /// An Err Result is returned if the specified temperature is not finite. It is
+    /// `SetPointOutOfSafeRange` if the temperature is above the maximum safe temperature, and
+    /// `InvalidConfiguration` if it is below the minimum set temperature or if the hysteresis or
+    /// deadbands would no longer fit between the set temperatures (see `set_hysteresis` and
+    /// `validate_maintain_range_config`).
+    pub fn set_maximum_set_temperature<T: Into<Temperature<Celsius>>>(
+        &mut self,
+        temperature: T,
+    ) -> Result<(), Error> {
+        let temperature = temperature.into().as_f64();
+        if !temperature.is_finite() {
+            return Err(Error::InvalidTemperature);
+        }
+        let temperature = self.round_temperature(temperature);
+        if temperature > self.state.maximum_safe_temperature {
+            return Err(Error::SetPointOutOfSafeRange);
+        }
+        SetPointBands {
+            maximum: temperature,
+            ..self.set_point_bands()
+        }
+        .validate()?;
+        self.commit_maximum_set_temperature(temperature);
+        Ok(())
+    }
+
+    // Changes the maximum set point once it has been rounded and validated, ramping towards it if
+    // a ramp rate is set.
+    fn commit_maximum_set_temperature(&mut self, temperature: f64) {
+        self.start_set_point_ramp();
+        if self.state.temperature_ramp_rate > 0.0
+            && self.state.ramped_maximum_set_temperature.is_none()
+        {
+            self.state.ramped_maximum_set_temperature = Some(self.state.maximum_set_temperature);
+        }
+        self.state.maximum_set_temperature = temperature;
+        self.state.config_hash = None;
+        self.log_event(EventKind::MaximumSetTemperatureChanged(temperature));
+    }
+    /// Get the current maximum set temperature.
+    pub fn get_maximum_set_temperature(&self) -> f64 {
+        self.state.maximum_set_temperature
+    }
+
+    /// Change the minimum set temperature.
+    ///
+    /// The maximum set temperature is left as it is, so a minimum above it is rejected rather than
+    /// moving the maximum up to match. In `Vacation` mode this changes the vacation set point,
+    /// leaving the one restored on exit untouched.
+    ///
+    /// An Err Result is returned if the specified temperature is not finite. It is
+    /// `SetPointOutOfSafeRange` if the temperature is below the minimum safe temperature, and
+    /// `InvalidConfiguration` if it is above the maximum set temperature or if the hysteresis or
+    /// deadbands would no longer fit between the set temperatures (see `set_hysteresis` and
+    /// `validate_maintain_range_config`).
+    pub fn set_minimum_set_temperature<T: Into<Temperature<Celsius>>>(
+        &mut self,
+        temperature: T,
+    ) -> Result<(), Error> {
+        let temperature = temperature.into().as_f64();
+        if !temperature.is_finite() {
+            return Err(Error::InvalidTemperature);
+        }
+        let temperature = self.round_temperature(temperature);
+        if temperature < self.state.minimum_safe_temperature {
+            return Err(Error::SetPointOutOfSafeRange);
+        }
+        SetPointBands {
+            minimum: temperature,
+            ..self.set_point_bands()
+        }
+        .validate()?;
+        self.commit_minimum_set_temperature(temperature);
+        Ok(())
+    }
+
+    // Changes the minimum set point once it has been rounded and validated, ramping towards it if
+    // a ramp rate is set.
+    fn commit_minimum_set_temperature(&mut self, temperature: f64) {
+        self.start_set_point_ramp();
+        if self.state.temperature_ramp_rate > 0.0
+            && self.state.ramped_minimum_set_temperature.is_none()
+        {
+            self.state.ramped_minimum_set_temperature = Some(self.state.minimum_set_temperature);
+        }
+        self.state.minimum_set_temperature = temperature;
+        self.state.config_hash = None;
+        self.log_event(EventKind::MinimumSetTemperatureChanged(temperature));
+    }
+    /// Get the current minimum set temperature.
+    pub fn get_minimum_set_temperature(&self) -> f64 {
+        self.state.minimum_set_temperature
+    }
+
+    /// Change how quickly the set points move to newly set values, in degrees Celsius per minute.
+    ///
+    /// When non-zero, `set_minimum_set_temperature` and `set_maximum_set_temperature` record the
+    /// new value as a target, and the effective set point used for control moves towards it at
+    /// this rate as readings arrive, avoiding full demand after a large change such as returning
+    /// from vacation. Defaults to 0, which applies new set points instantly; setting it to 0 ends
+    /// any ramp in progress.
+    ///
+    /// An Err Result is returned if the rate is negative or not finite.
+    pub fn set_temperature_ramp_rate_celsius_per_minute(
+        &mut self,
+        rate: f64,
+    ) -> Result<(), Error> {
+        if !rate.is_finite() || rate < 0.0 {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.advance_set_point_ramp();
+        self.state.temperature_ramp_rate = rate;
+        if rate == 0.0 {
+            self.state.ramped_minimum_set_temperature = None;
+            self.state.ramped_maximum_set_temperature = None;
+        }
+        Ok(())
+    }
+    /// Get how quickly the set points move to newly set values, in degrees Celsius per minute
+    pub fn get_temperature_ramp_rate_celsius_per_minute(&self) -> f64 {
+        self.state.temperature_ramp_rate
+    }
+
+    /// Enable or disable adaptive start. Disabled by default.
+    ///
+    /// While enabled, the time each heat cycle takes to bring the temperature up to the minimum
+    /// set temperature is measured and folded into a moving average, reported by
+    /// `get_recommended_start_advance_secs`.
+    pub fn set_adaptive_start_enabled(&mut self, enabled: bool) {
+        self.state.adaptive_start_enabled = enabled;
+    }
+    /// Check whether adaptive start is enabled
+    pub fn get_adaptive_start_enabled(&self) -> bool {
+        self.state.adaptive_start_enabled
+    }
+
+    /// Get how many seconds before the desired time a scheduler should raise the minimum set
+    /// temperature for it to be reached on time.
+    ///
+    /// This is the moving average of measured heat cycle lead times. Zero while adaptive start is
+    /// disabled or before a heat cycle has reached the set point.
+    pub fn get_recommended_start_advance_secs(&self) -> u64 {
+        if self.state.adaptive_start_enabled {
+            self.state.adaptive_heat_lead_secs
+        } else {
+            0
+        }
+    }
+
+    /// Get the minimum set temperature currently used for control.
+    ///
+    /// This is the target temperature in `AutoChangeover` mode and the unoccupied minimum set
+    /// temperature in `Unoccupied` mode. Otherwise it is part way along any ramp towards
+    /// `get_minimum_set_temperature`.
+    pub fn get_effective_minimum_set_temperature(&self) -> f64 {
+        match self.state.operating_mode {
+            OperatingMode::AutoChangeover => self.state.target_temperature,
+            OperatingMode::Unoccupied => self.state.unoccupied_minimum_set_temperature,
+            _ => self
+                .state
+                .ramped_minimum_set_temperature
+                .unwrap_or(self.state.minimum_set_temperature),
+        }
+    }
+    /// Get the maximum set temperature currently used for control.
+    ///
+    /// This is the target temperature in `AutoChangeover` mode and the unoccupied maximum set
+    /// temperature in `Unoccupied` mode. Otherwise it is part way along any ramp towards
+    /// `get_maximum_set_temperature`.
+    pub fn get_effective_maximum_set_temperature(&self) -> f64 {
+        match self.state.operating_mode {
+            OperatingMode::AutoChangeover => self.state.target_temperature,
+            OperatingMode::Unoccupied => self.state.unoccupied_maximum_set_temperature,
+            _ => self
+                .state
+                .ramped_maximum_set_temperature
+                .unwrap_or(self.state.maximum_set_temperature),
+        }
+    }
+
+    // Brings any ramp in progress up to date before a set point changes, and starts timing a new
+    // ramp from now.
+    fn start_set_point_ramp(&mut self) {
+        self.advance_set_point_ramp();
+        if self.state.ramped_minimum_set_temperature.is_none()
+            && self.state.ramped_maximum_set_temperature.is_none()
+        {
+            self.state.set_point_ramp_updated_at = self.interface.get_seconds().ok();
+        }
+    }
+
+    // Moves the ramped set points towards their targets by the time elapsed since the last move.
+    fn advance_set_point_ramp(&mut self) {
+        let now = match self.interface.get_seconds() {
+            Ok(now) => now,
+            Err(_) => return,
+        };
+        let elapsed = now.saturating_sub(self.state.set_point_ramp_updated_at.unwrap_or(now));
+        self.state.set_point_ramp_updated_at = Some(now);
+        let step = self.state.temperature_ramp_rate * elapsed as f64 / 60.0;
+        let ramp = |ramped: Option<f64>, target: f64| {
+            ramped.and_then(|ramped| {
+                if (target - ramped).abs() <= step {
+                    None
+                } else if ramped < target {
+                    Some(ramped + step)
+                } else {
+                    Some(ramped - step)
+                }
+            })
+        };
+        self.state.ramped_minimum_set_temperature =
+            ramp(self.state.ramped_minimum_set_temperature, self.state.minimum_set_temperature);
+        self.state.ramped_maximum_set_temperature =
+            ramp(self.state.ramped_maximum_set_temperature, self.state.maximum_set_temperature);
+    }
+
+    /// Change the temperature maintained in `AutoChangeover` mode. Defaults to 21 °C.
+    ///
+    /// An Err Result is returned if the specified temperature is not finite or is outside the
+    /// safe temperature range.
+    pub fn set_target_temperature<T: Into<Temperature<Celsius>>>(
+        &mut self,
+        temperature: T,
+    ) -> Result<(), Error> {
+        let temperature = temperature.into().as_f64();
+        if !temperature.is_finite() {
+            return Err(Error::InvalidTemperature);
+        }
+        let temperature = self.round_temperature(temperature);
+        if temperature < self.state.minimum_safe_temperature
+            || temperature > self.state.maximum_safe_temperature
+        {
+            return Err(Error::SetPointOutOfSafeRange);
+        }
+        self.state.target_temperature = temperature;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the temperature maintained in `AutoChangeover` mode.
+    pub fn get_target_temperature(&self) -> f64 {
+        self.state.target_temperature
+    }
+
+    /// Change the minimum set temperature maintained in `Unoccupied` mode. Defaults to 16 °C.
+    ///
+    /// An Err Result is returned if the specified temperature is not finite, is outside the safe
+    /// temperature range, is not below the unoccupied maximum set temperature, or if the
+    /// hysteresis or deadbands would no longer fit between the unoccupied set temperatures.
+    pub fn set_unoccupied_minimum_set_temperature<T: Into<Temperature<Celsius>>>(
+        &mut self,
+        temperature: T,
+    ) -> Result<(), Error> {
+        let temperature = self.checked_unoccupied_set_point(temperature.into().as_f64())?;
+        SetPointBands {
+            unoccupied_minimum: temperature,
+            ..self.set_point_bands()
+        }
+        .validate()?;
+        self.state.unoccupied_minimum_set_temperature = temperature;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the minimum set temperature maintained in `Unoccupied` mode.
+    pub fn get_unoccupied_minimum_set_temperature(&self) -> f64 {
+        self.state.unoccupied_minimum_set_temperature
+    }
+
+    /// Change the maximum set temperature maintained in `Unoccupied` mode. Defaults to 28 °C.
+    ///
+    /// An Err Result is returned if the specified temperature is not finite, is outside the safe
+    /// temperature range, is not above the unoccupied minimum set temperature, or if the
+    /// hysteresis or deadbands would no longer fit between the unoccupied set temperatures.
+    pub fn set_unoccupied_maximum_set_temperature<T: Into<Temperature<Celsius>>>(
+        &mut self,
+        temperature: T,
+    ) -> Result<(), Error> {
+        let temperature = self.checked_unoccupied_set_point(temperature.into().as_f64())?;
+        SetPointBands {
+            unoccupied_maximum: temperature,
+            ..self.set_point_bands()
+        }
+        .validate()?;
+        self.state.unoccupied_maximum_set_temperature = temperature;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the maximum set temperature maintained in `Unoccupied` mode.
+    pub fn get_unoccupied_maximum_set_temperature(&self) -> f64 {
+        self.state.unoccupied_maximum_set_temperature
+    }
+
+    fn checked_unoccupied_set_point(&self, temperature: f64) -> Result<f64, Error> {
+        if !temperature.is_finite() {
+            return Err(Error::InvalidTemperature);
+        }
+        let temperature = self.round_temperature(temperature);
+        if temperature < self.state.minimum_safe_temperature
+            || temperature > self.state.maximum_safe_temperature
+        {
+            return Err(Error::SetPointOutOfSafeRange);
+        }
+        Ok(temperature)
+    }
+
+    /// Change the safe and set temperatures together.
+    ///
+    /// The four values are validated as a whole and applied only if
+    /// `safe_min < set_min <= set_max < safe_max`, so switching between profiles (e.g. summer and
+    /// winter) never passes through an inconsistent intermediate state. An Err Result is returned
+    /// and nothing changes if the values are not finite or not ordered, if the hysteresis or
+    /// deadbands would no longer fit between the set temperatures, or if the safe temperatures
+    /// are locked and would change.
+    pub fn apply_temperature_profile(
+        &mut self,
+        safe_min: f64,
+        safe_max: f64,
+        set_min: f64,
+        set_max: f64,
+    ) -> Result<(), Error> {
+        let safe_min = self.round_temperature(safe_min);
+        let safe_max = self.round_temperature(safe_max);
+        let set_min = self.round_temperature(set_min);
+        let set_max = self.round_temperature(set_max);
+        if [safe_min, safe_max, set_min, set_max]
+            .iter()
+            .any(|t| !t.is_finite())
+            || !(safe_min < set_min && set_min <= set_max && set_max < safe_max)
+        {
+            return Err(Error::InvalidConfiguration);
+        }
+        SetPointBands {
+            minimum: set_min,
+            maximum: set_max,
+            ..self.set_point_bands()
+        }
+        .validate()?;
+        self.check_safe_temperatures_unchanged(safe_min, safe_max)?;
+        self.state.minimum_safe_temperature = safe_min;
+        self.state.maximum_safe_temperature = safe_max;
+        self.state.minimum_set_temperature = set_min;
+        self.state.maximum_set_temperature = set_max;
+        self.state.config_hash = None;
+        self.log_event(EventKind::MinimumSetTemperatureChanged(set_min));
+        self.log_event(EventKind::MaximumSetTemperatureChanged(set_max));
+        Ok(())
+    }
+
+    /// Change the number of decimal places temperatures are rounded to.
+    ///
+    /// Readings, set points, and safe temperatures are rounded half up to this precision before
+    /// they are stored, so control decisions are not swayed by fluctuations smaller than the
+    /// sensor can meaningfully resolve. Values stored before the change are not re-rounded.
+    ///
+    /// An Err Result is returned if the precision is greater than 4.
+    pub fn set_temperature_precision(&mut self, digits_after_decimal: u8) -> Result<(), Error> {
+        if digits_after_decimal > MAXIMUM_TEMPERATURE_PRECISION {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.temperature_precision = digits_after_decimal;
+        Ok(())
+    }
+    /// Get the number of decimal places temperatures are rounded to
+    pub fn get_temperature_precision(&self) -> u8 {
+        self.state.temperature_precision
+    }
+
+    fn round_temperature(&self, temperature: f64) -> f64 {
+        round_half_up(temperature, self.state.temperature_precision)
+    }
+
+    /// Get the current temperature as known to the thermostat
+    pub fn get_current_temperature(&self) -> f64 {
+        self.state.current_temperature
+    }
+
+    /// Get the temperature last passed to `set_current_temperature`, before filtering and rounding
+    pub fn get_last_raw_temperature(&self) -> f64 {
+        self.state.last_raw_temperature
+    }
+
+    /// Change how many of the most recent temperature readings are averaged.
+    ///
+    /// With a depth of N, the mean of the last N readings passed to `set_current_temperature` is
+    /// used as the current temperature for all control decisions, smoothing out spurious spikes
+    /// from noisy sensors. The default depth of 1 uses each reading as is. Changing the depth
+    /// discards the readings averaged so far.
+    ///
+    /// An Err Result is returned if the depth is less than 1 or more than 64 readings.
+    pub fn set_temperature_filter_depth(&mut self, depth: usize) -> Result<(), Error> {
+        if !(1..=MAXIMUM_TEMPERATURE_FILTER_DEPTH).contains(&depth) {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.temperature_filter.set_depth(depth);
+        Ok(())
+    }
+    /// Get how many of the most recent temperature readings are averaged
+    pub fn get_temperature_filter_depth(&self) -> usize {
+        self.state.temperature_filter.depth
+    }
+
+    /// Get the interface used by this thermostat
+    pub fn get_interface(&self) -> &I {
+        &self.interface
+    }
+
+    /// Get the number of seconds since this thermostat was created.
+    ///
+    /// If the interface clock was unavailable when the thermostat was created, uptime is measured
+    /// from system start instead and will be overestimated.
+    pub fn get_uptime_secs(&self) -> Result<u64, Error> {
+        let now = self.interface.get_seconds()?;
+        Ok(now.saturating_sub(self.state.initialization_time))
+    }
+
+    /// Get the installation location reported by the interface when this thermostat was created.
+    ///
+    /// Returns None if the interface does not provide a location or failed to report it.
+    pub fn get_location_info(&self) -> Option<LocationInfo> {
+        self.state.location
+    }
+
+    /// Change the unit used when displaying temperatures.
+    ///
+    /// Temperatures are always stored and set in degrees Celsius regardless of the display unit.
+    pub fn set_display_unit(&mut self, unit: TemperatureUnit) {
+        self.state.display_unit = unit;
+    }
+    /// Get the unit used when displaying temperatures.
+    pub fn get_display_unit(&self) -> TemperatureUnit {
+        self.state.display_unit
+    }
+
+    /// Get a displayable form of a Celsius temperature in the configured display unit.
+    pub fn display_temperature(&self, celsius: f64) -> TemperatureDisplay {
+        TemperatureDisplay {
+            celsius,
+            unit: self.state.display_unit,
+        }
+    }
+
+    /// Get a displayable form of the current temperature in the configured display unit.
+    pub fn display_current_temperature(&self) -> TemperatureDisplay {
+        self.display_temperature(self.state.current_temperature)
+    }
+
+    /// Update the thermostat with a new relative humidity reading.
+    ///
+    /// With humidity control enabled, the current temperature is re-evaluated so dehumidification
+    /// starts or stops without waiting for the next temperature reading.
+    ///
+    /// An Err Result is returned if the humidity is not between 0 and 100 percent.
+    pub fn set_current_humidity(&mut self, humidity: f64) -> Result<(), Error> {
+        if !(0.0..=100.0).contains(&humidity) {
+            return Err(Error::HumidityOutOfRange);
+        }
+        self.state.current_humidity = humidity;
+        self.state.humidity_history.push(humidity);
+        if self.state.humidity_control_enabled {
+            self.reevaluate(TransitionTrigger::HumidityReading(humidity))?;
+        }
+        Ok(())
+    }
+
+    /// Get the current relative humidity as known to the thermostat
+    pub fn get_current_humidity(&self) -> f64 {
+        self.state.current_humidity
+    }
+
+    /// Change how many of the most recent humidity readings the humidity trend spans.
+    ///
+    /// Wider windows smooth out sensor noise but respond to changes more slowly. The default is 2,
+    /// the change between the last two readings.
+    ///
+    /// An Err Result is returned if the window is less than 2 or more than 16 readings.
+    pub fn set_humidity_trend_window(&mut self, window: usize) -> Result<(), Error> {
+        if !(2..=MAXIMUM_HUMIDITY_TREND_WINDOW).contains(&window) {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.humidity_history.set_window(window);
+        Ok(())
+    }
+
+    /// Get the average change in relative humidity per reading, in percent, across the trend
+    /// window.
+    ///
+    /// Returns zero until two readings are available.
+    pub fn get_humidity_trend(&self) -> f64 {
+        self.state.humidity_history.trend()
+    }
+
+    /// Enable or disable humidity control. Disabled by default.
+    ///
+    /// While enabled and once a humidity reading has been received, the interface's dehumidifier
+    /// is called for once humidity rises above the target humidity by half the humidity deadband,
+    /// and keeps running until it falls below the target by half the deadband. The humidifier is
+    /// driven the same way below the target. Neither runs in the disabled modes.
+    ///
+    /// Without a dehumidifier, cool is called for to dehumidify instead. Dehumidification by
+    /// cooling never cools below the minimum set temperature and is not done in the modes that do
+    /// not cool to a set point.
+    pub fn set_humidity_control_enabled(&mut self, enabled: bool) {
+        self.state.humidity_control_enabled = enabled;
+    }
+    /// Get whether humidity control is enabled
+    pub fn get_humidity_control_enabled(&self) -> bool {
+        self.state.humidity_control_enabled
+    }
+
+    /// Change the relative humidity maintained by humidity control. Defaults to 50 percent.
+    ///
+    /// An Err Result is returned if the humidity is not between 0 and 100 percent.
+    pub fn set_target_humidity(&mut self, humidity: f64) -> Result<(), Error> {
+        if !(0.0..=100.0).contains(&humidity) {
+            return Err(Error::HumidityOutOfRange);
+        }
+        self.state.target_humidity = humidity;
+        Ok(())
+    }
+    /// Get the relative humidity maintained by humidity control
+    pub fn get_target_humidity(&self) -> f64 {
+        self.state.target_humidity
+    }
+
+    /// Change the width of the band centred on the target humidity. Defaults to 10 percent.
+    ///
+    /// An Err Result is returned if the deadband is negative or not finite.
+    pub fn set_humidity_deadband(&mut self, deadband: f64) -> Result<(), Error> {
+        if !deadband.is_finite() || deadband < 0.0 {
+            return Err(Error::InvalidDeadband);
+        }
+        self.state.humidity_deadband = deadband;
+        Ok(())
+    }
+    /// Get the width of the band centred on the target humidity
+    pub fn get_humidity_deadband(&self) -> f64 {
+        self.state.humidity_deadband
+    }
+
+    /// Change the minimum number of seconds the humidifier runs once called for. Defaults to 300.
+    pub fn set_minimum_humidifier_run_secs(&mut self, secs: u32) {
+        self.state.minimum_humidifier_run_secs = secs;
+    }
+    /// Get the minimum number of seconds the humidifier runs once called for
+    pub fn get_minimum_humidifier_run_secs(&self) -> u32 {
+        self.state.minimum_humidifier_run_secs
+    }
+
+    /// Change the minimum number of seconds the humidifier stays off before it may be called for
+    /// again. Defaults to 300.
+    pub fn set_minimum_humidifier_off_secs(&mut self, secs: u32) {
+        self.state.minimum_humidifier_off_secs = secs;
+    }
+    /// Get the minimum number of seconds the humidifier stays off before it may be called for again
+    pub fn get_minimum_humidifier_off_secs(&self) -> u32 {
+        self.state.minimum_humidifier_off_secs
+    }
+
+    /// Change the minimum number of seconds the dehumidifier runs once called for. Defaults to 300.
+    pub fn set_minimum_dehumidifier_run_secs(&mut self, secs: u32) {
+        self.state.minimum_dehumidifier_run_secs = secs;
+    }
+    /// Get the minimum number of seconds the dehumidifier runs once called for
+    pub fn get_minimum_dehumidifier_run_secs(&self) -> u32 {
+        self.state.minimum_dehumidifier_run_secs
+    }
+
+    /// Change the minimum number of seconds the dehumidifier stays off before it may be called for
+    /// again. Defaults to 300.
+    pub fn set_minimum_dehumidifier_off_secs(&mut self, secs: u32) {
+        self.state.minimum_dehumidifier_off_secs = secs;
+    }
+    /// Get the minimum number of seconds the dehumidifier stays off between calls
+    pub fn get_minimum_dehumidifier_off_secs(&self) -> u32 {
+        self.state.minimum_dehumidifier_off_secs
+    }
+
+    /// Change the minimum safe relative humidity.
+    ///
+    /// In the `Disabled` operating mode the humidifier is called for while humidity is below the
+    /// safe humidity range, and the fan, along with the dehumidifier if one is connected, while it
+    /// is above, as heat and cool are called for outside the safe temperature range.
+    ///
+    /// An Err Result is returned if the humidity is not between 0 and 100 percent or not below the
+    /// maximum safe humidity.
+    pub fn set_minimum_safe_humidity(&mut self, humidity: f64) -> Result<(), Error> {
+        if !(0.0..=100.0).contains(&humidity) || humidity >= self.state.maximum_safe_humidity {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.minimum_safe_humidity = humidity;
+        Ok(())
+    }
+    /// Get the current minimum safe relative humidity
+    pub fn get_minimum_safe_humidity(&self) -> f64 {
+        self.state.minimum_safe_humidity
+    }
+
+    /// Change the maximum safe relative humidity.
+    ///
+    /// An Err Result is returned if the humidity is not between 0 and 100 percent or not above the
+    /// minimum safe humidity.
+    pub fn set_maximum_safe_humidity(&mut self, humidity: f64) -> Result<(), Error> {
+        if !(0.0..=100.0).contains(&humidity) || humidity <= self.state.minimum_safe_humidity {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.maximum_safe_humidity = humidity;
+        Ok(())
+    }
+    /// Get the current maximum safe relative humidity
+    pub fn get_maximum_safe_humidity(&self) -> f64 {
+        self.state.maximum_safe_humidity
+    }
+
+    /// Provide predicted future temperatures for anticipatory control.
+    ///
+    /// The forecast holds up to 24 predicted temperatures spaced `interval_secs` apart, starting
+    /// one interval from now. As time passes each prediction is used until its time arrives, after
+    /// which the next one takes over, and the forecast expires once the last one has passed. When
+    /// the upcoming prediction falls outside the set point range while the current temperature is
+    /// still inside it, the thermostat starts heating or cooling early. An empty forecast clears
+    /// any previous one.
+    ///
+    /// An Err Result is returned if the forecast is too long, contains a non-finite value, or the
+    /// interval is zero, or if the current time cannot be read.
+    pub fn set_temperature_forecast(
+        &mut self,
+        forecast: &[f64],
+        interval_secs: u64,
+    ) -> Result<(), Error> {
+        if forecast.len() > MAXIMUM_FORECAST_LENGTH
+            || interval_secs == 0
+            || forecast.iter().any(|t| !t.is_finite())
+        {
+            return Err(Error::InvalidConfiguration);
+        }
+        let now = self.interface.get_seconds()?;
+        self.state.forecast.temperatures[..forecast.len()].copy_from_slice(forecast);
+        self.state.forecast.len = forecast.len();
+        self.state.forecast.interval_secs = interval_secs;
+        self.state.forecast.set_at = now;
+        Ok(())
+    }
+
+    /// Get the temperature rate of change predicted by the forecast in degrees per hour.
+    ///
+    /// The rate is taken from the current temperature to the upcoming prediction. Negative values
+    /// indicate a coming temperature drop (heating demand), positive values a coming rise (cooling
+    /// demand). Returns None when no forecast has been provided, every prediction has passed, or
+    /// the current time cannot be read.
+    pub fn get_forecast_based_demand(&self) -> Option<f64> {
+        let now = self.interface.get_seconds().ok()?;
+        let (forecast, remaining_secs) = self.state.forecast.next(now)?;
+        Some((forecast - self.state.current_temperature) * 3600.0 / remaining_secs as f64)
+    }
+
+    /// Change the hysteresis band applied once heating or cooling has started.
+    ///
+    /// Heat started below the minimum set temperature keeps running until the temperature reaches
+    /// the minimum set temperature plus the hysteresis, and cool started above the maximum set
+    /// temperature keeps running until it falls to the maximum set temperature minus the
+    /// hysteresis. The hysteresis must be narrower than the set point range, otherwise neither
+    /// band could ever be left without entering the other. Zero, the default, disables it.
+    ///
+    /// An Err Result is returned if the hysteresis is negative, not finite, or not less than the
+    /// difference between the maximum and minimum set temperatures, or in `MaintainRange` mode if
+    /// `validate_maintain_range_config` would fail with it.
+    pub fn set_hysteresis(&mut self, hysteresis: f64) -> Result<(), Error> {
+        if !hysteresis.is_finite() || hysteresis < 0.0 {
+            return Err(Error::InvalidConfiguration);
+        }
+        SetPointBands {
+            hysteresis,
+            ..self.set_point_bands()
+        }
+        .validate()?;
+        self.state.hysteresis = hysteresis;
+        self.state.config_hash = None;
+        Ok(())
+    }
+
+    /// Change the deadband centred on the minimum set temperature.
+    ///
+    /// Heat starts once the temperature falls below the minimum set temperature by half the
+    /// deadband, and stops once it rises above it by half the deadband, so a noisy reading near
+    /// the set point does not cycle the equipment. Defaults to 0.5 °C.
+    ///
+    /// An Err Result is returned if the deadband is negative or not finite, or in `MaintainRange`
+    /// mode if `validate_maintain_range_config` would fail with it.
+    pub fn set_heat_deadband(&mut self, deadband: f64) -> Result<(), Error> {
+        if !deadband.is_finite() || deadband < 0.0 {
+            return Err(Error::InvalidDeadband);
+        }
+        SetPointBands {
+            heat_deadband: deadband,
+            ..self.set_point_bands()
+        }
+        .validate()?;
+        self.state.heat_deadband = deadband;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the deadband centred on the minimum set temperature
+    pub fn get_heat_deadband(&self) -> f64 {
+        self.state.heat_deadband
+    }
+
+    /// Change the deadband centred on the maximum set temperature.
+    ///
+    /// Cool starts once the temperature rises above the maximum set temperature by half the
+    /// deadband, and stops once it falls below it by half the deadband. Defaults to 0.5 °C.
+    ///
+    /// An Err Result is returned if the deadband is negative or not finite, or in `MaintainRange`
+    /// mode if `validate_maintain_range_config` would fail with it.
+    pub fn set_cool_deadband(&mut self, deadband: f64) -> Result<(), Error> {
+        if !deadband.is_finite() || deadband < 0.0 {
+            return Err(Error::InvalidDeadband);
+        }
+        SetPointBands {
+            cool_deadband: deadband,
+            ..self.set_point_bands()
+        }
+        .validate()?;
+        self.state.cool_deadband = deadband;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the deadband centred on the maximum set temperature
+    pub fn get_cool_deadband(&self) -> f64 {
+        self.state.cool_deadband
+    }
+
+    /// Change the minimum number of seconds heat runs once called for.
+    ///
+    /// An Err Result is returned if the run time is zero or not below the maximum heat run time.
+    pub fn set_minimum_heat_run_secs(&mut self, secs: u32) -> Result<(), Error> {
+        if secs == 0 || secs >= self.state.maximum_heat_run_secs {
+            return Err(Error::InvalidConstraint);
+        }
+        self.state.minimum_heat_run_secs = secs;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the minimum number of seconds heat runs once called for
+    pub fn get_minimum_heat_run_secs(&self) -> u32 {
+        self.state.minimum_heat_run_secs
+    }
+
+    /// Change the maximum number of seconds heat may run continuously.
+    ///
+    /// An Err Result is returned if the run time is zero or not above the minimum heat run time.
+    pub fn set_maximum_heat_run_secs(&mut self, secs: u32) -> Result<(), Error> {
+        if secs == 0 || secs <= self.state.minimum_heat_run_secs {
+            return Err(Error::InvalidConstraint);
+        }
+        self.state.maximum_heat_run_secs = secs;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the maximum number of seconds heat may run continuously
+    pub fn get_maximum_heat_run_secs(&self) -> u32 {
+        self.state.maximum_heat_run_secs
+    }
+
+    /// Change the minimum number of seconds heat stays off before it may be called for again.
+    ///
+    /// An Err Result is returned if the off time is zero.
+    pub fn set_minimum_heat_off_secs(&mut self, secs: u32) -> Result<(), Error> {
+        if secs == 0 {
+            return Err(Error::InvalidConstraint);
+        }
+        self.state.minimum_heat_off_secs = secs;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the minimum number of seconds heat stays off before it may be called for again
+    pub fn get_minimum_heat_off_secs(&self) -> u32 {
+        self.state.minimum_heat_off_secs
+    }
+
+    /// Change the minimum number of seconds cool runs once called for.
+    ///
+    /// An Err Result is returned if the run time is zero or not below the maximum cool run time.
+    pub fn set_minimum_cool_run_secs(&mut self, secs: u32) -> Result<(), Error> {
+        if secs == 0 || secs >= self.state.maximum_cool_run_secs {
+            return Err(Error::InvalidConstraint);
+        }
+        self.state.minimum_cool_run_secs = secs;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the minimum number of seconds cool runs once called for
+    pub fn get_minimum_cool_run_secs(&self) -> u32 {
+        self.state.minimum_cool_run_secs
+    }
+
+    /// Change the maximum number of seconds cool may run continuously.
+    ///
+    /// An Err Result is returned if the run time is zero or not above the minimum cool run time.
+    pub fn set_maximum_cool_run_secs(&mut self, secs: u32) -> Result<(), Error> {
+        if secs == 0 || secs <= self.state.minimum_cool_run_secs {
+            return Err(Error::InvalidConstraint);
+        }
+        self.state.maximum_cool_run_secs = secs;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the maximum number of seconds cool may run continuously
+    pub fn get_maximum_cool_run_secs(&self) -> u32 {
+        self.state.maximum_cool_run_secs
+    }
+
+    /// Change the minimum number of seconds cool stays off before it may be called for again.
+    ///
+    /// An Err Result is returned if the off time is zero.
+    pub fn set_minimum_cool_off_secs(&mut self, secs: u32) -> Result<(), Error> {
+        if secs == 0 {
+            return Err(Error::InvalidConstraint);
+        }
+        self.state.minimum_cool_off_secs = secs;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the minimum number of seconds cool stays off before it may be called for again
+    pub fn get_minimum_cool_off_secs(&self) -> u32 {
+        self.state.minimum_cool_off_secs
+    }
+
+    /// Change the minimum number of seconds fan runs once called for.
+    ///
+    /// An Err Result is returned if the run time is zero or not below the maximum fan run time.
+    pub fn set_minimum_fan_run_secs(&mut self, secs: u32) -> Result<(), Error> {
+        if secs == 0 || secs >= self.state.maximum_fan_run_secs {
+            return Err(Error::InvalidConstraint);
+        }
+        self.state.minimum_fan_run_secs = secs;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the minimum number of seconds fan runs once called for
+    pub fn get_minimum_fan_run_secs(&self) -> u32 {
+        self.state.minimum_fan_run_secs
+    }
+
+    /// Change the maximum number of seconds fan may run continuously.
+    ///
+    /// An Err Result is returned if the run time is zero or not above the minimum fan run time.
+    pub fn set_maximum_fan_run_secs(&mut self, secs: u32) -> Result<(), Error> {
+        if secs == 0 || secs <= self.state.minimum_fan_run_secs {
+            return Err(Error::InvalidConstraint);
+        }
+        self.state.maximum_fan_run_secs = secs;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the maximum number of seconds fan may run continuously
+    pub fn get_maximum_fan_run_secs(&self) -> u32 {
+        self.state.maximum_fan_run_secs
+    }
+
+    /// Change the minimum number of seconds fan stays off before it may be called for again.
+    ///
+    /// An Err Result is returned if the off time is zero.
+    pub fn set_minimum_fan_off_secs(&mut self, secs: u32) -> Result<(), Error> {
+        if secs == 0 {
+            return Err(Error::InvalidConstraint);
+        }
+        self.state.minimum_fan_off_secs = secs;
+        self.state.config_hash = None;
+        Ok(())
+    }
+    /// Get the minimum number of seconds fan stays off before it may be called for again
+    pub fn get_minimum_fan_off_secs(&self) -> u32 {
+        self.state.minimum_fan_off_secs
+    }
+
+    /// Check that the heating and cooling bands of `MaintainRange` mode do not overlap.
+    ///
+    /// Heat runs until the minimum set temperature plus the hysteresis or half the heat deadband,
+    /// whichever is wider, and cool until the maximum set temperature minus the hysteresis or
+    /// half the cool deadband, so the former must not exceed the latter or both would be called
+    /// for in between.
+    ///
+    /// An Err Result is returned if the bands overlap.
+    pub fn validate_maintain_range_config(&self) -> Result<(), Error> {
+        SetPointBands {
+            operating_mode: OperatingMode::MaintainRange,
+            ..self.set_point_bands()
+        }
+        .validate()
+    }
+
+    fn set_point_bands(&self) -> SetPointBands {
+        SetPointBands {
+            operating_mode: self.state.operating_mode,
+            minimum: self.state.minimum_set_temperature,
+            maximum: self.state.maximum_set_temperature,
+            unoccupied_minimum: self.state.unoccupied_minimum_set_temperature,
+            unoccupied_maximum: self.state.unoccupied_maximum_set_temperature,
+            hysteresis: self.state.hysteresis,
+            heat_deadband: self.state.heat_deadband,
+            cool_deadband: self.state.cool_deadband,
+        }
+    }
+    /// Get the hysteresis band applied once heating or cooling has started
+    pub fn get_hysteresis(&self) -> f64 {
+        self.state.hysteresis
+    }
+
+    /// Change the thermal mass profile used for anticipatory control.
+    ///
+    /// While trend anticipation is enabled, the measured temperature trend is projected ahead by
+    /// the profile's time constant multiplied by `get_trend_anticipation_gain`. Heating starts
+    /// once the projection falls below the minimum set point, and cooling once it rises above the
+    /// maximum set point, provided the current temperature is still within the set points. A
+    /// trend of zero, such as before two readings are available, anticipates nothing. Defaults to
+    /// `ThermalMassProfile::Medium`.
+    ///
+    /// An Err Result is returned if a custom time constant is negative or not finite.
+    pub fn set_thermal_mass(&mut self, profile: ThermalMassProfile) -> Result<(), Error> {
+        let secs = profile.time_constant_secs();
+        if !secs.is_finite() || secs < 0.0 {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.thermal_mass = profile;
+        Ok(())
+    }
+
+    /// Get the thermal mass profile used for anticipatory control
+    pub fn get_thermal_mass(&self) -> ThermalMassProfile {
+        self.state.thermal_mass
+    }
+
+    /// Change how many of the most recent temperature readings the temperature trend spans.
+    ///
+    /// Wider windows smooth out sensor noise but respond to changes more slowly. The default is 2,
+    /// the rate between the last two readings. Readings less than a second after the previous one
+    /// are not recorded.
+    ///
+    /// An Err Result is returned if the window is less than 2 or more than 16 readings.
+    pub fn set_temperature_trend_window(&mut self, window: usize) -> Result<(), Error> {
+        if !(2..=MAXIMUM_TEMPERATURE_TREND_WINDOW).contains(&window) {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.temperature_history.set_window(window);
+        self.state.temperature_trend = self.state.temperature_history.trend();
+        Ok(())
+    }
+
+    /// Get the average rate of change of the temperature across the trend window, in degrees
+    /// Celsius per minute.
+    ///
+    /// Returns zero until two readings are available.
+    pub fn get_temperature_trend_celsius_per_minute(&self) -> f64 {
+        self.state.temperature_trend * 60.0
+    }
+
+    /// Enable or disable trend anticipation. Disabled by default.
+    ///
+    /// While enabled, the temperature trend is projected ahead by the thermal mass time constant
+    /// multiplied by `get_trend_anticipation_gain`, and heating or cooling starts early once the
+    /// projection leaves the set point range, as described in `set_thermal_mass`.
+    /// An early start held back by a timing constraint is retried on the next reading and is not
+    /// reported as a constraint violation.
+    pub fn set_trend_anticipation_enabled(&mut self, enabled: bool) {
+        self.state.trend_anticipation_enabled = enabled;
+    }
+    /// Check whether trend anticipation is enabled
+    pub fn get_trend_anticipation_enabled(&self) -> bool {
+        self.state.trend_anticipation_enabled
+    }
+
+    /// Change the multiple of the thermal mass time constant that trend anticipation projects the
+    /// temperature trend ahead by. Defaults to 1.
+    ///
+    /// An Err Result is returned if the gain is negative or not finite.
+    pub fn set_trend_anticipation_gain(&mut self, gain: f64) -> Result<(), Error> {
+        if !gain.is_finite() || gain < 0.0 {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.trend_anticipation_gain = gain;
+        Ok(())
+    }
+    /// Get the multiple of the thermal mass time constant that trend anticipation projects ahead by
+    pub fn get_trend_anticipation_gain(&self) -> f64 {
+        self.state.trend_anticipation_gain
+    }
+
+    /// Change the maximum temperature rise allowed in a single heat cycle.
+    ///
+    /// When a heat cycle ends having raised the temperature by more than this amount, the
+    /// thermostat enters a fault state and refuses to start heat again until `clear_fault` is
+    /// called. Defaults to infinity (no limit).
+    ///
+    /// An Err Result is returned if the swing is not positive.
+    pub fn set_maximum_heat_swing(&mut self, swing: f64) -> Result<(), Error> {
+        if swing.is_nan() || swing <= 0.0 {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.maximum_heat_swing = swing;
+        Ok(())
+    }
+    /// Get the maximum temperature rise allowed in a single heat cycle.
+    pub fn get_maximum_heat_swing(&self) -> f64 {
+        self.state.maximum_heat_swing
+    }
+
+    /// Get the active fault, if any.
+    pub fn get_fault(&self) -> Option<Error> {
+        self.state.fault
+    }
+
+    /// Clear the active fault, allowing normal operation to resume.
+    ///
+    /// If a fault is active and hardware resets before fault clears are enabled, the interface is
+    /// asked to reset the hardware first. The reset is best effort and its result is ignored.
+    pub fn clear_fault(&mut self) {
+        if self.state.fault.is_some() && self.state.reset_hardware_before_fault_clear {
+            let _ = self.interface.reset_hardware();
+        }
+        self.state.fault = None;
+    }
+
+    /// Change whether the hardware is reset when an active fault is cleared.
+    ///
+    /// The hardware is always reset when a fault is first detected. Enabled by default.
+    pub fn set_reset_hardware_before_fault_clear(&mut self, reset: bool) {
+        self.state.reset_hardware_before_fault_clear = reset;
+    }
+    /// Get whether the hardware is reset when an active fault is cleared
+    pub fn get_reset_hardware_before_fault_clear(&self) -> bool {
+        self.state.reset_hardware_before_fault_clear
+    }
+
+    /// Change whether temperature overrun faults clear themselves.
+    ///
+    /// When enabled, a `HeatSwingExceeded` fault is cleared as if by `clear_fault` once a reading
+    /// is within the auto recovery temperature margin of the set point range. Disabled by default.
+    pub fn set_auto_recovery_enabled(&mut self, enabled: bool) {
+        self.state.auto_recovery_enabled = enabled;
+    }
+    /// Get whether temperature overrun faults clear themselves
+    pub fn get_auto_recovery_enabled(&self) -> bool {
+        self.state.auto_recovery_enabled
+    }
+
+    /// Change how far outside the set point range a reading may be for auto recovery.
+    ///
+    /// An Err Result is returned if the margin is negative or not finite.
+    pub fn set_auto_recovery_temperature_margin(&mut self, margin: f64) -> Result<(), Error> {
+        if !margin.is_finite() || margin < 0.0 {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.auto_recovery_temperature_margin = margin;
+        Ok(())
+    }
+    /// Get how far outside the set point range a reading may be for auto recovery
+    pub fn get_auto_recovery_temperature_margin(&self) -> f64 {
+        self.state.auto_recovery_temperature_margin
+    }
+
+    fn auto_recover(&mut self) {
+        let margin = self.state.auto_recovery_temperature_margin;
+        if self.state.auto_recovery_enabled
+            && self.state.fault == Some(Error::HeatSwingExceeded)
+            && self.state.current_temperature >= self.state.minimum_set_temperature - margin
+            && self.state.current_temperature <= self.state.maximum_set_temperature + margin
+        {
+            self.clear_fault();
+        }
+    }
+
+    fn set_fault(&mut self, now: u64, fault: Error) {
+        if self.state.fault.is_none() {
+            let _ = self.interface.reset_hardware(); // best effort
+        }
+        self.state.fault = Some(fault);
+        self.state.event_log.push(now, EventKind::Fault(fault));
+    }
+
+    /// Check the timing constraints for values that are likely to harm equipment in the current
+    /// operating mode.
+    ///
+    /// Returns the first concern found, if any. An Err Result is returned if a minimum run time
+    /// exceeds its maximum run time, as no cycle could then satisfy both.
+    pub fn warn_suboptimal_constraints(&self) -> Result<Option<ConstraintWarning>, Error> {
+        if self.state.minimum_heat_run_secs > self.state.maximum_heat_run_secs
+            || self.state.minimum_cool_run_secs > self.state.maximum_cool_run_secs
+            || self.state.minimum_fan_run_secs > self.state.maximum_fan_run_secs
+        {
+            return Err(Error::InvalidConfiguration);
+        }
+        let (may_heat, may_cool) = match self.state.operating_mode {
+            OperatingMode::HeatToSetPoint => (true, false),
+            OperatingMode::CoolToSetPoint => (false, true),
+            OperatingMode::DisabledUnsafe => (false, false),
+            _ => (true, true),
+        };
+        if may_cool && self.state.minimum_cool_off_secs < RECOMMENDED_MINIMUM_COMPRESSOR_OFF_SECS {
+            return Ok(Some(ConstraintWarning {
+                constraint: Error::CoolMinOffTimeConstraint,
+                description:
+                    "cool minimum off time is too short for compressor pressures to equalize",
+                recommended_minimum_secs: RECOMMENDED_MINIMUM_COMPRESSOR_OFF_SECS,
+            }));
+        }
+        if may_heat && self.state.minimum_heat_run_secs < RECOMMENDED_MINIMUM_HEAT_RUN_SECS {
+            return Ok(Some(ConstraintWarning {
+                constraint: Error::HeatMinRunTimeConstraint,
+                description: "heat minimum run time is too short for the heat exchanger to warm up",
+                recommended_minimum_secs: RECOMMENDED_MINIMUM_HEAT_RUN_SECS,
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Audit the thermostat for unsafe conditions.
+    ///
+    /// Checks that the current temperature and both set temperatures are within the safe
+    /// temperatures, that each minimum does not exceed its maximum, that there is no active fault,
+    /// and that heat and cool are not being called for at the same time. Returns `Ok(false)` if
+    /// any check fails, with the first failure available from `get_last_error`. An Err Result is
+    /// returned if the interface cannot be queried.
+    pub fn is_safe(&mut self) -> Result<bool, Error> {
+        let failure = if self.state.minimum_safe_temperature > self.state.maximum_safe_temperature
+            || self.state.minimum_set_temperature > self.state.maximum_set_temperature
+            || self.state.minimum_set_temperature < self.state.minimum_safe_temperature
+            || self.state.maximum_set_temperature > self.state.maximum_safe_temperature
+        {
+            Some(Error::InvalidConfiguration)
+        } else if self.state.current_temperature < self.state.minimum_safe_temperature
+            || self.state.current_temperature > self.state.maximum_safe_temperature
+        {
+            Some(Error::TemperatureOutsideSafeRange)
+        } else if self.state.fault.is_some() {
+            self.state.fault
+        } else if self.interface.calling_for_heat()? && self.interface.calling_for_cool()? {
+            Some(Error::ConflictingOutputs)
+        } else {
+            None
+        };
+        if let Some(error) = failure {
+            self.state.last_error = failure;
+            self.state.last_error_detail = Some(ConstraintError(error, None));
+        }
+        Ok(failure.is_none())
+    }
+
+    /// Get the most recent error reported by a temperature update or safety audit.
+    pub fn get_last_error(&self) -> Option<Error> {
+        self.state.last_error
+    }
+
+    /// Get the most recent error along with any context available when it occurred.
+    pub fn get_last_error_detail(&self) -> Option<ConstraintError> {
+        self.state.last_error_detail
+    }
+
+    /// Estimate the temperature at which the current heat cycle will end.
+    ///
+    /// Projects the current temperature trend forward over the run time remaining before the
+    /// maximum heat run time is reached. An endpoint far above the maximum set temperature
+    /// suggests runaway heating; one below the minimum set temperature suggests insufficient heat.
+    /// Returns None if heat is not running, the temperature is not changing, or the clock is
+    /// unavailable.
+    pub fn get_predicted_heat_endpoint_temperature(&self) -> Option<f64> {
+        let start = match (self.state.last_call_for_heat_start, self.state.last_call_for_heat_end) {
+            (Some(start), Some(end)) if end >= start => return None,
+            (Some(start), _) => start,
+            (None, _) => return None,
+        };
+        if self.state.temperature_trend == 0.0 {
+            return None;
+        }
+        let now = self.interface.get_seconds().ok()?;
+        let elapsed = now.saturating_sub(start);
+        let remaining_secs = (self.state.maximum_heat_run_secs as u64).saturating_sub(elapsed);
+        Some(self.state.current_temperature + self.state.temperature_trend * remaining_secs as f64)
+    }
+
+    /// Write when heat was last started, or `never`, in the `format_timestamp` format.
+    pub fn format_last_heat_start<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        format_optional_timestamp(self.state.last_call_for_heat_start, w)
+    }
+
+    /// Write when heat was last stopped, or `never`, in the `format_timestamp` format.
+    pub fn format_last_heat_end<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        format_optional_timestamp(self.state.last_call_for_heat_end, w)
+    }
+
+    /// Write when cool was last started, or `never`, in the `format_timestamp` format.
+    pub fn format_last_cool_start<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        format_optional_timestamp(self.state.last_call_for_cool_start, w)
+    }
+
+    /// Write when cool was last stopped, or `never`, in the `format_timestamp` format.
+    pub fn format_last_cool_end<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        format_optional_timestamp(self.state.last_call_for_cool_end, w)
+    }
+
+    /// Write when fan was last started, or `never`, in the `format_timestamp` format.
+    pub fn format_last_fan_start<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        format_optional_timestamp(self.state.last_call_for_fan_start, w)
+    }
+
+    /// Write when fan was last stopped, or `never`, in the `format_timestamp` format.
+    pub fn format_last_fan_end<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        format_optional_timestamp(self.state.last_call_for_fan_end, w)
+    }
+
+    /// Write a compact multi-line dump of the thermostat state for serial debugging.
+    ///
+    /// Lines are kept short for small terminals: mode and fault, temperatures, set and safe
+    /// points, timing constraints and last start/stop time for each output, then the last five
+    /// events.
+    pub fn write_debug<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(w, "{} flt:{:?}", self.state.operating_mode, self.state.fault)?;
+        writeln!(
+            w,
+            "T:{:.1} RH:{:.1} dT:{:+.4}/s",
+            self.state.current_temperature,
+            self.state.current_humidity,
+            self.state.temperature_trend
+        )?;
+        writeln!(
+            w,
+            "set:{:.1}-{:.1} safe:{:.1}-{:.1}",
+            self.state.minimum_set_temperature,
+            self.state.maximum_set_temperature,
+            self.state.minimum_safe_temperature,
+            self.state.maximum_safe_temperature
+        )?;
+        let outputs = [
+            (
+                "heat",
+                self.state.minimum_heat_run_secs,
+                self.state.maximum_heat_run_secs,
+                self.state.minimum_heat_off_secs,
+                self.state.last_call_for_heat_start,
+                self.state.last_call_for_heat_end,
+            ),
+            (
+                "cool",
+                self.state.minimum_cool_run_secs,
+                self.state.maximum_cool_run_secs,
+                self.state.minimum_cool_off_secs,
+                self.state.last_call_for_cool_start,
+                self.state.last_call_for_cool_end,
+            ),
+            (
+                "fan",
+                self.state.minimum_fan_run_secs,
+                self.state.maximum_fan_run_secs,
+                self.state.minimum_fan_off_secs,
+                self.state.last_call_for_fan_start,
+                self.state.last_call_for_fan_end,
+            ),
+        ];
+        for &(name, min_run, max_run, min_off, start, end) in outputs.iter() {
+            writeln!(
+                w,
+                "{} {}-{}/{} {:?}-{:?}",
+                name, min_run, max_run, min_off, start, end
+            )?;
+        }
+        let events = self.state.event_log.as_slice();
+        for event in &events[events.len().saturating_sub(5)..] {
+            writeln!(w, "{} {:?}", event.timestamp_secs, event.event)?;
+        }
+        Ok(())
+    }
+
+    /// Get the most recent events, oldest first.
+    pub fn get_event_log(&self) -> &[TimestampedEvent] {
+        self.state.event_log.as_slice()
+    }
+
+    /// Move up to `buffer.len()` unread events, oldest first, into the provided buffer.
+    ///
+    /// Returns the number of events written. Drained events remain visible through
+    /// `get_event_log` but are not returned by subsequent calls to this method. Events discarded
+    /// from a full log before being drained are lost.
+    pub fn drain_events(&mut self, buffer: &mut [TimestampedEvent]) -> usize {
+        self.state.event_log.drain(buffer)
+    }
+
+    /// Get the number of events waiting to be drained.
+    pub fn pending_event_count(&self) -> usize {
+        self.state.event_log.unread()
+    }
+
+    // Events are timestamped with the interface clock. If the clock is unavailable, the timestamp
+    // of the latest logged event is reused so the log stays in chronological order.
+    fn log_event(&mut self, event: EventKind) {
+        let now = match self.interface.get_seconds() {
+            Ok(now) => now,
+            Err(_) => self.state.event_log.latest().map_or(0, |e| e.timestamp_secs),
+        };
+        self.state.event_log.push(now, event);
+    }
+
+    fn log_error(&mut self, detail: ConstraintError) {
+        let error = detail.0;
+        self.state.last_error = Some(error);
+        self.state.last_error_detail = Some(detail);
+        let event = match error {
+            Error::HandlerFailed | Error::MeasurementFailed => EventKind::Fault(error),
+            _ => EventKind::ConstraintViolation(error),
+        };
+        // a held constraint is reported on every update; only log when it first occurs
+        if self.state.event_log.latest().map(|e| e.event) != Some(event) {
+            self.log_event(event);
+        }
+    }
+
+    /// Get the proportional heat demand as a percentage of full capacity.
+    ///
+    /// Demand ramps linearly from 0% at the minimum set temperature to 100% when the current
+    /// temperature is a full proportional band below it. This is the value handed to
+    /// `ThermostatInterface::set_heat_modulation` when heating.
+    pub fn get_heat_control_output(&self) -> f64 {
+        proportional_output(self.state.minimum_set_temperature - self.state.current_temperature)
+    }
+
+    /// Get the proportional cool demand as a percentage of full capacity.
+    ///
+    /// Demand ramps linearly from 0% at the maximum set temperature to 100% when the current
+    /// temperature is a full proportional band above it. This is the value handed to
+    /// `ThermostatInterface::set_cool_modulation` when cooling.
+    pub fn get_cool_control_output(&self) -> f64 {
+        proportional_output(self.state.current_temperature - self.state.maximum_set_temperature)
+    }
+
+    /// Get the seconds until the next minimum run or off time clears.
+    ///
+    /// Each output that is on counts down its minimum run time, and each output that is off its
+    /// minimum off time. Returns None if none are counting down or the current time cannot be read.
+    pub fn next_constraint_clearance_secs(&self) -> Option<u64> {
+        let now = self.interface.get_seconds().ok()?;
+        let elapsed = |since: Option<u64>| now.saturating_sub(since.unwrap_or(0));
+        let remaining = |calling: Result<bool, Error>,
+                         start: Option<u64>,
+                         end: Option<u64>,
+                         run_secs: u32,
+                         off_secs: u32| {
+            let remaining = match calling {
+                Ok(true) => (run_secs as u64).saturating_sub(elapsed(start)),
+                Ok(false) => (off_secs as u64).saturating_sub(elapsed(end)),
+                Err(_) => 0,
+            };
+            Some(remaining).filter(|&secs| secs > 0)
+        };
+        let heat = remaining(
+            self.interface.calling_for_heat(),
+            self.state.last_call_for_heat_start,
+            self.state.last_call_for_heat_end,
+            self.state.minimum_heat_run_secs,
+            self.state.minimum_heat_off_secs,
+        );
+        let cool = remaining(
+            self.interface.calling_for_cool(),
+            self.state.last_call_for_cool_start,
+            self.state.last_call_for_cool_end,
+            self.state.minimum_cool_run_secs,
+            self.state.minimum_cool_off_secs,
+        );
+        let fan = remaining(
+            self.interface.calling_for_fan(),
+            self.state.last_call_for_fan_start,
+            self.state.last_call_for_fan_end,
+            self.state.minimum_fan_run_secs,
+            self.state.minimum_fan_off_secs,
+        );
+        [heat, cool, fan].iter().filter_map(|&secs| secs).min()
+    }
+
+    /// Estimate when heat or cool will next start or stop.
+    ///
+    /// While heat or cool is called for, the next event is it stopping once the temperature passes
+    /// the set point and hysteresis. Otherwise it is heat or cool starting at the set point the
+    /// temperature trend is heading toward, in modes that maintain that set point. The time is
+    /// extrapolated from the trend and is never earlier than the relevant minimum run or off time
+    /// allows; it is None if the trend is heading away from the event.
+    ///
+    /// An Err Result is returned if the time or outputs cannot be read from the interface.
+    pub fn get_next_event_estimate(&self) -> Result<NextEventEstimate, Error> {
+        let now = self.interface.get_seconds()?;
+        let temperature = self.state.current_temperature;
+        let trend = self.state.temperature_trend;
+        let since = |time: Option<u64>| now.saturating_sub(time.unwrap_or(0));
+        // seconds for the trend to carry the temperature to the target, zero if already past it
+        let reach = |target: f64, past: bool| {
+            let secs = (target - temperature) / trend;
+            if past {
+                Some(0)
+            } else if secs.is_finite() && secs >= 0.0 {
+                Some(secs as u64)
+            } else {
+                None
+            }
+        };
+        let heats_to_set_point = matches!(
+            self.state.operating_mode,
+            OperatingMode::MaintainRange
+                | OperatingMode::HeatToSetPoint
+                | OperatingMode::Scheduling
+                | OperatingMode::HeatPump
+                | OperatingMode::Vacation
+                | OperatingMode::Unoccupied
+        );
+        let cools_to_set_point = matches!(
+            self.state.operating_mode,
+            OperatingMode::MaintainRange
+                | OperatingMode::CoolToSetPoint
+                | OperatingMode::Scheduling
+                | OperatingMode::HeatPump
+                | OperatingMode::Vacation
+                | OperatingMode::Unoccupied
+        );
+        let minimum_set_temperature = self.get_effective_minimum_set_temperature();
+        let maximum_set_temperature = self.get_effective_maximum_set_temperature();
+        let (event_type, reach_secs, constraint_secs) = if self.interface.calling_for_heat()? {
+            let band = (self.state.heat_deadband / 2.0).max(self.state.hysteresis);
+            let target = minimum_set_temperature + band;
+            (
+                ExpectedEventType::HeatWillStop,
+                reach(target, temperature >= target),
+                (self.state.minimum_heat_run_secs as u64)
+                    .saturating_sub(since(self.state.last_call_for_heat_start)),
+            )
+        } else if self.interface.calling_for_cool()? {
+            let band = (self.state.cool_deadband / 2.0).max(self.state.hysteresis);
+            let target = maximum_set_temperature - band;
+            (
+                ExpectedEventType::CoolWillStop,
+                reach(target, temperature <= target),
+                (self.state.minimum_cool_run_secs as u64)
+                    .saturating_sub(since(self.state.last_call_for_cool_start)),
+            )
+        } else if trend < 0.0 && heats_to_set_point {
+            let target = minimum_set_temperature - self.state.heat_deadband / 2.0;
+            (
+                ExpectedEventType::HeatWillStart,
+                reach(target, temperature < target),
+                (self.state.minimum_heat_off_secs as u64)
+                    .saturating_sub(since(self.state.last_call_for_heat_end)),
+            )
+        } else if trend > 0.0 && cools_to_set_point {
+            let target = maximum_set_temperature + self.state.cool_deadband / 2.0;
+            (
+                ExpectedEventType::CoolWillStart,
+                reach(target, temperature > target),
+                (self.state.minimum_cool_off_secs as u64)
+                    .saturating_sub(since(self.state.last_call_for_cool_end)),
+            )
+        } else {
+            return Ok(NextEventEstimate {
+                estimated_secs_from_now: None,
+                event_type: None,
+            });
+        };
+        Ok(NextEventEstimate {
+            estimated_secs_from_now: reach_secs.map(|secs| secs.max(constraint_secs)),
+            event_type: Some(event_type),
+        })
+    }
+
+    /// Forget when heat was last started and stopped.
+    ///
+    /// Intended for use after furnace or heat pump maintenance, when the previous cycle history no
+    /// longer reflects the equipment. The minimum off time protection restarts from system start.
+    pub fn reset_heat_timing_state(&mut self) {
+        self.state.last_call_for_heat_start = None;
+        self.state.last_call_for_heat_end = None;
+    }
+
+    /// Forget when cool was last started and stopped.
+    ///
+    /// Intended for use after compressor maintenance, when the previous cycle history no longer
+    /// reflects the equipment. The minimum off time protection restarts from system start.
+    pub fn reset_cool_timing_state(&mut self) {
+        self.state.last_call_for_cool_start = None;
+        self.state.last_call_for_cool_end = None;
+    }
+
+    /// Forget when the fan was last started and stopped.
+    ///
+    /// Intended for use after blower maintenance, when the previous cycle history no longer
+    /// reflects the equipment. The minimum off time protection restarts from system start.
+    pub fn reset_fan_timing_state(&mut self) {
+        self.state.last_call_for_fan_start = None;
+        self.state.last_call_for_fan_end = None;
+    }
+
+    fn start_heat(&mut self) -> Result<(), ConstraintError> {
+        if let Some(fault) = self.state.fault {
+            return Err(fault.into());
+        }
+        if !self.interface.calling_for_heat()? {
+            let now = self.interface.get_seconds()?;
+            if now - self.state.last_call_for_heat_end.unwrap_or(0)
+                >= self.state.minimum_heat_off_secs as u64
+            {
+                self.interface.call_for_heat()?; // we have been off long enough to start
+                self.state.last_call_for_heat_start = Some(now);
+                self.state.heat_start_temperature = self.state.current_temperature;
+                self.state.event_log.push(now, EventKind::HeatStarted);
+                if let Some(handler) = self.state.event_handler {
+                    handler.on_heat_started();
+                }
+                self.state.statistics.total_heat_cycles =
+                    self.state.statistics.total_heat_cycles.saturating_add(1);
+                Ok(())
+            } else {
+                let elapsed = now - self.state.last_call_for_heat_end.unwrap_or(0);
+                let remaining = self.state.minimum_heat_off_secs as u64 - elapsed;
+                // we haven't been off long enough
+                Err(ConstraintError(Error::HeatMinOffTimeConstraint, Some(remaining)))
+            }
+        } else {
+            let now = self.interface.get_seconds()?;
+            if now - self.state.last_call_for_heat_start.unwrap_or(0)
+                >= self.state.maximum_heat_run_secs as u64
+            {
+                self.stop_heat()?; // we have been running too long, e.g. because of a failed sensor
+                Err(Error::HeatMaxRunTimeConstraint.into())
+            } else {
+                Ok(()) // we're already heating
+            }
+        }
+    }
+
+    fn stop_heat(&mut self) -> Result<(), ConstraintError> {
+        if self.interface.calling_for_heat()? {
+            let now = self.interface.get_seconds()?;
+            if now - self.state.last_call_for_heat_start.unwrap_or(0)
+                >= self.state.minimum_heat_run_secs as u64
+            {
+                self.interface.stop_call_for_heat()?; // we have been running long enough to shut down
+                self.state.last_call_for_heat_end = Some(now);
+                self.state.event_log.push(now, EventKind::HeatStopped);
+                if let Some(handler) = self.state.event_handler {
+                    handler.on_heat_stopped();
+                }
+                self.state.heat_cycle_history
+                    .push(self.state.last_call_for_heat_start.unwrap_or(0), now);
+                self.state.statistics.total_heat_runtime_secs +=
+                    now - self.state.last_call_for_heat_start.unwrap_or(0);
+                self.state.runtime_stats.heat_total_secs +=
+                    now - self.state.last_call_for_heat_start.unwrap_or(0);
+                self.state.runtime_stats.heat_cycle_count =
+                    self.state.runtime_stats.heat_cycle_count.saturating_add(1);
+                if self.state.current_temperature - self.state.heat_start_temperature
+                    > self.state.maximum_heat_swing
+                {
+                    self.set_fault(now, Error::HeatSwingExceeded);
+                }
+                Ok(())
+            } else {
+                let elapsed = now - self.state.last_call_for_heat_start.unwrap_or(0);
+                let remaining = self.state.minimum_heat_run_secs as u64 - elapsed;
+                // we haven't been running long enough
+                Err(ConstraintError(Error::HeatMinRunTimeConstraint, Some(remaining)))
+            }
+        } else {
+            Ok(()) // no current call for heat
+        }
+    }
+
+    // Auxiliary heat ignores the compressor's timing, since it is the fallback when the compressor
+    // cannot run, but applies the heat run and off times to its own cycles.
+    fn start_aux_heat(&mut self) -> Result<(), ConstraintError> {
+        if let Some(fault) = self.state.fault {
+            return Err(fault.into());
+        }
+        let now = self.interface.get_seconds()?;
+        if !self.interface.calling_for_aux_heat()? {
+            let elapsed = now - self.state.last_call_for_aux_heat_end.unwrap_or(0);
+            if elapsed >= self.state.minimum_heat_off_secs as u64 {
+                self.interface.call_for_aux_heat()?;
+                self.state.last_call_for_aux_heat_start = Some(now);
+            } else {
+                let remaining = self.state.minimum_heat_off_secs as u64 - elapsed;
+                return Err(ConstraintError(Error::HeatMinOffTimeConstraint, Some(remaining)));
+            }
+        } else if now - self.state.last_call_for_aux_heat_start.unwrap_or(0)
+            >= self.state.maximum_heat_run_secs as u64
+        {
+            self.stop_aux_heat()?;
+            return Err(Error::HeatMaxRunTimeConstraint.into());
+        }
+        Ok(())
+    }
+
+    fn stop_aux_heat(&mut self) -> Result<(), ConstraintError> {
+        if self.interface.calling_for_aux_heat()? {
+            let now = self.interface.get_seconds()?;
+            let elapsed = now - self.state.last_call_for_aux_heat_start.unwrap_or(0);
+            if elapsed < self.state.minimum_heat_run_secs as u64 {
+                let remaining = self.state.minimum_heat_run_secs as u64 - elapsed;
+                return Err(ConstraintError(Error::HeatMinRunTimeConstraint, Some(remaining)));
+            }
+            self.interface.stop_call_for_aux_heat()?;
+            self.state.last_call_for_aux_heat_end = Some(now);
+        }
+        Ok(())
+    }
+
+    fn start_cool(&mut self) -> Result<(), ConstraintError> {
+        if !self.interface.calling_for_cool()? {
+            let now = self.interface.get_seconds()?;
+            if now - self.state.last_call_for_cool_end.unwrap_or(0)
+                >= self.state.minimum_cool_off_secs as u64
+            {
+                self.interface.call_for_cool()?; // we have been off long enough to start
+                self.state.last_call_for_cool_start = Some(now);
+                self.state.cool_start_temperature = self.state.current_temperature;
+                self.state.event_log.push(now, EventKind::CoolStarted);
+                if let Some(handler) = self.state.event_handler {
+                    handler.on_cool_started();
+                }
+                self.state.statistics.total_cool_cycles =
+                    self.state.statistics.total_cool_cycles.saturating_add(1);
+                Ok(())
+            } else {
+                let elapsed = now - self.state.last_call_for_cool_end.unwrap_or(0);
+                let remaining = self.state.minimum_cool_off_secs as u64 - elapsed;
+                // we haven't been off long enough
+                Err(ConstraintError(Error::CoolMinOffTimeConstraint, Some(remaining)))
+            }
+        } else {
+            let now = self.interface.get_seconds()?;
+            if now - self.state.last_call_for_cool_start.unwrap_or(0)
+                >= self.state.maximum_cool_run_secs as u64
+            {
+                self.stop_cool()?; // we have been running too long, e.g. because of a failed sensor
+                Err(Error::CoolMaxRunTimeConstraint.into())
+            } else {
+                Ok(()) // we're already cooling
+            }
+        }
+    }
+
+    fn stop_cool(&mut self) -> Result<(), ConstraintError> {
+        if self.interface.calling_for_cool()? {
             let now = self.interface.get_seconds()?;
-            if now - self.last_call_for_cool_start.unwrap_or(0) >= self.minimum_cool_run_secs as u64
+            if now - self.state.last_call_for_cool_start.unwrap_or(0)
+                >= self.state.minimum_cool_run_secs as u64
             {
                 self.interface.stop_call_for_cool()?; // we have been running long enough to shut down
-                self.last_call_for_cool_end = Some(now);
+                self.state.last_call_for_cool_end = Some(now);
+                #[cfg(feature = "defrost")]
+                {
+                    self.state.cumulative_cool_runtime_since_defrost +=
+                        now - self.state.last_call_for_cool_start.unwrap_or(0);
+                }
+                self.state.event_log.push(now, EventKind::CoolStopped);
+                if let Some(handler) = self.state.event_handler {
+                    handler.on_cool_stopped();
+                }
+                self.state.cool_cycle_history
+                    .push(self.state.last_call_for_cool_start.unwrap_or(0), now);
+                self.state.statistics.total_cool_runtime_secs +=
+                    now - self.state.last_call_for_cool_start.unwrap_or(0);
+                self.state.runtime_stats.cool_total_secs +=
+                    now - self.state.last_call_for_cool_start.unwrap_or(0);
+                self.state.runtime_stats.cool_cycle_count =
+                    self.state.runtime_stats.cool_cycle_count.saturating_add(1);
+                self.state.total_cool_temperature_drop +=
+                    self.state.cool_start_temperature - self.state.current_temperature;
                 Ok(())
             } else {
-                Err(Error::CoolMinRunTimeConstraint) // we haven't been running long enough
+                let elapsed = now - self.state.last_call_for_cool_start.unwrap_or(0);
+                let remaining = self.state.minimum_cool_run_secs as u64 - elapsed;
+                // we haven't been running long enough
+                Err(ConstraintError(Error::CoolMinRunTimeConstraint, Some(remaining)))
             }
         } else {
             Ok(()) // no current call for cool
         }
     }
 
-    fn start_fan(&mut self) -> Result<(), Error> {
+    fn start_fan(&mut self) -> Result<(), ConstraintError> {
         if !self.interface.calling_for_fan()? {
             let now = self.interface.get_seconds()?;
-            if now - self.last_call_for_fan_end.unwrap_or(0) >= self.minimum_fan_off_secs as u64 {
+            if now - self.state.last_call_for_fan_end.unwrap_or(0)
+                >= self.state.minimum_fan_off_secs as u64
+            {
                 self.interface.call_for_fan()?; // we have been off long enough to start
-                self.last_call_for_fan_start = Some(now);
+                self.state.last_call_for_fan_start = Some(now);
+                self.state.event_log.push(now, EventKind::FanStarted);
+                if let Some(handler) = self.state.event_handler {
+                    handler.on_fan_started();
+                }
+                self.state.statistics.total_fan_cycles =
+                    self.state.statistics.total_fan_cycles.saturating_add(1);
                 Ok(())
             } else {
-                Err(Error::FanMinOffTimeConstraint) // we haven't been off long enough
+                let elapsed = now - self.state.last_call_for_fan_end.unwrap_or(0);
+                let remaining = self.state.minimum_fan_off_secs as u64 - elapsed;
+                // we haven't been off long enough
+                Err(ConstraintError(Error::FanMinOffTimeConstraint, Some(remaining)))
+            }
+        } else {
+            let now = self.interface.get_seconds()?;
+            if now - self.state.last_call_for_fan_start.unwrap_or(0)
+                >= self.state.maximum_fan_run_secs as u64
+            {
+                self.stop_fan()?; // we have been running too long, e.g. because of a failed sensor
+                Err(Error::FanMaxRunTimeConstraint.into())
+            } else {
+                Ok(()) // we're already faning
             }
-        } else {
-            Ok(()) // we're already faning
         }
     }
 
-    fn stop_fan(&mut self) -> Result<(), Error> {
+    fn stop_fan(&mut self) -> Result<(), ConstraintError> {
         if self.interface.calling_for_fan()? {
             let now = self.interface.get_seconds()?;
-            if now - self.last_call_for_fan_start.unwrap_or(0) >= self.minimum_fan_run_secs as u64 {
+            if now - self.state.last_call_for_fan_start.unwrap_or(0)
+                >= self.state.minimum_fan_run_secs as u64
+            {
                 self.interface.stop_call_for_fan()?; // we have been running long enough to shut down
-                self.last_call_for_fan_end = Some(now);
+                self.state.last_call_for_fan_end = Some(now);
+                self.state.event_log.push(now, EventKind::FanStopped);
+                if let Some(handler) = self.state.event_handler {
+                    handler.on_fan_stopped();
+                }
+                self.state.statistics.total_fan_runtime_secs +=
+                    now - self.state.last_call_for_fan_start.unwrap_or(0);
+                self.state.runtime_stats.fan_total_secs +=
+                    now - self.state.last_call_for_fan_start.unwrap_or(0);
+                self.state.runtime_stats.fan_cycle_count =
+                    self.state.runtime_stats.fan_cycle_count.saturating_add(1);
+                self.state.filter_runtime_secs += now.saturating_sub(
+                    self.state.last_call_for_fan_start.unwrap_or(0).max(self.state.filter_reset_at),
+                );
                 Ok(())
             } else {
-                Err(Error::FanMinRunTimeConstraint) // we haven't been running long enough
+                let elapsed = now - self.state.last_call_for_fan_start.unwrap_or(0);
+                let remaining = self.state.minimum_fan_run_secs as u64 - elapsed;
+                // we haven't been running long enough
+                Err(ConstraintError(Error::FanMinRunTimeConstraint, Some(remaining)))
             }
         } else {
             Ok(()) // no current call for fan
         }
     }
 
-    fn heat(&mut self) -> Result<(), Error> {
-        self.stop_cool()?;
+    // Outputs always start and stop through the binary helpers so the timing constraints and
+    // statistics apply; modulating equipment additionally gets its output percentage once the
+    // call has started or stopped. A failure to set the percentage means the equipment is binary,
+    // which the on/off call has already handled.
+    fn modulate_heat(&mut self, percent: f64) -> Result<(), ConstraintError> {
+        if percent > 0.0 {
+            self.start_heat()?;
+        } else {
+            self.stop_heat()?;
+        }
+        let _ = self.interface.set_heat_modulation(percent);
+        Ok(())
+    }
+
+    fn modulate_cool(&mut self, percent: f64) -> Result<(), ConstraintError> {
+        if percent > 0.0 {
+            self.start_cool()?;
+        } else {
+            self.stop_cool()?;
+        }
+        let _ = self.interface.set_cool_modulation(percent);
+        Ok(())
+    }
+
+    // The heat pump compressor is locked out while it is too cold outdoors, unless the space has
+    // already fallen below the minimum safe temperature.
+    fn heat_pump_locked_out(&self) -> bool {
+        match (self.state.outdoor_temperature, self.state.heat_pump_lockout_temperature) {
+            (Some(outdoor), Some(lockout)) => {
+                self.state.operating_mode == OperatingMode::HeatPump
+                    && outdoor < lockout
+                    && self.state.current_temperature >= self.state.minimum_safe_temperature
+            }
+            _ => false,
+        }
+    }
+
+    fn heat(&mut self) -> Result<(), ConstraintError> {
+        self.modulate_cool(0.0)?;
+        self.shift_reversing_valve(false)?;
         self.start_fan()?;
-        self.start_heat()?;
+        if self.heat_pump_locked_out() {
+            // auxiliary heat takes over from the compressor until the lockout clears
+            self.modulate_heat(0.0)?;
+            return self.start_aux_heat().map_err(|error| match error.0 {
+                Error::HandlerFailed => Error::OutdoorLockout.into(),
+                _ => error,
+            });
+        }
+        self.stop_aux_heat()?;
+        // safety limits may call for heat with no set point demand, so never modulate to zero
+        let output = self.get_heat_control_output().max(MINIMUM_MODULATION_OUTPUT);
+        self.modulate_heat(output)?;
         Ok(())
     }
 
-    fn cool(&mut self) -> Result<(), Error> {
-        self.stop_heat()?;
+    fn cool(&mut self) -> Result<(), ConstraintError> {
+        self.modulate_heat(0.0)?;
+        self.stop_aux_heat()?;
+        self.shift_reversing_valve(true)?;
         self.start_fan()?;
-        self.start_cool()?;
+        let output = self.get_cool_control_output().max(MINIMUM_MODULATION_OUTPUT);
+        self.modulate_cool(output)?;
+        Ok(())
+    }
+
+    // Moves the reversing valve into position for heating or cooling in the `HeatPump` mode, and
+    // holds the compressor off until the valve has had time to shift. The compressor must already
+    // be stopped.
+    fn shift_reversing_valve(&mut self, engaged: bool) -> Result<(), ConstraintError> {
+        if self.state.operating_mode != OperatingMode::HeatPump {
+            return Ok(());
+        }
+        let now = self.interface.get_seconds()?;
+        if self.interface.calling_for_reversing_valve()? != engaged {
+            if engaged {
+                self.interface.engage_reversing_valve()?;
+            } else {
+                self.interface.disengage_reversing_valve()?;
+            }
+            self.state.reversing_valve_switched_at = Some(now);
+        }
+        let elapsed = now.saturating_sub(self.state.reversing_valve_switched_at.unwrap_or(0));
+        if self.state.reversing_valve_switched_at.is_some()
+            && elapsed < REVERSING_VALVE_DELAY_SECS
+        {
+            let remaining = REVERSING_VALVE_DELAY_SECS - elapsed;
+            return Err(ConstraintError(Error::ReversingValveShiftConstraint, Some(remaining)));
+        }
         Ok(())
     }
 
-    fn fan(&mut self) -> Result<(), Error> {
+    fn fan(&mut self) -> Result<(), ConstraintError> {
         self.start_fan()?;
         Ok(())
     }
 
-    fn off(&mut self) -> Result<(), Error> {
-        self.stop_cool()?;
-        self.stop_heat()?;
+    /// Call for heat immediately, bypassing every timing, temperature, and mode check.
+    ///
+    /// Only permitted in the `DisabledUnsafe` operating mode. In any other mode this panics in
+    /// debug builds and returns `Err(Error::InvalidConfiguration)` in release builds.
+    ///
+    /// # Safety
+    ///
+    /// This does not cause memory unsafety. It is marked `unsafe` because it can damage equipment
+    /// (e.g., short-cycling a compressor) or drive the controlled space to dangerous temperatures.
+    /// The caller is responsible for protecting the equipment and the space.
+    #[allow(unsafe_code)]
+    pub unsafe fn force_heat_unsafe(&mut self) -> Result<(), Error> {
+        self.check_unsafe_mode()?;
+        let before = self.output_state().ok();
+        self.interface.call_for_heat()?;
+        self.record_transition(before, TransitionTrigger::ManualCommand);
+        Ok(())
+    }
+
+    /// Call for cool immediately, bypassing every timing, temperature, and mode check.
+    ///
+    /// Only permitted in the `DisabledUnsafe` operating mode. In any other mode this panics in
+    /// debug builds and returns `Err(Error::InvalidConfiguration)` in release builds.
+    ///
+    /// # Safety
+    ///
+    /// This does not cause memory unsafety. It is marked `unsafe` because it can damage equipment
+    /// (e.g., short-cycling a compressor) or drive the controlled space to dangerous temperatures.
+    /// The caller is responsible for protecting the equipment and the space.
+    #[allow(unsafe_code)]
+    pub unsafe fn force_cool_unsafe(&mut self) -> Result<(), Error> {
+        self.check_unsafe_mode()?;
+        let before = self.output_state().ok();
+        self.interface.call_for_cool()?;
+        self.record_transition(before, TransitionTrigger::ManualCommand);
+        Ok(())
+    }
+
+    fn check_unsafe_mode(&self) -> Result<(), Error> {
+        debug_assert!(
+            self.state.operating_mode == OperatingMode::DisabledUnsafe,
+            "forced outputs require the DisabledUnsafe operating mode"
+        );
+        if self.state.operating_mode == OperatingMode::DisabledUnsafe {
+            Ok(())
+        } else {
+            Err(Error::InvalidConfiguration)
+        }
+    }
+
+    // Stop outputs before starting others so heat and cool are never on together unless the
+    // decision explicitly asks for both.
+    fn apply_control_decision(&mut self, decision: ControlDecision) -> Result<(), ConstraintError> {
+        if !decision.call_cool {
+            self.stop_cool()?;
+        }
+        if !decision.call_heat {
+            self.stop_heat()?;
+        }
+        if !decision.call_fan {
+            self.stop_fan()?;
+        }
+        if decision.call_fan {
+            self.start_fan()?;
+        }
+        if decision.call_heat {
+            self.start_heat()?;
+        }
+        if decision.call_cool {
+            self.start_cool()?;
+        }
+        Ok(())
+    }
+
+    fn off(&mut self) -> Result<(), ConstraintError> {
+        self.modulate_cool(0.0)?;
+        self.modulate_heat(0.0)?;
+        self.stop_aux_heat()?;
         self.stop_fan()?;
         Ok(())
     }
 
-    /// Update the thermostat with a new temperature reading
-    pub fn set_current_temperature(&mut self, temperature: f64) -> Result<(), Error> {
-        self.current_temperature = temperature;
-        if (temperature < self.minimum_safe_temperature
-            && self.operating_mode != OperatingMode::DisabledUnsafe)
-            || (temperature < self.minimum_set_temperature
-                && self.operating_mode != OperatingMode::CoolToSetPoint)
+    /// Update the thermostat with a new temperature reading.
+    ///
+    /// Returns the resulting change in outputs, or `Action::NoChange` if the outputs cannot be
+    /// read from the interface.
+    pub fn set_current_temperature<T: Into<Temperature<Celsius>>>(
+        &mut self,
+        temperature: T,
+    ) -> Result<Action, Error> {
+        let temperature = temperature.into().as_f64();
+        if !temperature.is_finite() {
+            return Err(Error::InvalidTemperature);
+        }
+        self.state.last_raw_temperature = temperature;
+        let temperature = self.state.temperature_filter.push(temperature);
+        let temperature = self.round_temperature(temperature);
+        #[cfg(feature = "testing")]
+        let temperature = self.injected_temperature_or(temperature);
+        self.state.current_temperature = temperature;
+        self.state.readings_received = self.state.readings_received.saturating_add(1);
+        if let Ok(now) = self.interface.get_seconds() {
+            self.record_reading(now, temperature);
+            self.record_heat_lead(now, temperature);
+        }
+        if let Ok(occupied) = self.interface.get_occupancy_input() {
+            self.follow_occupancy(occupied);
+        }
+        self.auto_recover();
+        let result = self.reevaluate(TransitionTrigger::TemperatureReading(temperature));
+        if let Some(hook) = self.post_update_hook {
+            hook(self);
+        }
+        result
+    }
+
+    // Runs the control logic on the current temperature and records the outcome, attributing any
+    // change in outputs to the trigger unless a fault or cleared timing constraint explains it.
+    pub(crate) fn reevaluate(&mut self, trigger: TransitionTrigger) -> Result<Action, Error> {
+        let before = self.output_state().ok();
+        let faulted = self.state.fault.is_some();
+        let result = self.update_effective();
+        let result = result.and(self.update_humidity());
+        if let Err(error) = result {
+            self.log_error(error);
+        }
+        let trigger = if !faulted && self.state.fault.is_some() {
+            TransitionTrigger::FaultDetected
+        } else if self.state.timing_constrained {
+            TransitionTrigger::TimingConstraintCleared
+        } else {
+            trigger
+        };
+        self.record_transition(before, trigger);
+        let action = match (before, self.output_state()) {
+            (Some(from_state), Ok(to_state)) => Action::between(from_state, to_state),
+            _ => Action::NoChange,
+        };
+        self.state.timing_constrained = match result {
+            Err(error) => is_timing_constraint(error.0),
+            Ok(()) => false,
+        };
+        let alarm = self.update_alarm();
+        self.update_filter_alert();
+        result.map_err(Error::from).and(alarm).map(|()| action)
+    }
+
+    /// Act on a synthetic temperature reading for a while, for factory testing of equipment.
+    ///
+    /// The thermostat behaves as if `set_current_temperature(temperature)` was called, and keeps
+    /// using the injected temperature in place of real readings until `duration_secs` have passed.
+    /// The first reading after that is used as normal.
+    ///
+    /// An Err Result is returned, and real readings are used as before, if the temperature is not
+    /// finite. Only available with the `testing` feature; otherwise an Err Result is always
+    /// returned.
+    pub fn inject_test_temperature(
+        &mut self,
+        temperature: f64,
+        duration_secs: u64,
+    ) -> Result<(), Error> {
+        if !temperature.is_finite() {
+            return Err(Error::InvalidTemperature);
+        }
+        #[cfg(feature = "testing")]
+        {
+            let now = self.interface.get_seconds()?;
+            let temperature = self.round_temperature(temperature);
+            self.state.injected_temperature =
+                Some((temperature, now.saturating_add(duration_secs)));
+            self.set_current_temperature(temperature).map(|_| ())
+        }
+        #[cfg(not(feature = "testing"))]
+        {
+            let _ = (temperature, duration_secs);
+            Err(Error::InvalidConfiguration)
+        }
+    }
+
+    // Substitutes an injected test temperature for the reading until the injection expires.
+    #[cfg(feature = "testing")]
+    fn injected_temperature_or(&mut self, reading: f64) -> f64 {
+        match self.state.injected_temperature {
+            Some((temperature, until)) => match self.interface.get_seconds() {
+                Ok(now) if now >= until => {
+                    self.state.injected_temperature = None;
+                    reading
+                }
+                _ => temperature,
+            },
+            None => reading,
+        }
+    }
+
+    /// Get the most recent change in outputs, if any.
+    pub fn get_last_transition(&self) -> Option<Transition> {
+        self.state.last_transition
+    }
+
+    fn output_state(&self) -> Result<SystemState, Error> {
+        Ok(SystemState {
+            heat: self.interface.calling_for_heat()?,
+            cool: self.interface.calling_for_cool()?,
+            fan: self.interface.calling_for_fan()?,
+        })
+    }
+
+    // Records a transition from `before` if the outputs have since changed. Transitions are not
+    // recorded when the outputs or clock cannot be read.
+    fn record_transition(&mut self, before: Option<SystemState>, trigger: TransitionTrigger) {
+        if let (Some(from_state), Ok(to_state), Ok(now)) =
+            (before, self.output_state(), self.interface.get_seconds())
+        {
+            if from_state != to_state {
+                self.state.last_transition = Some(Transition {
+                    from_state,
+                    to_state,
+                    trigger,
+                    timestamp_secs: now,
+                });
+            }
+        }
+    }
+
+    /// Change the temperature above which the external alarm is triggered.
+    ///
+    /// Alarm thresholds are independent of the safe temperatures: they only notify, and never
+    /// change what the thermostat calls for. No alarm threshold is set by default.
+    ///
+    /// An Err Result is returned if the temperature is not finite or not above the low
+    /// temperature alarm.
+    pub fn set_high_temperature_alarm<T: Into<Temperature<Celsius>>>(
+        &mut self,
+        temperature: T,
+    ) -> Result<(), Error> {
+        let temperature = self.round_temperature(temperature.into().as_f64());
+        if !temperature.is_finite()
+            || self.state.low_temperature_alarm.is_some_and(|low| temperature <= low)
+        {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.high_temperature_alarm = Some(temperature);
+        Ok(())
+    }
+    /// Get the temperature above which the external alarm is triggered, if set
+    pub fn get_high_temperature_alarm(&self) -> Option<f64> {
+        self.state.high_temperature_alarm
+    }
+
+    /// Change the temperature below which the external alarm is triggered.
+    ///
+    /// An Err Result is returned if the temperature is not finite or not below the high
+    /// temperature alarm.
+    pub fn set_low_temperature_alarm<T: Into<Temperature<Celsius>>>(
+        &mut self,
+        temperature: T,
+    ) -> Result<(), Error> {
+        let temperature = self.round_temperature(temperature.into().as_f64());
+        if !temperature.is_finite()
+            || self.state.high_temperature_alarm.is_some_and(|high| temperature >= high)
+        {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.low_temperature_alarm = Some(temperature);
+        Ok(())
+    }
+    /// Get the temperature below which the external alarm is triggered, if set
+    pub fn get_low_temperature_alarm(&self) -> Option<f64> {
+        self.state.low_temperature_alarm
+    }
+
+    /// Check whether the external alarm is currently triggered
+    pub fn is_alarm_active(&self) -> bool {
+        self.state.alarm_active
+    }
+
+    // Triggers the alarm when a threshold is first crossed and clears it once back within them.
+    fn update_alarm(&mut self) -> Result<(), Error> {
+        let temperature = self.state.current_temperature;
+        let alarming = self.state.high_temperature_alarm.is_some_and(|high| temperature > high)
+            || self.state.low_temperature_alarm.is_some_and(|low| temperature < low);
+        if alarming && !self.state.alarm_active {
+            self.interface.trigger_alarm()?;
+            self.state.alarm_active = true;
+            self.log_error(Error::AlarmActive.into());
+        } else if !alarming && self.state.alarm_active {
+            self.interface.clear_alarm()?;
+            self.state.alarm_active = false;
+        }
+        Ok(())
+    }
+
+    /// Update the source temperature used by `OperatingMode::Differential`, e.g. a solar collector
+    ///
+    /// The new value takes effect on the next call to `set_current_temperature`.
+    pub fn set_source_temperature<T: Into<Temperature<Celsius>>>(&mut self, temperature: T) {
+        self.state.source_temperature = self.round_temperature(temperature.into().as_f64());
+    }
+
+    /// Request or cancel recirculation in the `RecirculationOnly` operating mode
+    ///
+    /// Intended to be driven by a ventilation schedule or an air quality sensor such as CO2. The
+    /// new value takes effect on the next call to `set_current_temperature`.
+    pub fn set_recirculation_demand(&mut self, demand: bool) {
+        self.state.recirculation_demand = demand;
+    }
+
+    /// Get whether recirculation is requested
+    pub fn get_recirculation_demand(&self) -> bool {
+        self.state.recirculation_demand
+    }
+
+    /// Update the outdoor temperature used by efficiency estimates and the heat pump lockout
+    ///
+    /// An Err Result is returned if the temperature is not finite.
+    pub fn set_outdoor_temperature<T: Into<Temperature<Celsius>>>(
+        &mut self,
+        temperature: T,
+    ) -> Result<(), Error> {
+        let temperature = temperature.into().as_f64();
+        if !temperature.is_finite() {
+            return Err(Error::InvalidTemperature);
+        }
+        self.state.outdoor_temperature = Some(self.round_temperature(temperature));
+        Ok(())
+    }
+
+    /// Get the outdoor temperature, if one has been provided
+    pub fn get_outdoor_temperature(&self) -> Option<f64> {
+        self.state.outdoor_temperature
+    }
+
+    /// Change the outdoor temperature below which the compressor is not run in the `HeatPump`
+    /// operating mode, protecting a heat pump that cannot run in the cold.
+    ///
+    /// While the outdoor temperature is below the lockout, a running compressor is stopped once it
+    /// has reached its minimum run time and heat is called for from auxiliary heat instead. If the
+    /// interface cannot call for auxiliary heat, heating fails with `Error::OutdoorLockout`. The
+    /// lockout clears once the outdoor temperature is no longer below it, and never applies while
+    /// the current temperature is below the minimum safe temperature. There is no lockout until
+    /// one is set or while the outdoor temperature is unknown.
+    ///
+    /// An Err Result is returned if the temperature is not finite.
+    pub fn set_heat_pump_lockout_temperature<T: Into<Temperature<Celsius>>>(
+        &mut self,
+        temperature: T,
+    ) -> Result<(), Error> {
+        let temperature = temperature.into().as_f64();
+        if !temperature.is_finite() {
+            return Err(Error::InvalidTemperature);
+        }
+        self.state.heat_pump_lockout_temperature = Some(self.round_temperature(temperature));
+        Ok(())
+    }
+
+    /// Get the outdoor temperature below which heat is not started, if one has been set
+    pub fn get_heat_pump_lockout_temperature(&self) -> Option<f64> {
+        self.state.heat_pump_lockout_temperature
+    }
+
+    /// Get the source temperature used by `OperatingMode::Differential`
+    pub fn get_source_temperature(&self) -> f64 {
+        self.state.source_temperature
+    }
+
+    /// Change the differential above which `OperatingMode::Differential` starts the fan
+    ///
+    /// An Err Result is returned if the threshold is not finite or not above the off threshold.
+    pub fn set_differential_on_threshold(&mut self, threshold: f64) -> Result<(), Error> {
+        if !threshold.is_finite() || threshold <= self.state.differential_off_threshold {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.differential_on_threshold = threshold;
+        Ok(())
+    }
+
+    /// Get the differential above which `OperatingMode::Differential` starts the fan
+    pub fn get_differential_on_threshold(&self) -> f64 {
+        self.state.differential_on_threshold
+    }
+
+    /// Change the differential below which `OperatingMode::Differential` stops the fan
+    ///
+    /// An Err Result is returned if the threshold is not finite or not below the on threshold.
+    pub fn set_differential_off_threshold(&mut self, threshold: f64) -> Result<(), Error> {
+        if !threshold.is_finite() || threshold >= self.state.differential_on_threshold {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.differential_off_threshold = threshold;
+        Ok(())
+    }
+
+    /// Get the differential below which `OperatingMode::Differential` stops the fan
+    pub fn get_differential_off_threshold(&self) -> f64 {
+        self.state.differential_off_threshold
+    }
+
+    /// Check whether a valid temperature reading has been received.
+    ///
+    /// Until the first finite reading is passed to `set_current_temperature`, the current
+    /// temperature is only the constructor default and the control logic will not fire.
+    pub fn is_ready(&self) -> bool {
+        self.state.readings_received > 0
+    }
+
+    /// Get the number of valid temperature readings received
+    pub fn get_readings_received(&self) -> u32 {
+        self.state.readings_received
+    }
+
+    /// Record the current time as the time of the latest temperature reading.
+    ///
+    /// Call this when the sensor is read, e.g. alongside `set_current_temperature`, to track the
+    /// age of the reading with `get_temperature_age_secs`. This is informational only and never
+    /// raises a fault.
+    ///
+    /// An Err Result is returned if the current time cannot be read from the interface.
+    pub fn mark_temperature_reading_time(&mut self) -> Result<(), Error> {
+        self.state.last_reading_timestamp = Some(self.interface.get_seconds()?);
+        Ok(())
+    }
+
+    /// Get how many seconds ago the latest temperature reading was marked.
+    ///
+    /// Returns None if `mark_temperature_reading_time` has never been called. An Err Result is
+    /// returned if the current time cannot be read from the interface.
+    pub fn get_temperature_age_secs(&self) -> Result<Option<u64>, Error> {
+        let now = self.interface.get_seconds()?;
+        Ok(self
+            .state
+            .last_reading_timestamp
+            .map(|timestamp| now.saturating_sub(timestamp)))
+    }
+
+    /// Check whether the space was occupied at the last temperature reading.
+    ///
+    /// Updated from `ThermostatInterface::get_occupancy_input` with every reading; if the input
+    /// cannot be read, the previous value is kept. True until the first reading.
+    pub fn is_occupancy_detected(&self) -> bool {
+        self.state.occupancy_detected
+    }
+
+    // Sets back when the occupancy input changes, leaving the mode alone while the input holds so
+    // a manual `set_occupancy` or mode change lasts. A disabled thermostat stays disabled and
+    // vacation mode ignores occupancy.
+    fn follow_occupancy(&mut self, occupied: bool) {
+        if occupied == self.state.occupancy_detected {
+            return;
+        }
+        self.state.occupancy_detected = occupied;
+        let ignored = matches!(
+            self.state.operating_mode,
+            OperatingMode::Disabled | OperatingMode::DisabledUnsafe | OperatingMode::Vacation
+        );
+        if !ignored {
+            if let Err(error) = self.set_occupancy(occupied) {
+                self.log_error(error.into());
+            }
+        }
+    }
+
+    // Tracks the rate of change in degrees per second across the trend window.
+    fn record_reading(&mut self, now: u64, temperature: f64) {
+        if let Some((then, _)) = self.state.temperature_history.latest() {
+            if now <= then {
+                return; // keep the earliest reading within a second for a meaningful rate
+            }
+        }
+        self.state.temperature_history.push(now, temperature);
+        self.state.temperature_trend = self.state.temperature_history.trend();
+    }
+
+    // Controls on the effective set points, which depend on the operating mode and any ramp in
+    // progress.
+    fn update_effective(&mut self) -> Result<(), ConstraintError> {
+        self.advance_set_point_ramp();
+        // override the set points for this update only
+        let minimum_set_temperature = self.state.minimum_set_temperature;
+        let maximum_set_temperature = self.state.maximum_set_temperature;
+        self.state.minimum_set_temperature = self.get_effective_minimum_set_temperature();
+        self.state.maximum_set_temperature = self.get_effective_maximum_set_temperature();
+        let result = self.update();
+        self.state.minimum_set_temperature = minimum_set_temperature;
+        self.state.maximum_set_temperature = maximum_set_temperature;
+        result
+    }
+
+    // Measures how long a heat cycle took to reach the minimum set temperature, once per cycle.
+    fn record_heat_lead(&mut self, now: u64, temperature: f64) {
+        if !self.state.adaptive_start_enabled || temperature < self.state.minimum_set_temperature {
+            return;
+        }
+        let start = match self.state.last_call_for_heat_start {
+            Some(start) if self.state.adaptive_heat_cycle != Some(start) => start,
+            _ => return,
+        };
+        if self.interface.calling_for_heat() != Ok(true) {
+            return;
+        }
+        self.state.adaptive_heat_cycle = Some(start);
+        let lead = now.saturating_sub(start) as f64;
+        self.state.adaptive_heat_lead_secs = if self.state.adaptive_heat_lead_secs == 0 {
+            lead as u64
+        } else {
+            let average = self.state.adaptive_heat_lead_secs as f64;
+            // round to the nearest second; the average is never negative
+            (average + ADAPTIVE_START_SMOOTHING * (lead - average) + 0.5) as u64
+        };
+    }
+
+    fn update(&mut self) -> Result<(), ConstraintError> {
+        if !self.is_ready() {
+            return Ok(());
+        }
+        let temperature = self.state.current_temperature;
+        let breached = (temperature < self.state.minimum_safe_temperature
+            || temperature > self.state.maximum_safe_temperature)
+            && self.state.operating_mode != OperatingMode::DisabledUnsafe;
+        if breached && !self.state.safety_limit_breached {
+            if let Some(handler) = self.state.event_handler {
+                handler.on_safety_limit_breached(temperature);
+            }
+        }
+        self.state.safety_limit_breached = breached;
+        #[cfg(feature = "defrost")]
+        {
+            // a defrost cycle that cannot start or stop must not hold up temperature control
+            match self.update_defrost() {
+                Ok(true) => return Ok(()),
+                Ok(false) => {}
+                Err(error) => self.log_error(error),
+            }
+        }
+        if self.state.operating_mode == OperatingMode::Custom
+            && temperature >= self.state.minimum_safe_temperature
+            && temperature <= self.state.maximum_safe_temperature
+        {
+            if let Some(control) = self.state.custom_control_fn {
+                let decision = control(temperature, self.state.current_humidity);
+                return self.apply_control_decision(decision);
+            }
+        }
+        if self.state.operating_mode == OperatingMode::Differential
+            && temperature >= self.state.minimum_safe_temperature
+            && temperature <= self.state.maximum_safe_temperature
+        {
+            let differential = self.state.source_temperature - temperature;
+            let call_fan = if differential > self.state.differential_on_threshold {
+                true
+            } else if differential < self.state.differential_off_threshold {
+                false
+            } else {
+                self.interface.calling_for_fan()? // hold the current state between thresholds
+            };
+            return self.apply_control_decision(ControlDecision {
+                call_fan,
+                ..ControlDecision::default()
+            });
+        }
+        if self.state.operating_mode == OperatingMode::RecirculationOnly
+            && temperature >= self.state.minimum_safe_temperature
+            && temperature <= self.state.maximum_safe_temperature
+        {
+            return self.apply_control_decision(ControlDecision {
+                call_fan: self.state.recirculation_demand,
+                ..ControlDecision::default()
+            });
+        }
+        if self.state.operating_mode == OperatingMode::FanOnly
+            && temperature >= self.state.minimum_safe_temperature
+            && temperature <= self.state.maximum_safe_temperature
+        {
+            self.modulate_cool(0.0)?;
+            self.modulate_heat(0.0)?;
+            return self.fan();
+        }
+        if self.state.operating_mode == OperatingMode::EmergencyHeat {
+            let heating = self.interface.calling_for_aux_heat()?;
+            // the primary heat is shut down on entering this mode; if it is somehow still running,
+            // report it but keep the auxiliary heat in control
+            if let Err(error) = self.stop_heat() {
+                self.log_error(error);
+            }
+            if temperature > self.state.maximum_safe_temperature {
+                self.stop_aux_heat()?;
+                return self.cool();
+            }
+            self.modulate_cool(0.0)?;
+            return if self.heat_demand(temperature, heating) {
+                self.start_fan()?;
+                self.start_aux_heat()
+            } else {
+                self.stop_aux_heat()?;
+                self.stop_fan()
+            };
+        }
+        if self.state.operating_mode == OperatingMode::Scheduling {
+            let now = self.interface.get_seconds()?;
+            let hour = (now / 3600 % 24) as u8;
+            let minute = (now / 60 % 60) as u8;
+            if let Some(entry) = self.interface.get_schedule_entry(hour, minute)? {
+                if !entry.heat_set_point.is_finite()
+                    || !entry.cool_set_point.is_finite()
+                    || entry.heat_set_point > entry.cool_set_point
+                {
+                    return Err(Error::InvalidConfiguration.into());
+                }
+                // override the set points for this update only
+                let minimum_set_temperature = self.state.minimum_set_temperature;
+                let maximum_set_temperature = self.state.maximum_set_temperature;
+                self.state.minimum_set_temperature = entry.heat_set_point;
+                self.state.maximum_set_temperature = entry.cool_set_point;
+                let result = self.maintain_set_points(temperature);
+                self.state.minimum_set_temperature = minimum_set_temperature;
+                self.state.maximum_set_temperature = maximum_set_temperature;
+                return result;
+            }
+        }
+        self.maintain_set_points(temperature)
+    }
+
+    // Heat starts below the deadband around the minimum set temperature and, once running, keeps
+    // going until the temperature rises past the deadband or the hysteresis band, whichever is
+    // wider.
+    fn heat_demand(&self, temperature: f64, heating: bool) -> bool {
+        let half_deadband = self.state.heat_deadband / 2.0;
+        temperature < self.state.minimum_set_temperature - half_deadband
+            || (heating
+                && (temperature <= self.state.minimum_set_temperature + half_deadband
+                    || temperature < self.state.minimum_set_temperature + self.state.hysteresis))
+    }
+
+    // Mirrors heat_demand around the maximum set temperature.
+    fn cool_demand(&self, temperature: f64, cooling: bool) -> bool {
+        let half_deadband = self.state.cool_deadband / 2.0;
+        temperature > self.state.maximum_set_temperature + half_deadband
+            || (cooling
+                && (temperature >= self.state.maximum_set_temperature - half_deadband
+                    || temperature > self.state.maximum_set_temperature - self.state.hysteresis))
+    }
+
+    // Drives the humidifier and any dehumidifier towards the target humidity.
+    fn update_humidity(&mut self) -> Result<(), ConstraintError> {
+        let active = self.state.humidity_control_enabled
+            && !self.state.humidity_history.is_empty()
+            && self.state.operating_mode != OperatingMode::Disabled
+            && self.state.operating_mode != OperatingMode::DisabledUnsafe;
+        let half_deadband = self.state.humidity_deadband / 2.0;
+        // the Disabled mode only acts on the safe humidities
+        let disabled = self.state.operating_mode == OperatingMode::Disabled;
+        let humidifying = self.interface.calling_for_humidifier()?;
+        if (active
+            && (self.state.current_humidity < self.state.target_humidity - half_deadband
+                || (humidifying
+                    && self.state.current_humidity < self.state.target_humidity + half_deadband)))
+            || (disabled && self.state.current_humidity < self.state.minimum_safe_humidity)
+        {
+            self.start_humidifier()?;
+        } else {
+            self.stop_humidifier()?;
+        }
+        if self.interface.has_dehumidifier() {
+            let dehumidifying = self.interface.calling_for_dehumidifier()?;
+            if (active && self.dehumidify_demand(dehumidifying))
+                || (disabled && self.state.current_humidity > self.state.maximum_safe_humidity)
+            {
+                self.start_dehumidifier()?;
+            } else {
+                self.stop_dehumidifier()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn start_humidifier(&mut self) -> Result<(), ConstraintError> {
+        if !self.interface.calling_for_humidifier()? {
+            let now = self.interface.get_seconds()?;
+            let elapsed = now - self.state.last_call_for_humidifier_end.unwrap_or(0);
+            if elapsed < self.state.minimum_humidifier_off_secs as u64 {
+                let remaining = self.state.minimum_humidifier_off_secs as u64 - elapsed;
+                return Err(ConstraintError(Error::HumidifierMinOffTimeConstraint, Some(remaining)));
+            }
+            self.interface.call_for_humidifier()?;
+            self.state.last_call_for_humidifier_start = Some(now);
+        }
+        Ok(())
+    }
+
+    fn stop_humidifier(&mut self) -> Result<(), ConstraintError> {
+        if self.interface.calling_for_humidifier()? {
+            let now = self.interface.get_seconds()?;
+            let elapsed = now - self.state.last_call_for_humidifier_start.unwrap_or(0);
+            if elapsed < self.state.minimum_humidifier_run_secs as u64 {
+                let remaining = self.state.minimum_humidifier_run_secs as u64 - elapsed;
+                return Err(ConstraintError(Error::HumidifierMinRunTimeConstraint, Some(remaining)));
+            }
+            self.interface.stop_call_for_humidifier()?;
+            self.state.last_call_for_humidifier_end = Some(now);
+        }
+        Ok(())
+    }
+
+    fn start_dehumidifier(&mut self) -> Result<(), ConstraintError> {
+        if !self.interface.calling_for_dehumidifier()? {
+            let now = self.interface.get_seconds()?;
+            let elapsed = now - self.state.last_call_for_dehumidifier_end.unwrap_or(0);
+            if elapsed < self.state.minimum_dehumidifier_off_secs as u64 {
+                let remaining = self.state.minimum_dehumidifier_off_secs as u64 - elapsed;
+                return Err(ConstraintError(
+                    Error::DehumidifierMinOffTimeConstraint,
+                    Some(remaining),
+                ));
+            }
+            self.interface.call_for_dehumidifier()?;
+            self.state.last_call_for_dehumidifier_start = Some(now);
+        }
+        Ok(())
+    }
+
+    fn stop_dehumidifier(&mut self) -> Result<(), ConstraintError> {
+        if self.interface.calling_for_dehumidifier()? {
+            let now = self.interface.get_seconds()?;
+            let elapsed = now - self.state.last_call_for_dehumidifier_start.unwrap_or(0);
+            if elapsed < self.state.minimum_dehumidifier_run_secs as u64 {
+                let remaining = self.state.minimum_dehumidifier_run_secs as u64 - elapsed;
+                return Err(ConstraintError(
+                    Error::DehumidifierMinRunTimeConstraint,
+                    Some(remaining),
+                ));
+            }
+            self.interface.stop_call_for_dehumidifier()?;
+            self.state.last_call_for_dehumidifier_end = Some(now);
+        }
+        Ok(())
+    }
+
+    // An early start from trend anticipation alone that is held back by a timing constraint is not
+    // a constraint violation; the start is retried on the next reading.
+    fn start_early(
+        &mut self,
+        early: bool,
+        result: Result<(), ConstraintError>,
+    ) -> Result<(), ConstraintError> {
+        match result {
+            Err(error) if early && is_timing_constraint(error.0) => Ok(()),
+            result => result,
+        }
+    }
+
+    // Dehumidification starts above the humidity deadband and, once running, keeps going until the
+    // humidity falls below it.
+    fn dehumidify_demand(&self, dehumidifying: bool) -> bool {
+        let half_deadband = self.state.humidity_deadband / 2.0;
+        self.state.humidity_control_enabled
+            && !self.state.humidity_history.is_empty()
+            && (self.state.current_humidity > self.state.target_humidity + half_deadband
+                || (dehumidifying
+                    && self.state.current_humidity > self.state.target_humidity - half_deadband))
+    }
+
+    fn maintain_set_points(&mut self, temperature: f64) -> Result<(), ConstraintError> {
+        // anticipate a forecast breach of the set points, but only while still within them
+        let now = self.interface.get_seconds()?;
+        let (anticipated_low, anticipated_high) = match self.state.forecast.next(now) {
+            Some((forecast, _)) => (temperature.min(forecast), temperature.max(forecast)),
+            None => (temperature, temperature),
+        };
+        // the disabled modes only act on the safe temperatures
+        let maintaining = self.state.operating_mode != OperatingMode::Disabled
+            && self.state.operating_mode != OperatingMode::DisabledUnsafe;
+        let heating = (self.state.hysteresis > 0.0 || self.state.heat_deadband > 0.0)
+            && self.interface.calling_for_heat()?;
+        let cooling = (self.state.hysteresis > 0.0 || self.state.cool_deadband > 0.0)
+            && self.interface.calling_for_cool()?;
+        let dehumidifying = self.state.humidity_control_enabled
+            && !self.interface.has_dehumidifier()
+            && self.interface.calling_for_cool()?;
+        // a projected breach of the set points starts early, once anticipation has been enabled
+        let trend_projected = if self.state.trend_anticipation_enabled {
+            let lookahead_secs =
+                self.state.thermal_mass.time_constant_secs() * self.state.trend_anticipation_gain;
+            temperature + self.state.temperature_trend * lookahead_secs
+        } else {
+            temperature
+        };
+        let heat_demand =
+            self.heat_demand(anticipated_low, false) || self.heat_demand(temperature, heating);
+        let cool_demand = self.cool_demand(anticipated_high, false)
+            || self.cool_demand(temperature, cooling)
+            || (self.dehumidify_demand(dehumidifying) && !self.interface.has_dehumidifier());
+        if (temperature < self.state.minimum_safe_temperature
+            && self.state.operating_mode != OperatingMode::DisabledUnsafe)
+            || ((heat_demand || self.heat_demand(trend_projected, false))
+                && temperature <= self.state.maximum_set_temperature
+                && maintaining
+                && self.state.operating_mode != OperatingMode::CoolToSetPoint)
+        {
+            let early = !heat_demand && temperature >= self.state.minimum_safe_temperature;
+            let result = self.heat();
+            self.start_early(early, result)?
+        } else if (temperature > self.state.maximum_safe_temperature
+            && self.state.operating_mode != OperatingMode::DisabledUnsafe)
+            || ((cool_demand || self.cool_demand(trend_projected, false))
+                && temperature >= self.state.minimum_set_temperature
+                && maintaining
+                && self.state.operating_mode != OperatingMode::HeatToSetPoint)
         {
-            self.heat()?
-        } else if (temperature > self.maximum_safe_temperature
-            && self.operating_mode != OperatingMode::DisabledUnsafe)
-            || (temperature > self.maximum_set_temperature
-                && self.operating_mode != OperatingMode::HeatToSetPoint)
+            let early = !cool_demand && temperature <= self.state.maximum_safe_temperature;
+            let result = self.cool();
+            self.start_early(early, result)?
+        } else if self.state.operating_mode == OperatingMode::Disabled
+            && self.state.current_humidity > self.state.maximum_safe_humidity
         {
-            self.cool()?
+            // circulate air until humidity falls back within the safe range
+            self.apply_control_decision(ControlDecision {
+                call_fan: true,
+                ..ControlDecision::default()
+            })?
         } else {
             self.off()?
         }
@@ -0,0 +1,365 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use Error;
+use OperatingMode;
+use SetPointBands;
+use ThermostatCore;
+use ThermostatInterface;
+
+/// Number of slots a `Schedule` can hold, enough for four changes a day
+pub const MAXIMUM_SCHEDULE_SLOTS: usize = 28;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+const SECS_PER_WEEK: u64 = 7 * SECS_PER_DAY;
+
+/// Operating mode and set points taking effect at a time of the week
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScheduleSlot {
+    /// Day of the week, from 0 to 6
+    pub day_of_week: u8,
+    /// Seconds after midnight
+    pub time_of_day_secs: u32,
+    /// Operating mode to change to
+    pub operating_mode: OperatingMode,
+    /// Minimum set temperature in degrees Celsius to change to
+    pub min_set_temp: f64,
+    /// Maximum set temperature in degrees Celsius to change to
+    pub max_set_temp: f64,
+}
+
+impl ScheduleSlot {
+    // Seconds after the start of day 0 at which the slot takes effect
+    fn week_secs(&self) -> u64 {
+        self.day_of_week as u64 * SECS_PER_DAY + self.time_of_day_secs as u64
+    }
+}
+
+/// Weekly program of operating mode and set point changes holding up to `MAXIMUM_SCHEDULE_SLOTS`
+/// slots
+///
+/// The interface clock is taken as seconds since midnight at the start of day 0, repeating every
+/// week. Each slot stays in effect until the next one; before the first slot of the week, the
+/// last slot of the previous week is in effect.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Schedule {
+    slots: [ScheduleSlot; MAXIMUM_SCHEDULE_SLOTS],
+    len: usize,
+}
+
+impl Schedule {
+    /// Create an empty schedule
+    pub const fn new() -> Schedule {
+        Schedule {
+            slots: [ScheduleSlot {
+                day_of_week: 0,
+                time_of_day_secs: 0,
+                operating_mode: OperatingMode::Disabled,
+                min_set_temp: 0.0,
+                max_set_temp: 0.0,
+            }; MAXIMUM_SCHEDULE_SLOTS],
+            len: 0,
+        }
+    }
+
+    /// Add a slot, keeping the slots in order through the week.
+    ///
+    /// An Err Result is returned if the schedule is full, the day or time is out of range, a set
+    /// temperature is not finite, the minimum set temperature is above the maximum, or another
+    /// slot already starts at the same time.
+    pub fn add_slot(&mut self, slot: ScheduleSlot) -> Result<(), Error> {
+        if self.len == MAXIMUM_SCHEDULE_SLOTS
+            || slot.day_of_week > 6
+            || slot.time_of_day_secs as u64 >= SECS_PER_DAY
+            || !slot.min_set_temp.is_finite()
+            || !slot.max_set_temp.is_finite()
+            || slot.min_set_temp > slot.max_set_temp
+        {
+            return Err(Error::InvalidConfiguration);
+        }
+        let index = self.slots().partition_point(|s| s.week_secs() < slot.week_secs());
+        if self.slots().get(index).map(ScheduleSlot::week_secs) == Some(slot.week_secs()) {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.slots.copy_within(index..self.len, index + 1);
+        self.slots[index] = slot;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Get the slots in order through the week
+    pub fn slots(&self) -> &[ScheduleSlot] {
+        &self.slots[..self.len]
+    }
+
+    // Index of the slot in effect at `now`, if there are any slots
+    fn active_slot(&self, now: u64) -> Option<usize> {
+        let week_secs = now % SECS_PER_WEEK;
+        match self.slots().partition_point(|slot| slot.week_secs() <= week_secs) {
+            0 => self.len.checked_sub(1),
+            index => Some(index - 1),
+        }
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Schedule {
+        Schedule::new()
+    }
+}
+
+// Temporary settings taking precedence over the schedule until `expires_at`, and the settings to
+// go back to afterwards when there is no schedule.
+#[derive(Copy, Clone)]
+pub(crate) struct ScheduleOverride {
+    expires_at: u64,
+    resume_operating_mode: OperatingMode,
+    resume_min_set_temp: f64,
+    resume_max_set_temp: f64,
+}
+
+// Operating mode and set points to restore when vacation mode ends.
+#[derive(Copy, Clone)]
+pub(crate) struct VacationResume {
+    operating_mode: OperatingMode,
+    min_set_temp: f64,
+    max_set_temp: f64,
+}
+
+impl<'a, I: ThermostatInterface, const N: usize> ThermostatCore<'a, I, N> {
+    /// Follow a weekly schedule, replacing any previous one.
+    ///
+    /// `tick` applies each slot's operating mode and set points once when the slot takes effect,
+    /// so changes made in between last until the next slot.
+    pub fn set_schedule(&mut self, schedule: Schedule) {
+        self.state.schedule = Some(schedule);
+        self.state.applied_schedule_slot = None;
+    }
+
+    /// Stop following the weekly schedule. The current settings are kept.
+    pub fn clear_schedule(&mut self) {
+        self.state.schedule = None;
+        self.state.applied_schedule_slot = None;
+    }
+
+    /// Get the weekly schedule being followed, if any
+    pub fn get_schedule(&self) -> Option<&Schedule> {
+        self.state.schedule.as_ref()
+    }
+
+    /// Get the seconds until the next schedule slot takes effect.
+    ///
+    /// Returns None without a schedule with slots, or if the current time cannot be read from the
+    /// interface.
+    pub fn get_next_schedule_change_secs(&self) -> Option<u64> {
+        let schedule = self.state.schedule.as_ref()?;
+        let week_secs = self.interface.get_seconds().ok()? % SECS_PER_WEEK;
+        let slots = schedule.slots();
+        let next = slots
+            .iter()
+            .map(ScheduleSlot::week_secs)
+            .find(|&slot_secs| slot_secs > week_secs)
+            .or_else(|| slots.first().map(|slot| slot.week_secs() + SECS_PER_WEEK))?;
+        Some(next - week_secs)
+    }
+
+    /// Temporarily change the operating mode and set points, taking precedence over the schedule.
+    ///
+    /// Once `duration_secs` have passed, the next `tick` resumes the schedule slot then in effect,
+    /// or without a schedule restores the operating mode and set points from before the override.
+    /// Changes made while the override is active, such as with `set_operating_mode`, last until
+    /// then. Setting an override while one is active replaces it.
+    ///
+    /// An Err Result is returned, and nothing changes, if a temperature is not finite, the minimum
+    /// temperature is above the maximum, the duration is zero, a temperature is outside the safe
+    /// temperatures, the hysteresis or deadbands would not fit between the temperatures, the
+    /// operating mode cannot be changed to, or the current time cannot be read from the interface.
+    pub fn set_override(
+        &mut self,
+        operating_mode: OperatingMode,
+        min_temp: f64,
+        max_temp: f64,
+        duration_secs: u64,
+    ) -> Result<(), Error> {
+        if duration_secs == 0 {
+            return Err(Error::InvalidConfiguration);
+        }
+        let now = self.interface.get_seconds()?;
+        let resume = self.state.schedule_override.unwrap_or(ScheduleOverride {
+            expires_at: 0,
+            resume_operating_mode: self.state.operating_mode,
+            resume_min_set_temp: self.state.minimum_set_temperature,
+            resume_max_set_temp: self.state.maximum_set_temperature,
+        });
+        self.apply_settings(operating_mode, min_temp, max_temp)?;
+        self.state.schedule_override = Some(ScheduleOverride {
+            expires_at: now.saturating_add(duration_secs),
+            ..resume
+        });
+        Ok(())
+    }
+
+    /// End any override now. The schedule, or the settings from before the override, resume on
+    /// the next `tick`.
+    pub fn clear_override(&mut self) {
+        if let Some(schedule_override) = self.state.schedule_override.as_mut() {
+            schedule_override.expires_at = 0;
+        }
+    }
+
+    /// Get the seconds until the override expires.
+    ///
+    /// Returns None without an override, once it has expired, or if the current time cannot be
+    /// read from the interface.
+    pub fn get_override_remaining_secs(&self) -> Option<u64> {
+        let now = self.interface.get_seconds().ok()?;
+        let expires_at = self.state.schedule_override?.expires_at;
+        Some(expires_at.saturating_sub(now)).filter(|&secs| secs > 0)
+    }
+
+    /// Change to the `Vacation` mode, maintaining the provided set points until
+    /// `exit_vacation_mode`.
+    ///
+    /// The operating mode and set points in effect are saved and restored exactly on exit, and
+    /// are the ones `get_config` reports while on vacation, so a persisted configuration never
+    /// holds the `Vacation` mode. While on vacation, `set_minimum_set_temperature` and
+    /// `set_maximum_set_temperature` change the vacation set points, and the schedule and any
+    /// override are paused, resuming on the first `tick` after exit. Entering vacation mode again
+    /// only replaces the vacation set points.
+    ///
+    /// An Err Result is returned, and nothing changes, if a temperature is not finite, the minimum
+    /// temperature is above the maximum, a temperature is outside the safe temperatures, the
+    /// hysteresis or deadbands would not fit between the temperatures, or the current operating
+    /// mode cannot be left, as for `set_operating_mode`.
+    pub fn enter_vacation_mode(&mut self, min: f64, max: f64) -> Result<(), Error> {
+        let on_vacation = self.state.vacation_resume.is_some();
+        if !on_vacation {
+            self.state.vacation_resume = Some(VacationResume {
+                operating_mode: self.state.operating_mode,
+                min_set_temp: self.state.minimum_set_temperature,
+                max_set_temp: self.state.maximum_set_temperature,
+            });
+        }
+        let result = self.apply_settings(OperatingMode::Vacation, min, max);
+        if result.is_err() && !on_vacation {
+            self.state.vacation_resume = None;
+        }
+        result
+    }
+
+    /// Leave the `Vacation` mode, restoring the operating mode and set points saved by
+    /// `enter_vacation_mode`. Does nothing when not on vacation.
+    ///
+    /// An Err Result is returned, and the thermostat stays on vacation, if the saved settings can
+    /// no longer be applied, e.g. because the hysteresis has since been widened past them.
+    pub fn exit_vacation_mode(&mut self) -> Result<(), Error> {
+        let resume = match self.state.vacation_resume.take() {
+            Some(resume) => resume,
+            None => return Ok(()),
+        };
+        let result =
+            self.apply_settings(resume.operating_mode, resume.min_set_temp, resume.max_set_temp);
+        if result.is_err() {
+            self.state.vacation_resume = Some(resume);
+        }
+        result
+    }
+
+    // Moves the set points saved for the end of an override or vacation into a new safe range.
+    pub(crate) fn clamp_resume_set_points(&mut self, clamp: impl Fn(f64) -> f64) {
+        if let Some(resume) = self.state.schedule_override.as_mut() {
+            resume.resume_min_set_temp = clamp(resume.resume_min_set_temp);
+            resume.resume_max_set_temp = clamp(resume.resume_max_set_temp);
+        }
+        if let Some(resume) = self.state.vacation_resume.as_mut() {
+            resume.min_set_temp = clamp(resume.min_set_temp);
+            resume.max_set_temp = clamp(resume.max_set_temp);
+        }
+    }
+
+    // The operating mode and set points to persist, which are those saved on entering vacation
+    // mode rather than the vacation ones.
+    pub(crate) fn regular_settings(&self) -> (OperatingMode, f64, f64) {
+        match self.state.vacation_resume {
+            Some(resume) => (resume.operating_mode, resume.min_set_temp, resume.max_set_temp),
+            None => (
+                self.state.operating_mode,
+                self.state.minimum_set_temperature,
+                self.state.maximum_set_temperature,
+            ),
+        }
+    }
+
+    // Applies the slot in effect now if it has not been applied yet, unless overridden or on
+    // vacation.
+    pub(crate) fn apply_schedule(&mut self) -> Result<(), Error> {
+        if (self.state.schedule.is_none() && self.state.schedule_override.is_none())
+            || self.state.vacation_resume.is_some()
+        {
+            return Ok(());
+        }
+        let now = self.interface.get_seconds()?;
+        if let Some(schedule_override) = self.state.schedule_override {
+            if now < schedule_override.expires_at {
+                return Ok(());
+            }
+            self.state.schedule_override = None;
+            self.state.applied_schedule_slot = None;
+            if self.state.schedule.is_none() {
+                self.apply_settings(
+                    schedule_override.resume_operating_mode,
+                    schedule_override.resume_min_set_temp,
+                    schedule_override.resume_max_set_temp,
+                )?;
+            }
+        }
+        let schedule = match self.state.schedule {
+            Some(schedule) => schedule,
+            None => return Ok(()),
+        };
+        let index = match schedule.active_slot(now) {
+            Some(index) if self.state.applied_schedule_slot != Some(index) => index,
+            _ => return Ok(()),
+        };
+        let slot = schedule.slots[index];
+        self.apply_settings(slot.operating_mode, slot.min_set_temp, slot.max_set_temp)?;
+        self.state.applied_schedule_slot = Some(index);
+        Ok(())
+    }
+
+    // Changes the operating mode and both set points together. The combination is validated as a
+    // whole first, so nothing changes if any part of it is rejected.
+    fn apply_settings(
+        &mut self,
+        operating_mode: OperatingMode,
+        min_temp: f64,
+        max_temp: f64,
+    ) -> Result<(), Error> {
+        if !min_temp.is_finite() || !max_temp.is_finite() {
+            return Err(Error::InvalidTemperature);
+        }
+        let min_temp = self.round_temperature(min_temp);
+        let max_temp = self.round_temperature(max_temp);
+        if min_temp > max_temp {
+            return Err(Error::InvalidConfiguration);
+        }
+        if min_temp < self.state.minimum_safe_temperature
+            || max_temp > self.state.maximum_safe_temperature
+        {
+            return Err(Error::SetPointOutOfSafeRange);
+        }
+        self.check_operating_mode(operating_mode)?;
+        SetPointBands {
+            operating_mode,
+            minimum: min_temp,
+            maximum: max_temp,
+            ..self.set_point_bands()
+        }
+        .validate()?;
+        self.commit_operating_mode(operating_mode)?;
+        self.commit_minimum_set_temperature(min_temp);
+        self.commit_maximum_set_temperature(max_temp);
+        Ok(())
+    }
+}
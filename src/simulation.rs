@@ -0,0 +1,147 @@
+use core::cell::{Cell, RefCell};
+use std::vec::Vec;
+
+use Error;
+use ThermostatCore;
+use ThermostatInterface;
+
+/// A single reading fed to `Thermostat::simulate`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SimulationStep {
+    /// Seconds since system start reported by the simulated clock for this reading
+    pub timestamp_secs: u64,
+    /// Temperature reading in degrees Celsius
+    pub temperature: f64,
+    /// Relative humidity reading in percent
+    pub humidity: f64,
+}
+
+/// Actuator state changes observed during a simulation
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ActuatorAction {
+    /// Call for heat started
+    HeatStart,
+    /// Call for heat stopped
+    HeatStop,
+    /// Call for cool started
+    CoolStart,
+    /// Call for cool stopped
+    CoolStop,
+    /// Call for fan started
+    FanStart,
+    /// Call for fan stopped
+    FanStop,
+}
+
+/// An actuator state change and the step that caused it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SimulationResult {
+    /// Index into the scenario of the step being processed
+    pub step: usize,
+    /// What the thermostat did
+    pub action: ActuatorAction,
+}
+
+// Interface with all outputs initially off and a clock set by the simulation, recording every
+// output change.
+struct SimulationInterface {
+    heat: Cell<bool>,
+    cool: Cell<bool>,
+    fan: Cell<bool>,
+    seconds: Cell<u64>,
+    step: Cell<usize>,
+    results: RefCell<Vec<SimulationResult>>,
+}
+
+impl SimulationInterface {
+    fn set(&self, output: &Cell<bool>, on: bool, action: ActuatorAction) -> Result<(), Error> {
+        if output.replace(on) != on {
+            self.results.borrow_mut().push(SimulationResult {
+                step: self.step.get(),
+                action,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl ThermostatInterface for SimulationInterface {
+    fn calling_for_heat(&self) -> Result<bool, Error> {
+        Ok(self.heat.get())
+    }
+    fn call_for_heat(&self) -> Result<(), Error> {
+        self.set(&self.heat, true, ActuatorAction::HeatStart)
+    }
+    fn stop_call_for_heat(&self) -> Result<(), Error> {
+        self.set(&self.heat, false, ActuatorAction::HeatStop)
+    }
+    fn calling_for_cool(&self) -> Result<bool, Error> {
+        Ok(self.cool.get())
+    }
+    fn call_for_cool(&self) -> Result<(), Error> {
+        self.set(&self.cool, true, ActuatorAction::CoolStart)
+    }
+    fn stop_call_for_cool(&self) -> Result<(), Error> {
+        self.set(&self.cool, false, ActuatorAction::CoolStop)
+    }
+    fn calling_for_fan(&self) -> Result<bool, Error> {
+        Ok(self.fan.get())
+    }
+    fn call_for_fan(&self) -> Result<(), Error> {
+        self.set(&self.fan, true, ActuatorAction::FanStart)
+    }
+    fn stop_call_for_fan(&self) -> Result<(), Error> {
+        self.set(&self.fan, false, ActuatorAction::FanStop)
+    }
+    fn get_seconds(&self) -> Result<u64, Error> {
+        Ok(self.seconds.get())
+    }
+}
+
+impl<'a, I: ThermostatInterface, const N: usize> ThermostatCore<'a, I, N> {
+    /// Run a scenario of readings through a copy of this thermostat and record its actuator calls.
+    ///
+    /// The copy keeps this thermostat's configuration but starts with all outputs off, no cycle
+    /// history, no event handler, and a clock that reports each step's timestamp. This thermostat,
+    /// its interface and its event handler are left untouched. Steps whose update fails, e.g.
+    /// because a timing constraint blocked an output change, are recorded as far as they got and
+    /// the simulation continues.
+    pub fn simulate(&self, scenario: &[SimulationStep]) -> Vec<SimulationResult> {
+        let interface = SimulationInterface {
+            heat: Cell::new(false),
+            cool: Cell::new(false),
+            fan: Cell::new(false),
+            seconds: Cell::new(0),
+            step: Cell::new(0),
+            results: RefCell::new(Vec::new()),
+        };
+        let mut thermostat = self.with_interface(&interface);
+        thermostat.reset_heat_timing_state();
+        thermostat.reset_cool_timing_state();
+        thermostat.reset_fan_timing_state();
+        thermostat.state.temperature_history.clear();
+        thermostat.state.temperature_trend = 0.0;
+        for (index, step) in scenario.iter().enumerate() {
+            interface.step.set(index);
+            interface.seconds.set(step.timestamp_secs);
+            let _ = thermostat.set_current_humidity(step.humidity);
+            let _ = thermostat.set_current_temperature(step.temperature);
+        }
+        interface.results.into_inner()
+    }
+}
+
+impl<'a, I, const N: usize> ThermostatCore<'a, I, N> {
+    // Copy this thermostat's state onto another interface. The post-update hook is typed by the
+    // interface, so it is not carried over, and the event handler is dropped so the copy's actions
+    // are not reported as this thermostat's.
+    fn with_interface<J>(&self, interface: J) -> ThermostatCore<'a, J, N> {
+        let mut state = self.state;
+        state.event_handler = None;
+        ThermostatCore {
+            state,
+            interface,
+            post_update_hook: None,
+        }
+    }
+}
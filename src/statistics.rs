@@ -0,0 +1,340 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use Error;
+use ThermostatCore;
+use ThermostatInterface;
+
+/// Aggregate operational metrics accumulated since the thermostat was created
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OperationalStatistics {
+    /// Number of times heat was started
+    pub total_heat_cycles: u32,
+    /// Number of times cool was started
+    pub total_cool_cycles: u32,
+    /// Number of times the fan was started
+    pub total_fan_cycles: u32,
+    /// Seconds heat has been called for, including any cycle in progress
+    pub total_heat_runtime_secs: u64,
+    /// Seconds cool has been called for, including any cycle in progress
+    pub total_cool_runtime_secs: u64,
+    /// Seconds the fan has been called for, including any cycle in progress
+    pub total_fan_runtime_secs: u64,
+    /// Heat runtime multiplied by the rated heat power
+    pub estimated_heat_energy_kwh: f64,
+    /// Cool runtime multiplied by the rated cool power
+    pub estimated_cool_energy_kwh: f64,
+    /// Seconds since the thermostat was created
+    pub uptime_secs: u64,
+}
+
+/// Equipment runtime accumulated over completed cycles, for maintenance scheduling
+///
+/// Unlike `OperationalStatistics`, a cycle is only counted once it ends, and the totals can be
+/// zeroed with `reset_runtime_stats`, e.g. after servicing the equipment.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RuntimeStats {
+    /// Seconds heat was called for in completed cycles
+    pub heat_total_secs: u64,
+    /// Seconds cool was called for in completed cycles
+    pub cool_total_secs: u64,
+    /// Seconds the fan was called for in completed cycles
+    pub fan_total_secs: u64,
+    /// Number of completed heat cycles
+    pub heat_cycle_count: u32,
+    /// Number of completed cool cycles
+    pub cool_cycle_count: u32,
+    /// Number of completed fan cycles
+    pub fan_cycle_count: u32,
+}
+
+impl OperationalStatistics {
+    // Same as `default()`, for const contexts.
+    pub(crate) const fn new() -> OperationalStatistics {
+        OperationalStatistics {
+            total_heat_cycles: 0,
+            total_cool_cycles: 0,
+            total_fan_cycles: 0,
+            total_heat_runtime_secs: 0,
+            total_cool_runtime_secs: 0,
+            total_fan_runtime_secs: 0,
+            estimated_heat_energy_kwh: 0.0,
+            estimated_cool_energy_kwh: 0.0,
+            uptime_secs: 0,
+        }
+    }
+}
+
+impl RuntimeStats {
+    // Same as `default()`, for const contexts.
+    pub(crate) const fn new() -> RuntimeStats {
+        RuntimeStats {
+            heat_total_secs: 0,
+            cool_total_secs: 0,
+            fan_total_secs: 0,
+            heat_cycle_count: 0,
+            cool_cycle_count: 0,
+            fan_cycle_count: 0,
+        }
+    }
+}
+
+// Fan runtime after which the filter is due for replacement by default: 90 days.
+pub(crate) const DEFAULT_FILTER_ALERT_SECS: u64 = 90 * 24 * 60 * 60;
+
+// Number of completed cycles remembered for windowed metrics. Minimum run and off times keep the
+// number of cycles in an hour small; older cycles are discarded when full.
+const CYCLE_HISTORY_CAPACITY: usize = 32;
+
+// Fixed-size buffer of the most recent completed cycles as (start, end) seconds, oldest first.
+#[derive(Copy, Clone)]
+pub(crate) struct CycleHistory {
+    cycles: [(u64, u64); CYCLE_HISTORY_CAPACITY],
+    len: usize,
+}
+
+impl CycleHistory {
+    pub(crate) const fn new() -> CycleHistory {
+        CycleHistory {
+            cycles: [(0, 0); CYCLE_HISTORY_CAPACITY],
+            len: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, start: u64, end: u64) {
+        if self.len == CYCLE_HISTORY_CAPACITY {
+            self.cycles.copy_within(1.., 0);
+            self.len -= 1;
+        }
+        self.cycles[self.len] = (start, end);
+        self.len += 1;
+    }
+
+    // Seconds of overlap between the remembered cycles, plus any cycle still running since
+    // `running_since`, and the window `[from, to]`
+    fn active_secs(&self, running_since: Option<u64>, from: u64, to: u64) -> u64 {
+        let overlap = |start: u64, end: u64| end.min(to).saturating_sub(start.max(from));
+        let completed: u64 = self.cycles[..self.len]
+            .iter()
+            .map(|&(start, end)| overlap(start, end))
+            .sum();
+        completed + running_since.map_or(0, |start| overlap(start, to))
+    }
+}
+
+impl<'a, I: ThermostatInterface, const N: usize> ThermostatCore<'a, I, N> {
+    /// Get a snapshot of the operational metrics.
+    ///
+    /// Runtime of a cycle still in progress is included up to now. If the interface clock is
+    /// unavailable, only completed cycles are counted and uptime is reported as zero. Energy
+    /// estimates are zero until rated powers are set.
+    pub fn get_statistics(&self) -> OperationalStatistics {
+        let mut statistics = self.state.statistics;
+        if let Ok(now) = self.interface.get_seconds() {
+            let running = |start: Option<u64>, end: Option<u64>| match (start, end) {
+                (Some(start), Some(end)) if end >= start => 0,
+                (Some(start), _) => now.saturating_sub(start),
+                (None, _) => 0,
+            };
+            statistics.total_heat_runtime_secs +=
+                running(self.state.last_call_for_heat_start, self.state.last_call_for_heat_end);
+            statistics.total_cool_runtime_secs +=
+                running(self.state.last_call_for_cool_start, self.state.last_call_for_cool_end);
+            statistics.total_fan_runtime_secs +=
+                running(self.state.last_call_for_fan_start, self.state.last_call_for_fan_end);
+            statistics.uptime_secs = now.saturating_sub(self.state.initialization_time);
+        }
+        statistics.estimated_heat_energy_kwh =
+            statistics.total_heat_runtime_secs as f64 / 3600.0 * self.state.rated_heat_power_kw;
+        statistics.estimated_cool_energy_kwh =
+            statistics.total_cool_runtime_secs as f64 / 3600.0 * self.state.rated_cool_power_kw;
+        statistics
+    }
+
+    /// Get the equipment runtime accumulated since creation or the last `reset_runtime_stats`.
+    pub fn get_runtime_stats(&self) -> RuntimeStats {
+        self.state.runtime_stats
+    }
+
+    /// Zero the equipment runtime totals and cycle counts.
+    pub fn reset_runtime_stats(&mut self) {
+        self.state.runtime_stats = RuntimeStats::default();
+    }
+
+    /// Get the seconds the fan has run since creation or the last `reset_filter_runtime`.
+    ///
+    /// A fan cycle still in progress is included up to now.
+    pub fn get_filter_runtime_secs(&self) -> u64 {
+        let running = match (
+            self.interface.get_seconds(),
+            self.interface.calling_for_fan(),
+            self.state.last_call_for_fan_start,
+        ) {
+            (Ok(now), Ok(true), Some(start)) => {
+                now.saturating_sub(start.max(self.state.filter_reset_at))
+            }
+            _ => 0,
+        };
+        self.state.filter_runtime_secs + running
+    }
+
+    /// Zero the filter runtime, e.g. after replacing the filter, and clear the filter alert.
+    pub fn reset_filter_runtime(&mut self) {
+        self.state.filter_runtime_secs = 0;
+        self.state.filter_reset_at = self.interface.get_seconds().unwrap_or(0);
+        self.state.filter_alert_notified = false;
+    }
+
+    /// Change the fan runtime after which the filter alert becomes active. Defaults to 90 days.
+    pub fn set_filter_alert_secs(&mut self, secs: u64) {
+        self.state.filter_alert_secs = secs;
+    }
+    /// Get the fan runtime after which the filter alert becomes active
+    pub fn get_filter_alert_secs(&self) -> u64 {
+        self.state.filter_alert_secs
+    }
+
+    /// Check whether the filter runtime has reached the filter alert threshold.
+    pub fn is_filter_alert_active(&self) -> bool {
+        self.get_filter_runtime_secs() >= self.state.filter_alert_secs
+    }
+
+    // Notifies the event handler the first time the filter alert is seen active after a reset.
+    pub(crate) fn update_filter_alert(&mut self) {
+        if self.state.filter_alert_notified || !self.is_filter_alert_active() {
+            return;
+        }
+        self.state.filter_alert_notified = true;
+        if let Some(handler) = self.state.event_handler {
+            handler.on_filter_alert();
+        }
+    }
+
+    /// Get the fraction of the last hour that heat was called for, from 0.0 to 1.0.
+    ///
+    /// A cycle still in progress counts up to now. Before the thermostat has been running for an
+    /// hour, the time before it was created counts as off.
+    ///
+    /// An Err Result is returned if the current time cannot be read from the interface.
+    pub fn get_heat_duty_cycle_last_hour(&self) -> Result<f64, Error> {
+        let now = self
+            .interface
+            .get_seconds()
+            .map_err(|_| Error::MeasurementFailed)?;
+        let running = running_since(
+            self.state.last_call_for_heat_start,
+            self.state.last_call_for_heat_end,
+        );
+        Ok(duty_cycle_last_hour(&self.state.heat_cycle_history, running, now))
+    }
+
+    /// Get the fraction of the last hour that cool was called for, from 0.0 to 1.0.
+    ///
+    /// A cycle still in progress counts up to now. Before the thermostat has been running for an
+    /// hour, the time before it was created counts as off.
+    ///
+    /// An Err Result is returned if the current time cannot be read from the interface.
+    pub fn get_cool_duty_cycle_last_hour(&self) -> Result<f64, Error> {
+        let now = self
+            .interface
+            .get_seconds()
+            .map_err(|_| Error::MeasurementFailed)?;
+        let running = running_since(
+            self.state.last_call_for_cool_start,
+            self.state.last_call_for_cool_end,
+        );
+        Ok(duty_cycle_last_hour(&self.state.cool_cycle_history, running, now))
+    }
+
+    /// Change the rated power draw of the heating equipment used for energy estimates.
+    ///
+    /// An Err Result is returned if the power is negative or not finite.
+    pub fn set_rated_heat_power_kw(&mut self, power_kw: f64) -> Result<(), Error> {
+        if !power_kw.is_finite() || power_kw < 0.0 {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.rated_heat_power_kw = power_kw;
+        Ok(())
+    }
+    /// Get the rated power draw of the heating equipment
+    pub fn get_rated_heat_power_kw(&self) -> f64 {
+        self.state.rated_heat_power_kw
+    }
+
+    /// Estimate the coefficient of performance of the cooling equipment.
+    ///
+    /// Heat removed is estimated from the temperature drop over completed cool cycles and the
+    /// thermal capacity of the space, and divided by the electrical energy those cycles used at the
+    /// rated cool power. When an outdoor temperature warmer than the current temperature is known,
+    /// the estimate is capped at the Carnot limit `T_cool / (T_hot - T_cool)` in kelvin.
+    ///
+    /// Returns None until the thermal capacity and rated cool power are set and at least one cool
+    /// cycle has lowered the temperature.
+    pub fn get_estimated_cooling_cop(&self) -> Option<f64> {
+        let capacity = self.state.thermal_capacity_kj_per_degree?;
+        let runtime_secs = self.state.statistics.total_cool_runtime_secs;
+        if self.state.rated_cool_power_kw <= 0.0 || runtime_secs == 0 {
+            return None;
+        }
+        if self.state.total_cool_temperature_drop <= 0.0 {
+            return None;
+        }
+        let cop = capacity * self.state.total_cool_temperature_drop
+            / (runtime_secs as f64 * self.state.rated_cool_power_kw);
+        match self.state.outdoor_temperature {
+            Some(outdoor) if outdoor > self.state.current_temperature => {
+                let indoor = self.state.current_temperature;
+                let carnot = (indoor + 273.15) / (outdoor - indoor);
+                Some(cop.min(carnot))
+            }
+            _ => Some(cop),
+        }
+    }
+
+    /// Change the heat capacity of the conditioned space used for efficiency estimates.
+    ///
+    /// This is the energy in kilojoules needed to change the temperature of the space by one
+    /// degree Celsius.
+    ///
+    /// An Err Result is returned if the capacity is not positive and finite.
+    pub fn set_thermal_capacity_kj_per_degree(&mut self, capacity: f64) -> Result<(), Error> {
+        if !capacity.is_finite() || capacity <= 0.0 {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.thermal_capacity_kj_per_degree = Some(capacity);
+        Ok(())
+    }
+    /// Get the heat capacity of the conditioned space, if set
+    pub fn get_thermal_capacity_kj_per_degree(&self) -> Option<f64> {
+        self.state.thermal_capacity_kj_per_degree
+    }
+
+    /// Change the rated power draw of the cooling equipment used for energy estimates.
+    ///
+    /// An Err Result is returned if the power is negative or not finite.
+    pub fn set_rated_cool_power_kw(&mut self, power_kw: f64) -> Result<(), Error> {
+        if !power_kw.is_finite() || power_kw < 0.0 {
+            return Err(Error::InvalidConfiguration);
+        }
+        self.state.rated_cool_power_kw = power_kw;
+        Ok(())
+    }
+    /// Get the rated power draw of the cooling equipment
+    pub fn get_rated_cool_power_kw(&self) -> f64 {
+        self.state.rated_cool_power_kw
+    }
+}
+
+fn running_since(start: Option<u64>, end: Option<u64>) -> Option<u64> {
+    match (start, end) {
+        (Some(start), Some(end)) if end >= start => None,
+        (start, _) => start,
+    }
+}
+
+fn duty_cycle_last_hour(history: &CycleHistory, running_since: Option<u64>, now: u64) -> f64 {
+    let active_secs = history.active_secs(running_since, now.saturating_sub(3600), now);
+    (active_secs as f64 / 3600.0).min(1.0)
+}
@@ -0,0 +1,236 @@
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Add, Sub};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Temperature units used when presenting temperatures
+///
+/// The thermostat always stores and compares temperatures in degrees Celsius; the unit only
+/// affects how temperatures are displayed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TemperatureUnit {
+    /// Degrees Celsius
+    Celsius,
+    /// Degrees Fahrenheit
+    Fahrenheit,
+}
+
+impl fmt::Display for TemperatureUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+        })
+    }
+}
+
+/// Convert degrees Celsius to degrees Fahrenheit
+pub fn celsius_to_fahrenheit(celsius: f64) -> f64 {
+    celsius * 9.0 / 5.0 + 32.0
+}
+
+/// Convert degrees Fahrenheit to degrees Celsius
+pub fn fahrenheit_to_celsius(fahrenheit: f64) -> f64 {
+    (fahrenheit - 32.0) * 5.0 / 9.0
+}
+
+/// Displays a Celsius temperature in degrees Celsius with a unit label, e.g. `22.5 °C`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CelsiusDisplay(pub f64);
+
+impl fmt::Display for CelsiusDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.1} {}", self.0, TemperatureUnit::Celsius)
+    }
+}
+
+/// Displays a Celsius temperature in degrees Fahrenheit with a unit label, e.g. `72.5 °F`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FahrenheitDisplay(pub f64);
+
+impl fmt::Display for FahrenheitDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} {}",
+            celsius_to_fahrenheit(self.0),
+            TemperatureUnit::Fahrenheit
+        )
+    }
+}
+
+/// Displays a Celsius temperature in the given unit with a unit label
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TemperatureDisplay {
+    /// Temperature in degrees Celsius
+    pub celsius: f64,
+    /// Unit to display the temperature in
+    pub unit: TemperatureUnit,
+}
+
+impl fmt::Display for TemperatureDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.unit {
+            TemperatureUnit::Celsius => CelsiusDisplay(self.celsius).fmt(f),
+            TemperatureUnit::Fahrenheit => FahrenheitDisplay(self.celsius).fmt(f),
+        }
+    }
+}
+
+/// Temperature scales usable with `Temperature`
+pub trait TemperatureScale {
+    /// Unit corresponding to this scale
+    const UNIT: TemperatureUnit;
+}
+
+/// Marker type for temperatures in degrees Celsius
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Celsius;
+
+impl TemperatureScale for Celsius {
+    const UNIT: TemperatureUnit = TemperatureUnit::Celsius;
+}
+
+/// Marker type for temperatures in degrees Fahrenheit
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Fahrenheit;
+
+impl TemperatureScale for Fahrenheit {
+    const UNIT: TemperatureUnit = TemperatureUnit::Fahrenheit;
+}
+
+/// A temperature tagged with its scale
+///
+/// Thermostat setters accept anything convertible into `Temperature<Celsius>`, including any
+/// `TemperatureValue` in degrees Celsius and a `Temperature<Fahrenheit>`, so values cannot be
+/// passed in the wrong scale by accident.
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Temperature<S: TemperatureScale>(f64, PhantomData<S>);
+
+impl<S: TemperatureScale> Temperature<S> {
+    /// Create a temperature in scale `S`
+    pub const fn new(value: f64) -> Temperature<S> {
+        Temperature(value, PhantomData)
+    }
+
+    /// Get the raw value in scale `S`
+    pub fn as_f64(self) -> f64 {
+        self.0
+    }
+}
+
+impl Temperature<Celsius> {
+    /// Convert to degrees Fahrenheit
+    pub fn to_fahrenheit(self) -> Temperature<Fahrenheit> {
+        Temperature::new(celsius_to_fahrenheit(self.0))
+    }
+}
+
+impl Temperature<Fahrenheit> {
+    /// Convert to degrees Celsius
+    pub fn to_celsius(self) -> Temperature<Celsius> {
+        Temperature::new(fahrenheit_to_celsius(self.0))
+    }
+}
+
+/// Numeric types usable as raw temperatures in degrees Celsius
+///
+/// Implemented for `f32` and `f64`. Fixed-point types can implement it to pass readings and set
+/// points straight to thermostat setters; the thermostat converts them with `to_celsius` and
+/// performs its own control arithmetic in `f64`.
+///
+/// The thermostat itself is deliberately not generic over this trait. Its control calculations
+/// scale temperatures by trends, gains, ramp rates, and proportional bands, which need more than
+/// the addition and subtraction required here, so a generic thermostat would convert to `f64`
+/// internally all the same while every getter and configuration field changed type.
+pub trait TemperatureValue: PartialOrd + Copy + Add<Output = Self> + Sub<Output = Self> {
+    /// Create a value from degrees Celsius
+    fn from_celsius(celsius: f64) -> Self;
+    /// Convert the value to degrees Celsius
+    fn to_celsius(self) -> f64;
+}
+
+impl TemperatureValue for f64 {
+    fn from_celsius(celsius: f64) -> f64 {
+        celsius
+    }
+    fn to_celsius(self) -> f64 {
+        self
+    }
+}
+
+impl TemperatureValue for f32 {
+    fn from_celsius(celsius: f64) -> f32 {
+        celsius as f32
+    }
+    fn to_celsius(self) -> f64 {
+        self as f64
+    }
+}
+
+impl<T: TemperatureValue> From<T> for Temperature<Celsius> {
+    fn from(celsius: T) -> Temperature<Celsius> {
+        Temperature::new(celsius.to_celsius())
+    }
+}
+
+impl From<Temperature<Fahrenheit>> for Temperature<Celsius> {
+    fn from(temperature: Temperature<Fahrenheit>) -> Temperature<Celsius> {
+        temperature.to_celsius()
+    }
+}
+
+impl From<Temperature<Celsius>> for Temperature<Fahrenheit> {
+    fn from(temperature: Temperature<Celsius>) -> Temperature<Fahrenheit> {
+        temperature.to_fahrenheit()
+    }
+}
+
+impl<S: TemperatureScale> fmt::Display for Temperature<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.1} {}", self.0, S::UNIT)
+    }
+}
+
+/// Common reference temperatures for heating, cooling, and refrigeration applications
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TemperatureRange {
+    /// Freezing point of water, 0 °C
+    FreezingPoint,
+    /// Food storage refrigerator, 3 °C (safely below the 4 °C food safety limit)
+    RefrigeratorIdeal,
+    /// Long-term wine storage, 13 °C
+    WineIdeal,
+    /// Draft beer dispensing, 3 °C
+    KegeratorIdeal,
+    /// Comfortable indoor room temperature, 21 °C
+    RoomTemperature,
+    /// Normal human body temperature, 37 °C
+    BodyTemperature,
+    /// Warmest reasonable residential cooling set point, 26 °C
+    HVACCoolMax,
+    /// Coolest reasonable residential heating set point, 18 °C
+    HVACHeatMin,
+}
+
+/// Get the reference temperature for a range in degrees Celsius
+pub fn temperature_range_celsius(range: TemperatureRange) -> f64 {
+    match range {
+        TemperatureRange::FreezingPoint => 0.0,
+        TemperatureRange::RefrigeratorIdeal => 3.0,
+        TemperatureRange::WineIdeal => 13.0,
+        TemperatureRange::KegeratorIdeal => 3.0,
+        TemperatureRange::RoomTemperature => 21.0,
+        TemperatureRange::BodyTemperature => 37.0,
+        TemperatureRange::HVACCoolMax => 26.0,
+        TemperatureRange::HVACHeatMin => 18.0,
+    }
+}
+
+/// Get the reference temperature for a range in degrees Fahrenheit
+pub fn temperature_range_fahrenheit(range: TemperatureRange) -> f64 {
+    celsius_to_fahrenheit(temperature_range_celsius(range))
+}
@@ -0,0 +1,245 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use is_timing_constraint;
+use Error;
+use ThermostatCore;
+use ThermostatInterface;
+use TransitionTrigger;
+use DEFAULT_EVENT_LOG_CAPACITY;
+
+/// How a `ZoneController` chooses which zone the shared equipment serves
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ZonePriority {
+    /// Serve the zone that started calling first until it is satisfied
+    FirstCall,
+    /// Serve the zone furthest beyond its set point, re-evaluated on every tick
+    MostDemand,
+    /// Serve calling zones in turn, moving on once the served zone is satisfied
+    RoundRobin,
+}
+
+/// Call a zone makes on the shared equipment
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ZoneCall {
+    /// The zone is calling for heat
+    Heat,
+    /// The zone is calling for cool
+    Cool,
+}
+
+/// Wrapper for the zone dampers of a shared air handler
+pub trait ZoneDamperInterface {
+    /// opens the damper of the zone at `zone_index`
+    fn open_damper(&self, zone_index: usize) -> Result<(), Error>;
+    /// closes the damper of the zone at `zone_index`
+    fn close_damper(&self, zone_index: usize) -> Result<(), Error>;
+}
+
+/// Arbitrates `Z` zone thermostats sharing one air handler
+///
+/// Each zone thermostat calls for heat or cool on its own interface as usual. On every `tick` the
+/// controller chooses one calling zone to serve according to its `ZonePriority`, and opens the
+/// dampers of every enabled zone making the same call as the served zone while closing the rest.
+/// The application runs the shared equipment for `get_active_call`.
+///
+/// The zones may be any kind of thermostat, e.g. `DynThermostat` or `Thermostat`, as long as they
+/// hold their interfaces the same way `I` and retain `N` events.
+pub struct ZoneController<'a, 'b, I, const Z: usize, const N: usize = DEFAULT_EVENT_LOG_CAPACITY>
+{
+    zones: [&'a mut ThermostatCore<'b, I, N>; Z],
+    dampers: &'a dyn ZoneDamperInterface,
+    priority: ZonePriority,
+    enabled: [bool; Z],
+    damper_open: [Option<bool>; Z],
+    // tick on which each zone started its current call, for FirstCall
+    calling_since: [Option<u64>; Z],
+    ticks: u64,
+    active_zone: Option<usize>,
+    active_call: Option<ZoneCall>,
+    last_served_zone: Option<usize>,
+}
+
+impl<'a, 'b, I: ThermostatInterface, const Z: usize, const N: usize>
+    ZoneController<'a, 'b, I, Z, N>
+{
+    /// Create a controller for the provided zones, all of them enabled
+    pub fn new(
+        zones: [&'a mut ThermostatCore<'b, I, N>; Z],
+        dampers: &'a dyn ZoneDamperInterface,
+        priority: ZonePriority,
+    ) -> ZoneController<'a, 'b, I, Z, N> {
+        ZoneController {
+            zones,
+            dampers,
+            priority,
+            enabled: [true; Z],
+            damper_open: [None; Z],
+            calling_since: [None; Z],
+            ticks: 0,
+            active_zone: None,
+            active_call: None,
+            last_served_zone: None,
+        }
+    }
+
+    /// Change the arbitration policy
+    pub fn set_priority(&mut self, priority: ZonePriority) {
+        self.priority = priority;
+    }
+    /// Get the arbitration policy
+    pub fn get_priority(&self) -> ZonePriority {
+        self.priority
+    }
+
+    /// Enable or disable a zone.
+    ///
+    /// A disabled zone is not evaluated and its damper is closed on the next tick.
+    ///
+    /// An Err Result is returned if there is no zone at `zone_index`.
+    pub fn set_zone_enabled(&mut self, zone_index: usize, enabled: bool) -> Result<(), Error> {
+        match self.enabled.get_mut(zone_index) {
+            Some(zone_enabled) => {
+                *zone_enabled = enabled;
+                Ok(())
+            }
+            None => Err(Error::InvalidZone),
+        }
+    }
+    /// Check whether the zone at `zone_index` is enabled, false if there is no such zone
+    pub fn is_zone_enabled(&self, zone_index: usize) -> bool {
+        self.enabled.get(zone_index).cloned().unwrap_or(false)
+    }
+
+    /// Get the thermostat of the zone at `zone_index`
+    pub fn get_zone(&self, zone_index: usize) -> Option<&ThermostatCore<'b, I, N>> {
+        self.zones.get(zone_index).map(|zone| &**zone)
+    }
+    /// Get the thermostat of the zone at `zone_index` to change it, e.g. to pass it a reading
+    pub fn get_zone_mut(&mut self, zone_index: usize) -> Option<&mut ThermostatCore<'b, I, N>> {
+        self.zones.get_mut(zone_index).map(|zone| &mut **zone)
+    }
+
+    /// Get the zone served on the last tick, if any zone was calling
+    pub fn get_active_zone(&self) -> Option<usize> {
+        self.active_zone
+    }
+    /// Get the call the shared equipment should run for, as decided on the last tick
+    pub fn get_active_call(&self) -> Option<ZoneCall> {
+        self.active_call
+    }
+
+    /// Evaluate all enabled zones, choose the zone to serve, and move the dampers to match.
+    ///
+    /// Timing constraints reported by zone thermostats are expected while they wait to change
+    /// outputs and are not returned. An Err Result is returned if a zone fails for any other
+    /// reason or a damper or zone output cannot be moved or read; every zone is still evaluated
+    /// and the dampers are still moved as far as possible.
+    pub fn tick(&mut self) -> Result<(), Error> {
+        self.ticks += 1;
+        let mut result = Ok(());
+        let mut calls = [None; Z];
+        for (zone_index, call) in calls.iter_mut().enumerate() {
+            if !self.enabled[zone_index] {
+                self.calling_since[zone_index] = None;
+                continue;
+            }
+            let zone = &mut *self.zones[zone_index];
+            let temperature = zone.get_current_temperature();
+            match zone.reevaluate(TransitionTrigger::TemperatureReading(temperature)) {
+                Err(error) if !is_timing_constraint(error) => result = result.and(Err(error)),
+                _ => {}
+            }
+            *call = match zone_call(zone) {
+                Ok(call) => call,
+                Err(error) => {
+                    result = result.and(Err(error));
+                    None
+                }
+            };
+            if call.is_none() {
+                self.calling_since[zone_index] = None;
+            } else if self.calling_since[zone_index].is_none() {
+                self.calling_since[zone_index] = Some(self.ticks);
+            }
+        }
+        self.active_zone = self.choose_zone(&calls);
+        self.active_call = self.active_zone.and_then(|zone_index| calls[zone_index]);
+        if self.active_zone.is_some() {
+            self.last_served_zone = self.active_zone;
+        }
+        for (zone_index, call) in calls.iter().enumerate() {
+            let open = call.is_some() && *call == self.active_call;
+            if self.damper_open[zone_index] == Some(open) {
+                continue;
+            }
+            let moved = if open {
+                self.dampers.open_damper(zone_index)
+            } else {
+                self.dampers.close_damper(zone_index)
+            };
+            match moved {
+                Ok(()) => self.damper_open[zone_index] = Some(open),
+                Err(error) => result = result.and(Err(error)),
+            }
+        }
+        result
+    }
+
+    fn choose_zone(&self, calls: &[Option<ZoneCall>; Z]) -> Option<usize> {
+        let calling = |zone_index: &usize| calls[*zone_index].is_some();
+        // the served zone keeps the equipment until satisfied, except under MostDemand
+        if let Some(zone_index) = self.active_zone {
+            if calls[zone_index] == self.active_call
+                && calling(&zone_index)
+                && self.priority != ZonePriority::MostDemand
+            {
+                return Some(zone_index);
+            }
+        }
+        match self.priority {
+            ZonePriority::FirstCall => (0..Z)
+                .filter(calling)
+                .min_by_key(|&zone_index| self.calling_since[zone_index]),
+            ZonePriority::MostDemand => (0..Z).filter(calling).fold(None, |best, zone_index| {
+                let demand = zone_demand(self.zones[zone_index], calls[zone_index]);
+                match best {
+                    Some((_, best_demand)) if best_demand >= demand => best,
+                    _ => Some((zone_index, demand)),
+                }
+            })
+            .map(|(zone_index, _)| zone_index),
+            ZonePriority::RoundRobin => {
+                let start = self.last_served_zone.map_or(0, |zone_index| zone_index + 1);
+                (start..start + Z).map(|zone_index| zone_index % Z).find(calling)
+            }
+        }
+    }
+}
+
+fn zone_call<I: ThermostatInterface, const N: usize>(
+    zone: &ThermostatCore<I, N>,
+) -> Result<Option<ZoneCall>, Error> {
+    Ok(if zone.interface.calling_for_heat()? {
+        Some(ZoneCall::Heat)
+    } else if zone.interface.calling_for_cool()? {
+        Some(ZoneCall::Cool)
+    } else {
+        None
+    })
+}
+
+// Degrees the zone is beyond the set point it is calling to reach, as used for its control
+fn zone_demand<I: ThermostatInterface, const N: usize>(
+    zone: &ThermostatCore<I, N>,
+    call: Option<ZoneCall>,
+) -> f64 {
+    let temperature = zone.state.current_temperature;
+    match call {
+        Some(ZoneCall::Heat) => zone.get_effective_minimum_set_temperature() - temperature,
+        Some(ZoneCall::Cool) => temperature - zone.get_effective_maximum_set_temperature(),
+        None => 0.0,
+    }
+}
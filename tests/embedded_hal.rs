@@ -0,0 +1,62 @@
+#![cfg(feature = "embedded-hal")]
+
+extern crate embedded_hal;
+extern crate nb;
+extern crate thermostat;
+extern crate void;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::timer::{CountDown, Periodic};
+use thermostat::*;
+use void::Void;
+
+#[derive(Clone, Default)]
+struct MockPin(Rc<Cell<bool>>);
+
+impl OutputPin for MockPin {
+    type Error = ();
+    fn set_high(&mut self) -> Result<(), ()> {
+        self.0.set(true);
+        Ok(())
+    }
+    fn set_low(&mut self) -> Result<(), ()> {
+        self.0.set(false);
+        Ok(())
+    }
+}
+
+// Completes a period every time it is polled
+struct MockTimer;
+
+impl CountDown for MockTimer {
+    type Time = u32;
+    fn start<T: Into<u32>>(&mut self, _count: T) {}
+    fn wait(&mut self) -> nb::Result<(), Void> {
+        Ok(())
+    }
+}
+
+impl Periodic for MockTimer {}
+
+#[test]
+fn hal_interface_drives_pins_and_counts_seconds() {
+    let heat = MockPin::default();
+    let interface = EmbeddedHalInterface::new(
+        heat.clone(),
+        MockPin::default(),
+        MockPin::default(),
+        MockTimer,
+    )
+    .unwrap();
+    assert!(!interface.calling_for_heat().unwrap());
+    interface.call_for_heat().unwrap();
+    assert!(heat.0.get());
+    assert!(interface.calling_for_heat().unwrap());
+    interface.stop_call_for_heat().unwrap();
+    assert!(!heat.0.get());
+    assert_eq!(interface.get_seconds().unwrap(), 1);
+    assert_eq!(interface.get_seconds().unwrap(), 2);
+}
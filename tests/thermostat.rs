@@ -1,57 +1,158 @@
 // #[cfg(test)]
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 extern crate thermostat;
 
-use std::time::SystemTime;
+use std::cell::{Cell, RefCell};
 use thermostat::*;
 
+#[derive(Default)]
 struct AlwaysWorksInterface {
-    heat: bool,
-    cool: bool,
-    fan: bool,
-    start: SystemTime,
-}
-
-impl Default for AlwaysWorksInterface {
-    fn default() -> Self {
-        Self {
-            heat: false,
-            cool: false,
-            fan: false,
-            start: SystemTime::now(),
-        }
-    }
+    heat: Cell<bool>,
+    cool: Cell<bool>,
+    fan: Cell<bool>,
+    seconds: Cell<u64>,
+    resets: Cell<u32>,
+    alarm: Cell<bool>,
+    vacant: Cell<bool>,
+    aux: Cell<bool>,
+    valve: Cell<bool>,
+    humidifier: Cell<bool>,
+    dehumidifier_fitted: Cell<bool>,
+    dehumidifier: Cell<bool>,
+    reading: Cell<Option<f64>>,
+    modulating: Cell<bool>,
+    heat_modulation: Cell<Option<f64>>,
+    #[cfg(feature = "defrost")]
+    defrost: Cell<bool>,
 }
 
 impl ThermostatInterface for AlwaysWorksInterface {
     fn calling_for_heat(&self) -> Result<bool, Error> {
-        Ok(self.heat)
+        Ok(self.heat.get())
     }
     fn calling_for_cool(&self) -> Result<bool, Error> {
-        Ok(self.cool)
+        Ok(self.cool.get())
     }
     fn calling_for_fan(&self) -> Result<bool, Error> {
-        Ok(self.fan)
+        Ok(self.fan.get())
     }
     fn call_for_heat(&self) -> Result<(), Error> {
+        self.heat.set(true);
         Ok(())
     }
     fn call_for_cool(&self) -> Result<(), Error> {
+        self.cool.set(true);
         Ok(())
     }
     fn call_for_fan(&self) -> Result<(), Error> {
+        self.fan.set(true);
         Ok(())
     }
     fn stop_call_for_heat(&self) -> Result<(), Error> {
+        self.heat.set(false);
         Ok(())
     }
     fn stop_call_for_cool(&self) -> Result<(), Error> {
+        self.cool.set(false);
         Ok(())
     }
     fn stop_call_for_fan(&self) -> Result<(), Error> {
+        self.fan.set(false);
         Ok(())
     }
     fn get_seconds(&self) -> Result<u64, Error> {
-        Ok(self.start.elapsed().unwrap().as_secs())
+        Ok(self.seconds.get())
+    }
+    fn reset_hardware(&self) -> Result<(), Error> {
+        self.resets.set(self.resets.get() + 1);
+        Ok(())
+    }
+    fn trigger_alarm(&self) -> Result<(), Error> {
+        self.alarm.set(true);
+        Ok(())
+    }
+    fn clear_alarm(&self) -> Result<(), Error> {
+        self.alarm.set(false);
+        Ok(())
+    }
+    fn get_occupancy_input(&self) -> Result<bool, Error> {
+        Ok(!self.vacant.get())
+    }
+    fn measure(&self) -> Result<Option<f64>, Error> {
+        Ok(self.reading.take())
+    }
+    fn calling_for_aux_heat(&self) -> Result<bool, Error> {
+        Ok(self.aux.get())
+    }
+    fn call_for_aux_heat(&self) -> Result<(), Error> {
+        self.aux.set(true);
+        Ok(())
+    }
+    fn stop_call_for_aux_heat(&self) -> Result<(), Error> {
+        self.aux.set(false);
+        Ok(())
+    }
+    fn calling_for_humidifier(&self) -> Result<bool, Error> {
+        Ok(self.humidifier.get())
+    }
+    fn call_for_humidifier(&self) -> Result<(), Error> {
+        self.humidifier.set(true);
+        Ok(())
+    }
+    fn stop_call_for_humidifier(&self) -> Result<(), Error> {
+        self.humidifier.set(false);
+        Ok(())
+    }
+    fn has_dehumidifier(&self) -> bool {
+        self.dehumidifier_fitted.get()
+    }
+    fn calling_for_dehumidifier(&self) -> Result<bool, Error> {
+        Ok(self.dehumidifier.get())
+    }
+    fn call_for_dehumidifier(&self) -> Result<(), Error> {
+        self.dehumidifier.set(true);
+        Ok(())
+    }
+    fn stop_call_for_dehumidifier(&self) -> Result<(), Error> {
+        self.dehumidifier.set(false);
+        Ok(())
+    }
+    fn set_heat_modulation(&self, percent: f64) -> Result<(), Error> {
+        if !self.modulating.get() {
+            return Err(Error::HandlerFailed);
+        }
+        self.heat_modulation.set(Some(percent));
+        Ok(())
+    }
+    fn set_cool_modulation(&self, _percent: f64) -> Result<(), Error> {
+        if !self.modulating.get() {
+            return Err(Error::HandlerFailed);
+        }
+        Ok(())
+    }
+    fn calling_for_reversing_valve(&self) -> Result<bool, Error> {
+        Ok(self.valve.get())
+    }
+    #[cfg(feature = "defrost")]
+    fn call_for_defrost_heat(&self) -> Result<(), Error> {
+        self.defrost.set(true);
+        Ok(())
+    }
+    #[cfg(feature = "defrost")]
+    fn stop_call_for_defrost_heat(&self) -> Result<(), Error> {
+        self.defrost.set(false);
+        Ok(())
+    }
+    fn engage_reversing_valve(&self) -> Result<(), Error> {
+        self.valve.set(true);
+        Ok(())
+    }
+    fn disengage_reversing_valve(&self) -> Result<(), Error> {
+        self.valve.set(false);
+        Ok(())
     }
 }
 
@@ -108,6 +209,10 @@ fn thermo_changes_operating_mode() {
 fn thermo_changes_maximum_safe_temperature() {
     let interface = AlwaysWorksInterface::default();
     let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_minimum_safe_temperature(-20.0).unwrap();
+    // set temperatures are lowered with the limit, so the unoccupied ones need room to stay apart
+    thermostat.set_unoccupied_minimum_set_temperature(-20.0).unwrap();
+    thermostat.set_unoccupied_maximum_set_temperature(-19.0).unwrap();
     thermostat.set_maximum_safe_temperature(5.0).unwrap();
     assert_eq!(thermostat.get_maximum_safe_temperature(), 5.0);
     thermostat.set_maximum_safe_temperature(15.0).unwrap();
@@ -136,10 +241,65 @@ fn thermo_changes_minimum_safe_temperature() {
     assert_eq!(thermostat.get_minimum_safe_temperature(), -0.0);
 }
 
+#[test]
+fn thermo_narrowing_safe_range_moves_set_temperatures() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_maximum_set_temperature(26.0).unwrap();
+    thermostat.set_hysteresis(1.0).unwrap();
+    thermostat.set_maximum_safe_temperature(24.0).unwrap();
+    assert_eq!(thermostat.get_maximum_set_temperature(), 24.0);
+    assert_eq!(thermostat.get_unoccupied_maximum_set_temperature(), 24.0);
+    assert_eq!(thermostat.get_target_temperature(), 21.0);
+    thermostat.set_minimum_safe_temperature(22.0).unwrap();
+    assert_eq!(thermostat.get_minimum_set_temperature(), 22.0);
+    assert_eq!(thermostat.get_target_temperature(), 22.0);
+    assert_eq!(thermostat.get_config().validate(), Ok(()));
+    // set temperatures squeezed too close for the hysteresis are refused
+    assert_eq!(
+        thermostat.set_maximum_safe_temperature(22.5),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(thermostat.get_maximum_safe_temperature(), 24.0);
+    assert_eq!(thermostat.get_maximum_set_temperature(), 24.0);
+}
+
+#[test]
+fn thermo_safe_temperature_range_invalid() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_minimum_safe_temperature(20.0).unwrap();
+    assert_eq!(
+        thermostat.set_maximum_safe_temperature(15.0),
+        Err(Error::SafeTemperatureRangeInversion)
+    );
+    assert_eq!(
+        thermostat.set_maximum_safe_temperature(20.0),
+        Err(Error::SafeTemperatureRangeInversion)
+    );
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_minimum_safe_temperature(10.0).unwrap();
+    thermostat.set_unoccupied_minimum_set_temperature(10.0).unwrap();
+    thermostat.set_unoccupied_maximum_set_temperature(14.0).unwrap();
+    thermostat.set_maximum_safe_temperature(15.0).unwrap();
+    assert_eq!(
+        thermostat.set_minimum_safe_temperature(20.0),
+        Err(Error::SafeTemperatureRangeInversion)
+    );
+    assert_eq!(
+        thermostat.set_minimum_safe_temperature(15.0),
+        Err(Error::SafeTemperatureRangeInversion)
+    );
+}
+
 #[test]
 fn thermo_changes_maximum_set_temperature() {
     let interface = AlwaysWorksInterface::default();
     let mut thermostat = Thermostat::new(&interface);
+    // the maximum may not go below the minimum set temperature
+    thermostat.set_minimum_safe_temperature(-20.0).unwrap();
+    thermostat.set_minimum_set_temperature(-20.0).unwrap();
     thermostat.set_maximum_set_temperature(5.0).unwrap();
     assert_eq!(thermostat.get_maximum_set_temperature(), 5.0);
     thermostat.set_maximum_set_temperature(15.0).unwrap();
@@ -156,6 +316,7 @@ fn thermo_changes_maximum_set_temperature() {
 fn thermo_changes_minimum_set_temperature() {
     let interface = AlwaysWorksInterface::default();
     let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_minimum_safe_temperature(-20.0).unwrap();
     thermostat.set_minimum_set_temperature(5.0).unwrap();
     assert_eq!(thermostat.get_minimum_set_temperature(), 5.0);
     thermostat.set_minimum_set_temperature(15.0).unwrap();
@@ -167,3 +328,2900 @@ fn thermo_changes_minimum_set_temperature() {
     thermostat.set_minimum_set_temperature(0.0).unwrap();
     assert_eq!(thermostat.get_minimum_set_temperature(), -0.0);
 }
+
+#[test]
+fn thermo_set_temperatures_must_be_within_safe_range() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert_eq!(
+        thermostat.set_maximum_set_temperature(50.0),
+        Err(Error::SetPointOutOfSafeRange)
+    );
+    assert_eq!(
+        thermostat.set_minimum_set_temperature(10.0),
+        Err(Error::SetPointOutOfSafeRange)
+    );
+    assert_eq!(
+        thermostat.set_maximum_set_temperature(f64::NAN),
+        Err(Error::InvalidTemperature)
+    );
+    assert_eq!(
+        thermostat.set_minimum_set_temperature(f64::NEG_INFINITY),
+        Err(Error::InvalidTemperature)
+    );
+    assert_eq!(thermostat.get_maximum_set_temperature(), 30.0);
+    assert_eq!(thermostat.get_minimum_set_temperature(), 15.0);
+}
+
+#[test]
+fn thermo_changes_run_time_constraints() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_minimum_cool_run_secs(30).unwrap();
+    thermostat.set_maximum_cool_run_secs(900).unwrap();
+    thermostat.set_minimum_cool_off_secs(30).unwrap();
+    thermostat.set_minimum_fan_run_secs(30).unwrap();
+    assert_eq!(thermostat.get_minimum_cool_run_secs(), 30);
+    assert_eq!(thermostat.get_maximum_cool_run_secs(), 900);
+    assert_eq!(thermostat.get_minimum_cool_off_secs(), 30);
+    assert_eq!(
+        thermostat.set_minimum_heat_run_secs(0),
+        Err(Error::InvalidConstraint)
+    );
+    assert_eq!(
+        thermostat.set_minimum_fan_run_secs(thermostat.get_maximum_fan_run_secs()),
+        Err(Error::InvalidConstraint)
+    );
+    assert_eq!(
+        thermostat.set_maximum_heat_run_secs(thermostat.get_minimum_heat_run_secs()),
+        Err(Error::InvalidConstraint)
+    );
+    assert_eq!(
+        thermostat.set_minimum_fan_off_secs(0),
+        Err(Error::InvalidConstraint)
+    );
+    let config = ThermostatConfig {
+        maximum_heat_run_secs: 600,
+        ..ThermostatConfig::default()
+    };
+    assert_eq!(config.validate(), Err(Error::InvalidConfiguration));
+    assert_eq!(thermostat.get_minimum_heat_run_secs(), 600);
+    thermostat
+        .set_operating_mode(OperatingMode::CoolToSetPoint)
+        .unwrap();
+    thermostat.set_maximum_set_temperature(24.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(26.0).unwrap();
+    assert!(interface.cool.get());
+    interface.seconds.set(1030);
+    thermostat.set_current_temperature(22.0).unwrap();
+    assert!(!interface.cool.get());
+}
+
+#[test]
+fn thermo_rejects_non_finite_temperatures() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    for &value in &[f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+        let invalid = Err(Error::InvalidTemperature);
+        assert_eq!(
+            thermostat.set_current_temperature(value).err(),
+            Some(Error::InvalidTemperature)
+        );
+        assert_eq!(thermostat.set_maximum_set_temperature(value), invalid);
+        assert_eq!(thermostat.set_minimum_set_temperature(value), invalid);
+        assert_eq!(thermostat.set_maximum_safe_temperature(value), invalid);
+        assert_eq!(thermostat.set_minimum_safe_temperature(value), invalid);
+    }
+    assert!(!thermostat.is_ready());
+    assert_eq!(thermostat.get_maximum_set_temperature(), 30.0);
+    assert_eq!(thermostat.get_minimum_set_temperature(), 15.0);
+}
+
+#[test]
+fn thermo_control_output_is_proportional() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_maximum_set_temperature(25.0).unwrap();
+    let _ = thermostat.set_current_temperature(19.0);
+    assert_eq!(thermostat.get_heat_control_output(), 50.0);
+    assert_eq!(thermostat.get_cool_control_output(), 0.0);
+    let _ = thermostat.set_current_temperature(28.0);
+    assert_eq!(thermostat.get_heat_control_output(), 0.0);
+    assert_eq!(thermostat.get_cool_control_output(), 100.0);
+}
+
+#[test]
+fn thermo_modulates_heat_within_timing_constraints() {
+    let interface = AlwaysWorksInterface::default();
+    interface.modulating.set(true);
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(19.0).unwrap();
+    assert!(interface.heat.get());
+    assert_eq!(interface.heat_modulation.get(), Some(50.0));
+    assert_eq!(thermostat.get_statistics().total_heat_cycles, 1);
+    interface.seconds.set(1100);
+    assert_eq!(
+        thermostat.set_current_temperature(21.0),
+        Err(Error::HeatMinRunTimeConstraint)
+    );
+    assert!(interface.heat.get());
+    assert_eq!(interface.heat_modulation.get(), Some(50.0));
+    interface.seconds.set(1600);
+    thermostat.set_current_temperature(21.0).unwrap();
+    assert!(!interface.heat.get());
+    assert_eq!(interface.heat_modulation.get(), Some(0.0));
+}
+
+#[test]
+fn thermo_falls_back_to_binary_when_modulation_fails() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_maximum_set_temperature(24.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(19.0).unwrap();
+    assert!(interface.heat.get());
+    assert_eq!(interface.heat_modulation.get(), None);
+    interface.seconds.set(2000);
+    thermostat.set_current_temperature(21.0).unwrap();
+    assert!(!interface.heat.get());
+    interface.seconds.set(3000);
+    thermostat.set_current_temperature(26.0).unwrap();
+    assert!(interface.cool.get());
+    interface.seconds.set(4000);
+    thermostat.set_current_temperature(22.0).unwrap();
+    assert!(!interface.cool.get());
+}
+
+#[test]
+fn thermo_logs_configuration_events() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert!(thermostat.get_event_log().is_empty());
+    thermostat.set_minimum_set_temperature(18.0).unwrap();
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    let events: Vec<EventKind> = thermostat.get_event_log().iter().map(|e| e.event).collect();
+    assert_eq!(
+        events,
+        vec![
+            EventKind::MinimumSetTemperatureChanged(18.0),
+            EventKind::OperatingModeChanged(OperatingMode::MaintainRange),
+        ]
+    );
+}
+
+#[test]
+fn thermo_event_log_discards_oldest_events() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::<2>::with_event_log_capacity(&interface);
+    thermostat.set_minimum_set_temperature(16.0).unwrap();
+    thermostat.set_minimum_set_temperature(17.0).unwrap();
+    thermostat.set_minimum_set_temperature(18.0).unwrap();
+    let events: Vec<EventKind> = thermostat.get_event_log().iter().map(|e| e.event).collect();
+    assert_eq!(
+        events,
+        vec![
+            EventKind::MinimumSetTemperatureChanged(17.0),
+            EventKind::MinimumSetTemperatureChanged(18.0),
+        ]
+    );
+}
+
+#[test]
+fn thermo_drains_events_in_batches() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_minimum_set_temperature(16.0).unwrap();
+    thermostat.set_minimum_set_temperature(17.0).unwrap();
+    thermostat.set_minimum_set_temperature(18.0).unwrap();
+    assert_eq!(thermostat.pending_event_count(), 3);
+    let empty = TimestampedEvent {
+        timestamp_secs: 0,
+        event: EventKind::FanStopped,
+    };
+    let mut buffer = [empty; 2];
+    assert_eq!(thermostat.drain_events(&mut buffer), 2);
+    assert_eq!(buffer[0].event, EventKind::MinimumSetTemperatureChanged(16.0));
+    assert_eq!(buffer[1].event, EventKind::MinimumSetTemperatureChanged(17.0));
+    assert_eq!(thermostat.pending_event_count(), 1);
+    assert_eq!(thermostat.drain_events(&mut buffer), 1);
+    assert_eq!(buffer[0].event, EventKind::MinimumSetTemperatureChanged(18.0));
+    assert_eq!(thermostat.pending_event_count(), 0);
+    assert_eq!(thermostat.drain_events(&mut buffer), 0);
+    assert_eq!(thermostat.get_event_log().len(), 3);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn thermo_config_parses_args() {
+    let config =
+        ThermostatConfig::from_args(&["--min-set=18.0", "--max-set=22.0", "--mode=MaintainRange"])
+            .unwrap();
+    assert_eq!(config.minimum_set_temperature, 18.0);
+    assert_eq!(config.maximum_set_temperature, 22.0);
+    assert_eq!(config.operating_mode, OperatingMode::MaintainRange);
+    assert_eq!(config.maximum_safe_temperature, 30.0);
+    assert_eq!(
+        ThermostatConfig::from_args(&["--bogus=1"]),
+        Err(ParseError::UnrecognizedKey("bogus"))
+    );
+    assert_eq!(
+        ThermostatConfig::from_args(&["--min-set=warm"]),
+        Err(ParseError::InvalidValue {
+            key: "min-set",
+            value: "warm"
+        })
+    );
+    assert_eq!(
+        ThermostatConfig::from_args(&["min-set"]),
+        Err(ParseError::MalformedArgument("min-set"))
+    );
+}
+
+fn always_fan(_temperature: f64, _humidity: f64) -> ControlDecision {
+    ControlDecision {
+        call_fan: true,
+        ..ControlDecision::default()
+    }
+}
+
+#[test]
+fn thermo_custom_mode_requires_control_fn() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert_eq!(
+        thermostat.set_operating_mode(OperatingMode::Custom),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::Disabled);
+    thermostat.set_custom_control_fn(always_fan);
+    thermostat.set_operating_mode(OperatingMode::Custom).unwrap();
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::Custom);
+}
+
+fn heat_below_twenty_cool_above_twenty_four(temperature: f64, _humidity: f64) -> ControlDecision {
+    let call_heat = temperature < 20.0;
+    let call_cool = temperature > 24.0;
+    ControlDecision {
+        call_heat,
+        call_cool,
+        call_fan: call_heat || call_cool,
+    }
+}
+
+#[test]
+fn thermo_custom_mode_drives_outputs() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_custom_control_fn(heat_below_twenty_cool_above_twenty_four);
+    thermostat.set_operating_mode(OperatingMode::Custom).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert!(interface.heat.get());
+    assert!(!interface.cool.get());
+    assert!(interface.fan.get());
+    interface.seconds.set(2000);
+    thermostat.set_current_temperature(22.0).unwrap();
+    assert!(!interface.heat.get());
+    assert!(!interface.cool.get());
+    assert!(!interface.fan.get());
+    interface.seconds.set(3000);
+    thermostat.set_current_temperature(26.0).unwrap();
+    assert!(!interface.heat.get());
+    assert!(interface.cool.get());
+    assert!(interface.fan.get());
+}
+
+#[test]
+fn thermo_forecast_sets_demand() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert_eq!(thermostat.get_forecast_based_demand(), None);
+    thermostat.set_current_temperature(20.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat
+        .set_temperature_forecast(&[18.0, 17.0], 1800)
+        .unwrap();
+    assert_eq!(thermostat.get_forecast_based_demand(), Some(-4.0));
+    // the second prediction takes over once the first one's time has passed
+    interface.seconds.set(2800);
+    assert_eq!(thermostat.get_forecast_based_demand(), Some(-6.0));
+    interface.seconds.set(3700);
+    assert_eq!(thermostat.get_forecast_based_demand(), Some(-12.0));
+    // and the forecast expires after the last one
+    interface.seconds.set(4600);
+    assert_eq!(thermostat.get_forecast_based_demand(), None);
+    assert_eq!(
+        thermostat.set_temperature_forecast(&[f64::NAN], 1800),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(
+        thermostat.set_temperature_forecast(&[20.0; 25], 1800),
+        Err(Error::InvalidConfiguration)
+    );
+    thermostat.set_temperature_forecast(&[], 1800).unwrap();
+    assert_eq!(thermostat.get_forecast_based_demand(), None);
+}
+
+#[test]
+fn thermo_forces_outputs_in_disabled_unsafe_mode() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::DisabledUnsafe)
+        .unwrap();
+    unsafe {
+        thermostat.force_heat_unsafe().unwrap();
+        thermostat.force_cool_unsafe().unwrap();
+    }
+}
+
+#[test]
+#[should_panic]
+fn thermo_forcing_outputs_outside_disabled_unsafe_mode_panics() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    let _ = unsafe { thermostat.force_heat_unsafe() };
+}
+
+#[test]
+fn thermo_displays_temperatures_in_display_unit() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert_eq!(thermostat.get_display_unit(), TemperatureUnit::Celsius);
+    assert_eq!(thermostat.display_temperature(22.5).to_string(), "22.5 °C");
+    thermostat.set_display_unit(TemperatureUnit::Fahrenheit);
+    assert_eq!(thermostat.get_display_unit(), TemperatureUnit::Fahrenheit);
+    assert_eq!(thermostat.display_temperature(22.5).to_string(), "72.5 °F");
+    assert_eq!(thermostat.get_minimum_set_temperature(), 15.0);
+}
+
+#[test]
+fn thermo_reports_uptime() {
+    let interface = AlwaysWorksInterface::default();
+    interface.seconds.set(1000);
+    let thermostat = Thermostat::new(&interface);
+    assert_eq!(thermostat.get_uptime_secs(), Ok(0));
+    interface.seconds.set(1600);
+    assert_eq!(thermostat.get_uptime_secs(), Ok(600));
+}
+
+#[test]
+fn thermo_safety_audit_reports_first_failure() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    let _ = thermostat.set_current_temperature(20.0);
+    assert_eq!(thermostat.is_safe(), Ok(true));
+    let _ = thermostat.set_current_temperature(35.0);
+    assert_eq!(thermostat.is_safe(), Ok(false));
+    assert_eq!(
+        thermostat.get_last_error(),
+        Some(Error::TemperatureOutsideSafeRange)
+    );
+}
+
+#[test]
+fn thermo_accepts_typed_temperatures() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_minimum_set_temperature(Temperature::<Celsius>::new(18.0))
+        .unwrap();
+    assert_eq!(thermostat.get_minimum_set_temperature(), 18.0);
+    thermostat
+        .set_maximum_set_temperature(Temperature::<Fahrenheit>::new(77.0))
+        .unwrap();
+    assert_eq!(thermostat.get_maximum_set_temperature(), 25.0);
+    assert_eq!(
+        Temperature::<Celsius>::new(100.0).to_fahrenheit(),
+        Temperature::<Fahrenheit>::new(212.0)
+    );
+}
+
+#[test]
+fn thermo_builds_from_config() {
+    let interface = AlwaysWorksInterface::default();
+    let config = ThermostatConfig {
+        operating_mode: OperatingMode::MaintainRange,
+        minimum_set_temperature: 18.0,
+        maximum_set_temperature: 22.5,
+        ..ThermostatConfig::default()
+    };
+    let thermostat = ThermostatBuilder::from_config(config, &interface).unwrap();
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::MaintainRange);
+    assert_eq!(thermostat.get_minimum_set_temperature(), 18.0);
+    assert_eq!(thermostat.get_maximum_set_temperature(), 22.5);
+
+    let inverted = ThermostatConfig {
+        minimum_set_temperature: 25.0,
+        maximum_set_temperature: 20.0,
+        ..ThermostatConfig::default()
+    };
+    assert!(ThermostatBuilder::from_config(inverted, &interface).is_err());
+}
+
+#[test]
+fn thermo_config_diff_lists_changed_fields() {
+    let saved = ThermostatConfig::default();
+    assert!(saved.diff(&saved).is_empty());
+    let current = ThermostatConfig {
+        operating_mode: OperatingMode::HeatToSetPoint,
+        minimum_set_temperature: 19.0,
+        minimum_fan_off_secs: 60,
+        hysteresis: 1.0,
+        ..saved
+    };
+    assert_eq!(
+        saved.diff(&current).changed_fields(),
+        &[
+            "operating_mode",
+            "minimum_set_temperature",
+            "minimum_fan_off_secs",
+            "hysteresis"
+        ]
+    );
+}
+
+#[derive(Default)]
+struct MemoryStorage {
+    config: std::cell::Cell<Option<ThermostatConfig>>,
+}
+
+impl ThermostatStorage for MemoryStorage {
+    fn load(&self) -> Result<Option<ThermostatConfig>, Error> {
+        Ok(self.config.get())
+    }
+    fn save(&self, config: &ThermostatConfig) -> Result<(), Error> {
+        self.config.set(Some(*config));
+        Ok(())
+    }
+}
+
+#[test]
+fn thermo_saves_and_restores_config() {
+    let interface = AlwaysWorksInterface::default();
+    let storage = MemoryStorage::default();
+    let mut thermostat = Thermostat::new_with_storage(&interface, &storage).unwrap();
+    assert_eq!(thermostat.get_minimum_set_temperature(), 15.0);
+    thermostat.set_minimum_set_temperature(19.5).unwrap();
+    thermostat.save_config().unwrap();
+    let restored = Thermostat::new_with_storage(&interface, &storage).unwrap();
+    assert_eq!(restored.get_minimum_set_temperature(), 19.5);
+    assert_eq!(
+        Thermostat::new(&interface).save_config(),
+        Err(Error::InvalidConfiguration)
+    );
+}
+
+#[test]
+fn thermo_predicts_no_heat_endpoint_when_not_heating() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    let _ = thermostat.set_current_temperature(20.0);
+    assert_eq!(thermostat.get_predicted_heat_endpoint_temperature(), None);
+}
+
+#[test]
+fn thermo_changes_maximum_heat_swing() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert_eq!(thermostat.get_maximum_heat_swing(), f64::INFINITY);
+    thermostat.set_maximum_heat_swing(5.0).unwrap();
+    assert_eq!(thermostat.get_maximum_heat_swing(), 5.0);
+    assert_eq!(
+        thermostat.set_maximum_heat_swing(0.0),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(
+        thermostat.set_maximum_heat_swing(f64::NAN),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(thermostat.get_fault(), None);
+}
+
+#[test]
+fn thermo_warns_about_suboptimal_constraints() {
+    let interface = AlwaysWorksInterface::default();
+    let config = ThermostatConfig {
+        minimum_cool_off_secs: 30,
+        ..ThermostatConfig::default()
+    };
+    assert_eq!(
+        Thermostat::new(&interface).warn_suboptimal_constraints(),
+        Ok(None)
+    );
+    let mut thermostat = ThermostatBuilder::from_config(config, &interface).unwrap();
+    let warning = thermostat.warn_suboptimal_constraints().unwrap().unwrap();
+    assert_eq!(warning.constraint, Error::CoolMinOffTimeConstraint);
+    assert_eq!(warning.recommended_minimum_secs, 180);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    assert_eq!(thermostat.warn_suboptimal_constraints(), Ok(None));
+}
+
+#[test]
+fn thermo_reference_temperatures_convert_between_scales() {
+    assert_eq!(temperature_range_celsius(TemperatureRange::FreezingPoint), 0.0);
+    assert_eq!(
+        temperature_range_fahrenheit(TemperatureRange::FreezingPoint),
+        32.0
+    );
+    assert_eq!(
+        temperature_range_fahrenheit(TemperatureRange::BodyTemperature),
+        98.6
+    );
+}
+
+#[test]
+fn thermo_writes_debug_dump() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    for t in 16..23 {
+        thermostat.set_minimum_set_temperature(t as f64).unwrap();
+    }
+    let mut dump = String::new();
+    thermostat.write_debug(&mut dump).unwrap();
+    let lines: Vec<&str> = dump.lines().collect();
+    assert_eq!(lines.len(), 11);
+    assert_eq!(lines[0], "Disabled flt:None");
+    assert_eq!(lines[2], "set:22.0-30.0 safe:15.0-30.0");
+    assert_eq!(lines[3], "heat 600-3600/300 None-None");
+    assert!(lines[10].ends_with("MinimumSetTemperatureChanged(22.0)"));
+}
+
+#[test]
+fn thermo_applies_temperature_profile_atomically() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .apply_temperature_profile(10.0, 35.0, 24.0, 27.0)
+        .unwrap();
+    assert_eq!(thermostat.get_minimum_safe_temperature(), 10.0);
+    assert_eq!(thermostat.get_maximum_safe_temperature(), 35.0);
+    assert_eq!(thermostat.get_minimum_set_temperature(), 24.0);
+    assert_eq!(thermostat.get_maximum_set_temperature(), 27.0);
+    assert_eq!(
+        thermostat.apply_temperature_profile(12.0, 30.0, 18.0, 30.0),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(thermostat.get_minimum_safe_temperature(), 10.0);
+    assert_eq!(thermostat.get_maximum_safe_temperature(), 35.0);
+    assert_eq!(thermostat.get_minimum_set_temperature(), 24.0);
+    assert_eq!(thermostat.get_maximum_set_temperature(), 27.0);
+}
+
+#[cfg(feature = "defrost")]
+#[test]
+fn thermo_changes_defrost_settings() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert_eq!(thermostat.get_defrost_interval_hours(), 0);
+    assert!(!thermostat.is_defrosting());
+    thermostat.set_defrost_interval_hours(6).unwrap();
+    assert_eq!(thermostat.get_defrost_interval_hours(), 6);
+    thermostat.set_defrost_duration_secs(900).unwrap();
+    assert_eq!(thermostat.get_defrost_duration_secs(), 900);
+    thermostat.set_defrost_max_temperature(8.0).unwrap();
+    assert_eq!(thermostat.get_defrost_max_temperature(), 8.0);
+    assert_eq!(
+        thermostat.set_defrost_duration_secs(6 * 3600),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(
+        thermostat.set_defrost_duration_secs(0),
+        Err(Error::InvalidConfiguration)
+    );
+}
+
+#[cfg(feature = "defrost")]
+#[test]
+fn thermo_runs_defrost_cycle_after_cooling_runtime() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::CoolToSetPoint)
+        .unwrap();
+    thermostat.set_maximum_set_temperature(20.0).unwrap();
+    thermostat.set_maximum_cool_run_secs(7200).unwrap();
+    thermostat.set_defrost_interval_hours(1).unwrap();
+    thermostat.set_defrost_max_temperature(40.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(25.0).unwrap();
+    assert!(interface.cool.get());
+    assert!(!thermostat.is_defrosting());
+    interface.seconds.set(4600);
+    thermostat.set_current_temperature(25.0).unwrap();
+    assert!(thermostat.is_defrosting());
+    assert!(interface.defrost.get());
+    assert!(!interface.cool.get());
+    interface.seconds.set(5000);
+    thermostat.set_current_temperature(25.0).unwrap();
+    assert!(thermostat.is_defrosting());
+    assert!(!interface.cool.get());
+    interface.seconds.set(5800);
+    thermostat.set_current_temperature(25.0).unwrap();
+    assert!(!thermostat.is_defrosting());
+    assert!(!interface.defrost.get());
+    assert!(interface.cool.get());
+}
+
+#[test]
+fn thermo_changes_thermal_mass() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert_eq!(thermostat.get_thermal_mass(), ThermalMassProfile::Medium);
+    assert_eq!(thermostat.get_thermal_mass().time_constant_secs(), 1800.0);
+    thermostat.set_thermal_mass(ThermalMassProfile::High).unwrap();
+    assert_eq!(thermostat.get_thermal_mass(), ThermalMassProfile::High);
+    assert_eq!(
+        thermostat.set_thermal_mass(ThermalMassProfile::Custom(f64::NAN)),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(thermostat.get_thermal_mass(), ThermalMassProfile::High);
+}
+
+#[test]
+fn thermo_anticipates_thermal_mass_only_when_enabled() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(23.0).unwrap();
+    interface.seconds.set(1060);
+    thermostat.set_current_temperature(22.5).unwrap();
+    assert!(!interface.heat.get());
+    thermostat.set_trend_anticipation_enabled(true);
+    thermostat.set_current_temperature(22.5).unwrap();
+    assert!(interface.heat.get());
+}
+
+#[test]
+fn thermo_is_ready_after_first_reading() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert!(!thermostat.is_ready());
+    let _ = thermostat.set_current_temperature(f64::NAN);
+    assert!(!thermostat.is_ready());
+    assert_eq!(thermostat.get_readings_received(), 0);
+    let _ = thermostat.set_current_temperature(20.0);
+    assert!(thermostat.is_ready());
+    assert_eq!(thermostat.get_readings_received(), 1);
+}
+
+#[test]
+fn thermo_changes_differential_settings() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::Differential)
+        .unwrap();
+    thermostat.set_source_temperature(60.0);
+    assert_eq!(thermostat.get_source_temperature(), 60.0);
+    thermostat.set_differential_on_threshold(10.0).unwrap();
+    thermostat.set_differential_off_threshold(2.0).unwrap();
+    assert_eq!(thermostat.get_differential_on_threshold(), 10.0);
+    assert_eq!(thermostat.get_differential_off_threshold(), 2.0);
+    assert_eq!(
+        thermostat.set_differential_off_threshold(10.0),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(
+        thermostat.set_differential_on_threshold(1.0),
+        Err(Error::InvalidConfiguration)
+    );
+}
+
+#[test]
+fn thermo_differential_mode_runs_pump() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::Differential)
+        .unwrap();
+    thermostat.set_differential_on_threshold(10.0).unwrap();
+    thermostat.set_differential_off_threshold(2.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_source_temperature(40.0);
+    thermostat.set_current_temperature(25.0).unwrap();
+    assert!(interface.fan.get());
+    interface.seconds.set(1500);
+    thermostat.set_source_temperature(30.0);
+    thermostat.set_current_temperature(25.0).unwrap();
+    assert!(interface.fan.get()); // between thresholds, so the pump keeps running
+    interface.seconds.set(2000);
+    thermostat.set_source_temperature(26.0);
+    thermostat.set_current_temperature(25.0).unwrap();
+    assert!(!interface.fan.get());
+    interface.seconds.set(2500);
+    thermostat.set_source_temperature(30.0);
+    thermostat.set_current_temperature(25.0).unwrap();
+    assert!(!interface.fan.get()); // between thresholds, so the pump stays off
+    assert!(!interface.heat.get());
+    assert!(!interface.cool.get());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn thermo_simulates_scenario() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(18.0).unwrap();
+    thermostat.set_maximum_set_temperature(24.0).unwrap();
+    let step = |timestamp_secs, temperature| SimulationStep {
+        timestamp_secs,
+        temperature,
+        humidity: 50.0,
+    };
+    let results = thermostat.simulate(&[step(1000, 15.0), step(1200, 16.0), step(5000, 21.0)]);
+    assert_eq!(
+        results,
+        vec![
+            SimulationResult {
+                step: 0,
+                action: ActuatorAction::FanStart
+            },
+            SimulationResult {
+                step: 0,
+                action: ActuatorAction::HeatStart
+            },
+            SimulationResult {
+                step: 2,
+                action: ActuatorAction::HeatStop
+            },
+            SimulationResult {
+                step: 2,
+                action: ActuatorAction::FanStop
+            },
+        ]
+    );
+    assert!(!interface.calling_for_heat().unwrap());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn thermo_simulation_does_not_notify_event_handler() {
+    #[derive(Default)]
+    struct CountingHandler {
+        heat_started: Cell<u32>,
+    }
+    impl ThermostatEventHandler for CountingHandler {
+        fn on_heat_started(&self) {
+            self.heat_started.set(self.heat_started.get() + 1);
+        }
+    }
+    let interface = AlwaysWorksInterface::default();
+    let handler = CountingHandler::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_event_handler(&handler);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    let results = thermostat.simulate(&[SimulationStep {
+        timestamp_secs: 1000,
+        temperature: 15.0,
+        humidity: 50.0,
+    }]);
+    assert!(results.contains(&SimulationResult {
+        step: 0,
+        action: ActuatorAction::HeatStart
+    }));
+    assert_eq!(handler.heat_started.get(), 0);
+}
+
+#[test]
+fn thermo_accepts_generic_temperature_values() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_maximum_set_temperature(24.5f32).unwrap();
+    assert_eq!(thermostat.get_maximum_set_temperature(), 24.5);
+    assert_eq!(f32::from_celsius(15.0), 15.0f32);
+    assert_eq!(TemperatureValue::to_celsius(21.5f32), 21.5);
+}
+
+#[test]
+fn thermo_maintain_range_calls_heat_below_minimum() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    // past the minimum off time, which counts from system start
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(19.0).unwrap();
+    assert!(interface.heat.get());
+    assert!(interface.fan.get());
+    assert!(!interface.cool.get());
+}
+
+struct ScheduledInterface {
+    inner: AlwaysWorksInterface,
+}
+
+impl ThermostatInterface for ScheduledInterface {
+    fn calling_for_heat(&self) -> Result<bool, Error> {
+        self.inner.calling_for_heat()
+    }
+    fn calling_for_cool(&self) -> Result<bool, Error> {
+        self.inner.calling_for_cool()
+    }
+    fn calling_for_fan(&self) -> Result<bool, Error> {
+        self.inner.calling_for_fan()
+    }
+    fn call_for_heat(&self) -> Result<(), Error> {
+        self.inner.call_for_heat()
+    }
+    fn call_for_cool(&self) -> Result<(), Error> {
+        self.inner.call_for_cool()
+    }
+    fn call_for_fan(&self) -> Result<(), Error> {
+        self.inner.call_for_fan()
+    }
+    fn stop_call_for_heat(&self) -> Result<(), Error> {
+        self.inner.stop_call_for_heat()
+    }
+    fn stop_call_for_cool(&self) -> Result<(), Error> {
+        self.inner.stop_call_for_cool()
+    }
+    fn stop_call_for_fan(&self) -> Result<(), Error> {
+        self.inner.stop_call_for_fan()
+    }
+    fn get_seconds(&self) -> Result<u64, Error> {
+        self.inner.get_seconds()
+    }
+    fn get_schedule_entry(&self, hour: u8, _minute: u8) -> Result<Option<ScheduleEntry>, Error> {
+        Ok(if hour >= 6 {
+            Some(ScheduleEntry {
+                heat_set_point: 21.0,
+                cool_set_point: 25.0,
+            })
+        } else {
+            None
+        })
+    }
+}
+
+#[test]
+fn thermo_scheduling_uses_schedule_entry() {
+    let interface = ScheduledInterface {
+        inner: AlwaysWorksInterface::default(),
+    };
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::Scheduling)
+        .unwrap();
+    interface.inner.seconds.set(3600);
+    thermostat.set_current_temperature(20.0).unwrap();
+    assert!(!interface.inner.heat.get());
+    interface.inner.seconds.set(7 * 3600);
+    thermostat.set_current_temperature(20.0).unwrap();
+    assert!(interface.inner.heat.get());
+    assert_eq!(thermostat.get_minimum_set_temperature(), 15.0);
+}
+
+#[test]
+fn thermo_disabled_acts_outside_safe_humidity() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert_eq!(thermostat.get_minimum_safe_humidity(), 10.0);
+    assert_eq!(thermostat.get_maximum_safe_humidity(), 90.0);
+    thermostat.set_maximum_safe_humidity(70.0).unwrap();
+    assert_eq!(
+        thermostat.set_minimum_safe_humidity(80.0),
+        Err(Error::InvalidConfiguration)
+    );
+    interface.seconds.set(1000);
+    thermostat.set_current_humidity(75.0).unwrap();
+    thermostat.set_current_temperature(20.0).unwrap();
+    assert!(interface.fan.get());
+    assert!(!interface.heat.get());
+    assert!(!interface.humidifier.get());
+    interface.seconds.set(2000);
+    thermostat.set_current_humidity(5.0).unwrap();
+    thermostat.set_current_temperature(20.0).unwrap();
+    assert!(interface.humidifier.get());
+    assert!(!interface.fan.get());
+}
+
+#[test]
+fn thermo_reports_effective_operating_mode() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_current_temperature(20.0).unwrap();
+    assert_eq!(
+        thermostat.get_effective_operating_mode(),
+        EffectiveMode {
+            base_mode: OperatingMode::Disabled,
+            override_reason: None,
+        }
+    );
+    let _ = thermostat.set_current_temperature(10.0);
+    assert_eq!(
+        thermostat.get_effective_operating_mode().override_reason,
+        Some(OverrideReason::FreezeProtection)
+    );
+    thermostat.set_current_temperature(20.0).unwrap();
+    thermostat.set_current_humidity(95.0).unwrap();
+    assert_eq!(
+        thermostat.get_effective_operating_mode().override_reason,
+        Some(OverrideReason::MoldPrevention)
+    );
+    thermostat.set_current_humidity(5.0).unwrap();
+    assert_eq!(
+        thermostat.get_effective_operating_mode().override_reason,
+        Some(OverrideReason::DryAirProtection)
+    );
+}
+
+#[test]
+fn thermo_location_defaults_to_none() {
+    let interface = AlwaysWorksInterface::default();
+    let thermostat = Thermostat::new(&interface);
+    assert_eq!(thermostat.get_location_info(), None);
+}
+
+#[test]
+fn thermo_config_hash_tracks_changes() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    let mut other = Thermostat::new(&interface);
+    let hash = thermostat.get_config_hash();
+    assert_eq!(hash, other.get_config_hash());
+    thermostat.set_minimum_set_temperature(18.0).unwrap();
+    assert_ne!(thermostat.get_config_hash(), hash);
+    thermostat.set_minimum_set_temperature(15.0).unwrap();
+    assert_eq!(thermostat.get_config_hash(), hash);
+
+    thermostat.set_target_temperature(22.0).unwrap();
+    let changed = thermostat.get_config_hash();
+    assert_ne!(changed, hash);
+    thermostat.set_hysteresis(1.0).unwrap();
+    assert_ne!(thermostat.get_config_hash(), changed);
+    let changed = thermostat.get_config_hash();
+    thermostat.set_heat_deadband(1.0).unwrap();
+    assert_ne!(thermostat.get_config_hash(), changed);
+    let changed = thermostat.get_config_hash();
+    thermostat.set_cool_deadband(1.0).unwrap();
+    assert_ne!(thermostat.get_config_hash(), changed);
+
+    other.set_target_temperature(22.0).unwrap();
+    other.set_hysteresis(1.0).unwrap();
+    other.set_heat_deadband(1.0).unwrap();
+    other.set_cool_deadband(1.0).unwrap();
+    assert_eq!(thermostat.get_config_hash(), other.get_config_hash());
+}
+
+#[test]
+fn thermo_rounds_temperatures_to_precision() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert_eq!(thermostat.get_temperature_precision(), 1);
+    thermostat.set_current_temperature(20.25).unwrap();
+    assert_eq!(thermostat.get_current_temperature(), 20.3);
+    let _ = thermostat.set_current_temperature(-20.25);
+    assert_eq!(thermostat.get_current_temperature(), -20.2);
+    thermostat.set_temperature_precision(0).unwrap();
+    thermostat.set_minimum_set_temperature(17.5).unwrap();
+    assert_eq!(thermostat.get_minimum_set_temperature(), 18.0);
+    assert_eq!(
+        thermostat.set_temperature_precision(5),
+        Err(Error::InvalidConfiguration)
+    );
+}
+
+#[test]
+fn thermo_resets_hardware_on_fault() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_maximum_heat_swing(5.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    interface.seconds.set(2000);
+    thermostat.set_current_temperature(25.0).unwrap();
+    assert_eq!(thermostat.get_fault(), Some(Error::HeatSwingExceeded));
+    assert_eq!(interface.resets.get(), 1);
+    thermostat.clear_fault();
+    assert_eq!(thermostat.get_fault(), None);
+    assert_eq!(interface.resets.get(), 2);
+}
+
+#[test]
+fn thermo_heat_swing_fault_locks_out_heat_until_cleared() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_maximum_heat_swing(5.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    interface.seconds.set(2000);
+    thermostat.set_current_temperature(25.0).unwrap();
+    interface.seconds.set(3000);
+    assert_eq!(
+        thermostat.set_current_temperature(18.0),
+        Err(Error::HeatSwingExceeded)
+    );
+    assert!(!interface.heat.get());
+    assert_eq!(thermostat.is_safe(), Ok(false));
+    assert_eq!(thermostat.get_last_error(), Some(Error::HeatSwingExceeded));
+    thermostat.clear_fault();
+    assert_eq!(thermostat.is_safe(), Ok(true));
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert!(interface.heat.get());
+}
+
+#[test]
+fn thermo_rejects_inverted_set_points_in_every_mode() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_maximum_set_temperature(22.0).unwrap();
+    assert_eq!(
+        thermostat.set_minimum_set_temperature(25.0),
+        Err(Error::InvalidConfiguration)
+    );
+    thermostat.set_minimum_set_temperature(22.0).unwrap();
+    assert_eq!(
+        thermostat.set_maximum_set_temperature(21.0),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(thermostat.get_config().validate(), Ok(()));
+}
+
+#[test]
+fn thermo_hysteresis_must_be_narrower_than_set_point_range() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_maximum_set_temperature(24.0).unwrap();
+    assert_eq!(
+        thermostat.set_hysteresis(4.0),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(
+        thermostat.set_hysteresis(-1.0),
+        Err(Error::InvalidConfiguration)
+    );
+    thermostat.set_hysteresis(1.0).unwrap();
+    assert_eq!(thermostat.get_hysteresis(), 1.0);
+    assert_eq!(
+        thermostat.set_maximum_set_temperature(20.5),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(
+        thermostat.set_minimum_set_temperature(23.5),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(
+        thermostat.apply_temperature_profile(15.0, 30.0, 22.0, 22.5),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(thermostat.get_maximum_set_temperature(), 24.0);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(19.0).unwrap();
+    interface.seconds.set(2000);
+    thermostat.set_current_temperature(20.5).unwrap();
+    assert!(interface.heat.get());
+    interface.seconds.set(3000);
+    thermostat.set_current_temperature(21.0).unwrap();
+    assert!(!interface.heat.get());
+}
+
+#[test]
+fn thermo_triggers_temperature_alarm() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_high_temperature_alarm(28.0).unwrap();
+    thermostat.set_low_temperature_alarm(16.0).unwrap();
+    assert_eq!(
+        thermostat.set_low_temperature_alarm(28.0),
+        Err(Error::InvalidConfiguration)
+    );
+    thermostat.set_current_temperature(29.0).unwrap();
+    assert!(thermostat.is_alarm_active());
+    assert!(interface.alarm.get());
+    assert_eq!(thermostat.get_last_error(), Some(Error::AlarmActive));
+    thermostat.set_current_temperature(22.0).unwrap();
+    assert!(!thermostat.is_alarm_active());
+    assert!(!interface.alarm.get());
+}
+
+struct MemoryKeyValueStore {
+    bytes: [Cell<u8>; 256],
+}
+
+impl KeyValueStore for MemoryKeyValueStore {
+    fn write_u8(&self, key: u8, value: u8) -> Result<(), Error> {
+        self.bytes[key as usize].set(value);
+        Ok(())
+    }
+    fn read_u8(&self, key: u8) -> Result<u8, Error> {
+        Ok(self.bytes[key as usize].get())
+    }
+}
+
+#[test]
+fn thermo_config_round_trips_through_kv_store() {
+    let store = MemoryKeyValueStore {
+        bytes: std::array::from_fn(|_| Cell::new(0xff)),
+    };
+    assert_eq!(
+        ThermostatConfig::load_from_kv_store(&store),
+        Err(Error::InvalidConfiguration)
+    );
+    let config = ThermostatConfig {
+        operating_mode: OperatingMode::MaintainRange,
+        minimum_set_temperature: 19.5,
+        maximum_cool_run_secs: 1800,
+        target_temperature: 20.5,
+        cool_deadband: 1.0,
+        unoccupied_maximum_set_temperature: 26.5,
+        ..ThermostatConfig::default()
+    };
+    config.save_to_kv_store(&store).unwrap();
+    assert_eq!(ThermostatConfig::load_from_kv_store(&store), Ok(config));
+}
+
+#[test]
+fn thermo_restores_config() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert_eq!(thermostat.get_config(), ThermostatConfig::default());
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(19.0).unwrap();
+    thermostat.set_minimum_heat_off_secs(120).unwrap();
+    thermostat.set_target_temperature(22.0).unwrap();
+    thermostat.set_hysteresis(0.5).unwrap();
+    thermostat.set_heat_deadband(1.0).unwrap();
+    let config = thermostat.get_config();
+    assert_eq!(config.target_temperature, 22.0);
+    assert_eq!(config.hysteresis, 0.5);
+    assert_eq!(config.heat_deadband, 1.0);
+    let mut restored = Thermostat::new(&interface);
+    restored.apply_config(&config).unwrap();
+    assert_eq!(restored.get_config(), config);
+    assert_eq!(restored.get_minimum_set_temperature(), 19.0);
+    assert_eq!(restored.get_hysteresis(), 0.5);
+    let too_wide = ThermostatConfig {
+        hysteresis: 20.0,
+        ..config
+    };
+    assert_eq!(
+        restored.apply_config(&too_wide),
+        Err(Error::InvalidConfiguration)
+    );
+    let inverted = ThermostatConfig {
+        minimum_set_temperature: 25.0,
+        maximum_set_temperature: 20.0,
+        ..config
+    };
+    assert_eq!(
+        restored.apply_config(&inverted),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(restored.get_config(), config);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn thermo_serde_round_trips() {
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use std::fmt::Debug;
+    fn round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(value: T) {
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<T>(&json).unwrap(), value);
+    }
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    round_trip(thermostat.get_config());
+    round_trip(ThermostatConfig::from_preset(ApplicationPreset::DataCenter));
+    round_trip(ApplicationPreset::DataCenter);
+    for &mode in &[OperatingMode::MaintainRange, OperatingMode::AutoChangeover] {
+        round_trip(mode);
+    }
+    for &error in Error::all_variants() {
+        round_trip(error);
+        round_trip(ConstraintError(error, Some(30)));
+    }
+    round_trip(thermostat.get_status().unwrap());
+    round_trip(thermostat.get_state());
+    round_trip(thermostat.get_statistics());
+    round_trip(thermostat.get_runtime_stats());
+    for &event in thermostat.get_event_log() {
+        round_trip(event);
+    }
+    round_trip(thermostat.get_last_transition().unwrap());
+    round_trip(thermostat.get_next_event_estimate().unwrap());
+    round_trip(ThermalMassProfile::Custom(900.0));
+    round_trip(ZonePriority::RoundRobin);
+    round_trip(ZoneCall::Cool);
+    round_trip(ScheduleSlot {
+        day_of_week: 5,
+        time_of_day_secs: 7 * 3600,
+        operating_mode: OperatingMode::MaintainRange,
+        min_set_temp: 19.0,
+        max_set_temp: 25.0,
+    });
+    round_trip(ScheduleEntry {
+        heat_set_point: 19.0,
+        cool_set_point: 25.0,
+    });
+    round_trip(LocationInfo {
+        latitude_degrees: 51.5,
+        longitude_degrees: -0.1,
+        altitude_meters: 11.0,
+    });
+    round_trip(ControlDecision::default());
+    round_trip(OverrideReason::FreezeProtection);
+    round_trip(TemperatureUnit::Fahrenheit);
+}
+
+#[test]
+fn thermo_accumulates_statistics() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_rated_heat_power_kw(10.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    interface.seconds.set(2800);
+    thermostat.set_current_temperature(21.0).unwrap();
+    let statistics = thermostat.get_statistics();
+    assert_eq!(statistics.total_heat_cycles, 1);
+    assert_eq!(statistics.total_fan_cycles, 1);
+    assert_eq!(statistics.total_heat_runtime_secs, 1800);
+    assert_eq!(statistics.estimated_heat_energy_kwh, 5.0);
+    assert_eq!(statistics.total_cool_cycles, 0);
+    assert_eq!(statistics.uptime_secs, 2800);
+}
+
+#[test]
+fn thermo_tracks_runtime_stats() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert_eq!(thermostat.get_runtime_stats(), RuntimeStats::default());
+    interface.seconds.set(1900);
+    thermostat.set_current_temperature(21.0).unwrap();
+    let stats = thermostat.get_runtime_stats();
+    assert_eq!(stats.heat_total_secs, 900);
+    assert_eq!(stats.heat_cycle_count, 1);
+    assert_eq!(stats.fan_total_secs, 900);
+    assert_eq!(stats.fan_cycle_count, 1);
+    assert_eq!(stats.cool_cycle_count, 0);
+    thermostat.reset_runtime_stats();
+    assert_eq!(thermostat.get_runtime_stats(), RuntimeStats::default());
+    assert_eq!(thermostat.get_statistics().total_heat_runtime_secs, 900);
+}
+
+#[test]
+fn thermo_heat_respects_minimum_run_time() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert!(interface.heat.get());
+    interface.seconds.set(1100);
+    assert_eq!(
+        thermostat.set_current_temperature(22.0),
+        Err(Error::HeatMinRunTimeConstraint)
+    );
+    assert!(interface.heat.get());
+    interface.seconds.set(1600);
+    thermostat.set_current_temperature(22.0).unwrap();
+    assert!(!interface.heat.get());
+}
+
+#[test]
+fn thermo_records_last_transition() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    assert_eq!(thermostat.get_last_transition(), None);
+    // blocked by the minimum off time counted from system start
+    assert!(thermostat.set_current_temperature(18.0).is_err());
+    assert_eq!(thermostat.get_last_transition(), None);
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert_eq!(
+        thermostat.get_last_transition(),
+        Some(Transition {
+            from_state: SystemState::default(),
+            to_state: SystemState {
+                heat: true,
+                cool: false,
+                fan: true,
+            },
+            trigger: TransitionTrigger::TimingConstraintCleared,
+            timestamp_secs: 1000,
+        })
+    );
+}
+
+#[test]
+fn thermo_borrows_concrete_interface() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new_static(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert!(thermostat.get_interface().heat.get());
+}
+
+#[test]
+fn thermo_owns_interface() {
+    let mut thermostat = OwnedThermostat::new_owned(AlwaysWorksInterface::default());
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.get_interface().seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert!(thermostat.get_interface().heat.get());
+}
+
+#[test]
+fn thermo_constructs_in_const_context() {
+    thread_local! {
+        static THERMOSTAT: RefCell<OwnedThermostat<'static, AlwaysWorksInterface>> = const {
+            RefCell::new(OwnedThermostat::const_new(AlwaysWorksInterface {
+                heat: Cell::new(false),
+                cool: Cell::new(false),
+                fan: Cell::new(false),
+                seconds: Cell::new(0),
+                resets: Cell::new(0),
+                alarm: Cell::new(false),
+                vacant: Cell::new(false),
+                aux: Cell::new(false),
+                valve: Cell::new(false),
+                humidifier: Cell::new(false),
+                dehumidifier_fitted: Cell::new(false),
+                dehumidifier: Cell::new(false),
+                reading: Cell::new(None),
+                modulating: Cell::new(false),
+                heat_modulation: Cell::new(None),
+                #[cfg(feature = "defrost")]
+                defrost: Cell::new(false),
+            }))
+        };
+    }
+    THERMOSTAT.with(|thermostat| {
+        let mut thermostat = thermostat.borrow_mut();
+        assert_eq!(thermostat.get_config(), ThermostatConfig::default());
+        thermostat
+            .set_operating_mode(OperatingMode::MaintainRange)
+            .unwrap();
+        thermostat.set_minimum_set_temperature(20.0).unwrap();
+        thermostat.get_interface().seconds.set(1000);
+        thermostat.set_current_temperature(18.0).unwrap();
+        assert!(thermostat.get_interface().heat.get());
+    });
+}
+
+#[test]
+fn thermo_builder_sets_initial_temperature() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = ThermostatBuilder::new(&interface)
+        .initial_temperature(21.0)
+        .build()
+        .unwrap();
+    assert_eq!(thermostat.get_current_temperature(), 21.0);
+    thermostat.set_current_temperature(19.0).unwrap();
+    assert_eq!(thermostat.get_current_temperature(), 19.0);
+    assert_eq!(
+        ThermostatBuilder::new(&interface)
+            .initial_temperature(f64::NAN)
+            .build()
+            .err(),
+        Some(Error::InvalidConfiguration)
+    );
+}
+
+#[test]
+fn thermo_builder_configures_fluently() {
+    let interface = AlwaysWorksInterface::default();
+    let thermostat = ThermostatBuilder::new(&interface)
+        .set_range(18.0, 24.0)
+        .safe_range(10.0, 32.0)
+        .operating_mode(OperatingMode::MaintainRange)
+        .heat_timings(120, 1800, 300)
+        .cool_timings(180, 2400, 360)
+        .fan_timings(60, 7200, 30)
+        .build()
+        .unwrap();
+    assert_eq!(thermostat.get_minimum_safe_temperature(), 10.0);
+    assert_eq!(thermostat.get_maximum_set_temperature(), 24.0);
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::MaintainRange);
+    assert_eq!(thermostat.get_maximum_heat_run_secs(), 1800);
+    assert_eq!(thermostat.get_minimum_cool_off_secs(), 360);
+    assert_eq!(thermostat.get_minimum_fan_run_secs(), 60);
+    assert_eq!(
+        ThermostatBuilder::new(&interface)
+            .heat_timings(600, 300, 0)
+            .build()
+            .err(),
+        Some(Error::InvalidConfiguration)
+    );
+    assert_eq!(
+        ThermostatBuilder::new(&interface)
+            .set_range(12.0, 24.0)
+            .build()
+            .err(),
+        Some(Error::InvalidConfiguration)
+    );
+}
+
+#[test]
+fn thermo_default_current_temperature_is_midpoint() {
+    let interface = AlwaysWorksInterface::default();
+    let thermostat = Thermostat::new(&interface);
+    let midpoint = (thermostat.get_minimum_safe_temperature()
+        + thermostat.get_maximum_safe_temperature())
+        / 2.0;
+    assert_eq!(thermostat.get_current_temperature(), midpoint);
+    assert_eq!(thermostat.get_current_temperature(), 22.5);
+}
+
+#[test]
+fn thermo_reports_remaining_constraint_time() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    interface.seconds.set(100);
+    assert!(thermostat.set_current_temperature(18.0).is_err());
+    let detail = thermostat.get_last_error_detail().unwrap();
+    assert_eq!(
+        detail,
+        ConstraintError(Error::FanMinOffTimeConstraint, Some(200))
+    );
+    assert_eq!(
+        detail.to_string(),
+        format!("{} (200 s remaining)", Error::FanMinOffTimeConstraint)
+    );
+}
+
+#[test]
+fn thermo_config_data_center_preset() {
+    let config = ThermostatConfig::from_preset(ApplicationPreset::DataCenter);
+    assert_eq!(config.validate(), Ok(()));
+    assert_eq!(config.operating_mode, OperatingMode::CoolToSetPoint);
+    assert_eq!(config.minimum_cool_off_secs, 30);
+    let interface = AlwaysWorksInterface::default();
+    let thermostat = ThermostatBuilder::from_config(config, &interface).unwrap();
+    assert_eq!(thermostat.get_maximum_safe_temperature(), 28.0);
+}
+
+#[test]
+fn thermo_estimates_cooling_cop() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_maximum_set_temperature(24.0).unwrap();
+    assert_eq!(thermostat.get_estimated_cooling_cop(), None);
+    thermostat.set_rated_cool_power_kw(2.0).unwrap();
+    thermostat.set_thermal_capacity_kj_per_degree(3600.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(26.0).unwrap();
+    interface.seconds.set(2800);
+    thermostat.set_current_temperature(23.0).unwrap();
+    // 3600 kJ/°C * 3 °C removed using 2 kW for 1800 s
+    assert_eq!(thermostat.get_estimated_cooling_cop(), Some(3.0));
+    thermostat.set_outdoor_temperature(123.0).unwrap();
+    let cop = thermostat.get_estimated_cooling_cop().unwrap();
+    assert!((cop - 2.9615).abs() < 1e-9);
+}
+
+#[test]
+fn thermo_locks_out_heat_pump_when_cold_outdoors() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_operating_mode(OperatingMode::HeatPump).unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    assert_eq!(thermostat.get_heat_pump_lockout_temperature(), None);
+    thermostat.set_heat_pump_lockout_temperature(-5.0).unwrap();
+    assert_eq!(
+        thermostat.set_outdoor_temperature(f64::NAN),
+        Err(Error::InvalidTemperature)
+    );
+    thermostat.set_outdoor_temperature(-10.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert!(!interface.heat.get());
+    assert!(interface.aux.get());
+    interface.seconds.set(1700);
+    thermostat.set_outdoor_temperature(-2.0).unwrap();
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert!(interface.heat.get());
+    assert!(!interface.aux.get());
+    // a running compressor is stopped once the lockout engages
+    interface.seconds.set(2400);
+    thermostat.set_outdoor_temperature(-10.0).unwrap();
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert!(!interface.heat.get());
+    assert!(interface.aux.get());
+    // and the compressor runs regardless below the minimum safe temperature
+    interface.seconds.set(3100);
+    thermostat.set_current_temperature(10.0).unwrap();
+    assert!(interface.heat.get());
+    assert!(!interface.aux.get());
+    // other modes are never locked out
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    interface.seconds.set(3800);
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert!(interface.heat.get());
+}
+
+#[test]
+fn thermo_recirculation_only_runs_fan() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::RecirculationOnly)
+        .unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_recirculation_demand(true);
+    thermostat.set_current_temperature(20.0).unwrap();
+    assert!(interface.fan.get());
+    assert!(!interface.heat.get());
+    interface.seconds.set(2000);
+    thermostat.set_recirculation_demand(false);
+    thermostat.set_current_temperature(20.0).unwrap();
+    assert!(!interface.fan.get());
+    // freeze protection still applies
+    interface.seconds.set(3000);
+    thermostat.set_current_temperature(10.0).unwrap();
+    assert!(interface.heat.get());
+}
+
+#[test]
+fn thermo_formats_timestamps() {
+    let mut formatted = String::new();
+    Thermostat::format_timestamp(3723, &mut formatted).unwrap();
+    assert_eq!(formatted, "T+1h2m3s");
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    let mut formatted = String::new();
+    thermostat.format_last_heat_start(&mut formatted).unwrap();
+    assert_eq!(formatted, "never");
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    let mut formatted = String::new();
+    thermostat.format_last_heat_start(&mut formatted).unwrap();
+    assert_eq!(formatted, "T+0h16m40s");
+}
+
+#[test]
+fn thermo_reports_duty_cycle_last_hour() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    interface.seconds.set(2800);
+    thermostat.set_current_temperature(21.0).unwrap();
+    interface.seconds.set(4000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    interface.seconds.set(5000);
+    // 1400 s of the completed cycle fall in the window, plus 1000 s of the one in progress
+    let duty_cycle = thermostat.get_heat_duty_cycle_last_hour().unwrap();
+    assert!((duty_cycle - 2400.0 / 3600.0).abs() < 1e-9);
+    assert_eq!(thermostat.get_cool_duty_cycle_last_hour().unwrap(), 0.0);
+}
+
+#[test]
+fn thermo_polls_occupancy_input() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert!(thermostat.is_occupancy_detected());
+    interface.vacant.set(true);
+    thermostat.set_current_temperature(21.0).unwrap();
+    assert!(!thermostat.is_occupancy_detected());
+    interface.vacant.set(false);
+    thermostat.set_current_temperature(21.0).unwrap();
+    assert!(thermostat.is_occupancy_detected());
+    // a disabled thermostat is not set back
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::Disabled);
+    thermostat.set_operating_mode(OperatingMode::MaintainRange).unwrap();
+    interface.vacant.set(true);
+    thermostat.set_current_temperature(21.0).unwrap();
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::Unoccupied);
+    // a manual change holds until the input next changes
+    thermostat.set_occupancy(true).unwrap();
+    thermostat.set_current_temperature(21.0).unwrap();
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::MaintainRange);
+    thermostat.set_occupancy(false).unwrap();
+    interface.vacant.set(false);
+    thermostat.set_current_temperature(21.0).unwrap();
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::MaintainRange);
+}
+
+#[test]
+fn thermo_rejects_overlapping_maintain_range_bands() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_maximum_set_temperature(24.0).unwrap();
+    thermostat.set_hysteresis(3.0).unwrap();
+    assert_eq!(
+        thermostat.validate_maintain_range_config(),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(
+        thermostat.set_operating_mode(OperatingMode::MaintainRange),
+        Err(Error::InvalidConfiguration)
+    );
+    thermostat.set_hysteresis(2.0).unwrap();
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    assert_eq!(
+        thermostat.set_hysteresis(2.5),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(thermostat.get_hysteresis(), 2.0);
+    thermostat.set_hysteresis(0.0).unwrap();
+    thermostat.set_heat_deadband(4.0).unwrap();
+    assert_eq!(
+        thermostat.set_cool_deadband(4.5),
+        Err(Error::InvalidConfiguration)
+    );
+    thermostat.set_operating_mode(OperatingMode::Disabled).unwrap();
+    thermostat.set_cool_deadband(4.5).unwrap();
+    assert_eq!(
+        thermostat.set_operating_mode(OperatingMode::MaintainRange),
+        Err(Error::InvalidConfiguration)
+    );
+}
+
+#[test]
+fn thermo_calls_post_update_hook() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    static READINGS_SEEN: AtomicU32 = AtomicU32::new(0);
+    static CALLS: AtomicU32 = AtomicU32::new(0);
+    fn hook(thermostat: &Thermostat) {
+        READINGS_SEEN.store(thermostat.get_readings_received(), Ordering::SeqCst);
+        CALLS.fetch_add(1, Ordering::SeqCst);
+    }
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_post_update_hook(hook);
+    thermostat.set_current_temperature(21.0).unwrap();
+    thermostat.set_current_temperature(21.5).unwrap();
+    assert_eq!(READINGS_SEEN.load(Ordering::SeqCst), 2);
+    // humidity readings are not temperature updates
+    thermostat.set_humidity_control_enabled(true);
+    thermostat.set_current_humidity(60.0).unwrap();
+    assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn thermo_auto_recovers_from_temperature_overrun() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_maximum_set_temperature(22.0).unwrap();
+    thermostat.set_maximum_heat_swing(5.0).unwrap();
+    thermostat.set_auto_recovery_enabled(true);
+    thermostat.set_auto_recovery_temperature_margin(1.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    interface.seconds.set(2000);
+    thermostat.set_current_temperature(25.0).unwrap();
+    assert_eq!(thermostat.get_fault(), Some(Error::HeatSwingExceeded));
+    interface.seconds.set(2100);
+    let _ = thermostat.set_current_temperature(24.0);
+    assert_eq!(thermostat.get_fault(), Some(Error::HeatSwingExceeded));
+    interface.seconds.set(2200);
+    thermostat.set_current_temperature(22.5).unwrap();
+    assert_eq!(thermostat.get_fault(), None);
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn thermo_acts_on_injected_test_temperature() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(21.0).unwrap();
+    thermostat.inject_test_temperature(10.0, 300).unwrap();
+    assert!(interface.heat.get());
+    interface.seconds.set(1200);
+    thermostat.set_current_temperature(21.0).unwrap();
+    assert_eq!(thermostat.get_current_temperature(), 10.0);
+    interface.seconds.set(1300);
+    let _ = thermostat.set_current_temperature(21.0);
+    assert_eq!(thermostat.get_current_temperature(), 21.0);
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn thermo_rejects_non_finite_injected_test_temperature() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    interface.seconds.set(1000);
+    assert_eq!(
+        thermostat.inject_test_temperature(f64::NAN, 300),
+        Err(Error::InvalidTemperature)
+    );
+    // real readings are still acted on, including one below the safe temperatures
+    thermostat.set_current_temperature(5.0).unwrap();
+    assert_eq!(thermostat.get_current_temperature(), 5.0);
+    assert!(interface.heat.get());
+}
+
+#[test]
+fn thermo_emergency_heat_uses_only_aux_heat() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert!(interface.heat.get());
+    interface.seconds.set(1100);
+    thermostat
+        .set_operating_mode(OperatingMode::EmergencyHeat)
+        .unwrap();
+    assert!(thermostat.is_in_emergency_heat());
+    assert!(!interface.heat.get());
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert!(interface.aux.get());
+    assert!(!interface.heat.get());
+    interface.seconds.set(1500);
+    assert_eq!(
+        thermostat.set_current_temperature(21.0),
+        Err(Error::HeatMinRunTimeConstraint)
+    );
+    assert!(interface.aux.get());
+    interface.seconds.set(1700);
+    thermostat.set_current_temperature(21.0).unwrap();
+    assert!(!interface.aux.get());
+}
+
+#[test]
+fn thermo_auto_changeover_maintains_target_temperature() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert_eq!(thermostat.get_target_temperature(), 21.0);
+    assert_eq!(
+        thermostat.set_target_temperature(40.0),
+        Err(Error::SetPointOutOfSafeRange)
+    );
+    thermostat.set_target_temperature(22.0).unwrap();
+    assert_eq!(thermostat.get_target_temperature(), 22.0);
+    thermostat
+        .set_operating_mode(OperatingMode::AutoChangeover)
+        .unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(21.7).unwrap();
+    assert!(interface.heat.get());
+    interface.seconds.set(1400);
+    thermostat.set_current_temperature(21.9).unwrap();
+    assert!(interface.heat.get());
+    interface.seconds.set(1700);
+    thermostat.set_current_temperature(22.1).unwrap();
+    assert!(!interface.heat.get());
+    assert!(!interface.cool.get());
+    interface.seconds.set(2100);
+    thermostat.set_current_temperature(22.3).unwrap();
+    assert!(interface.cool.get());
+    assert!(!interface.heat.get());
+}
+
+#[test]
+fn thermo_reports_state() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert_eq!(thermostat.get_state(), ThermostatState::Idle);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert_eq!(thermostat.get_state(), ThermostatState::Heating);
+    interface.seconds.set(1100);
+    assert!(thermostat.set_current_temperature(22.0).is_err());
+    assert_eq!(thermostat.get_state(), ThermostatState::HeatConstrained);
+    interface.seconds.set(1700);
+    thermostat.set_current_temperature(22.0).unwrap();
+    assert_eq!(thermostat.get_state(), ThermostatState::Idle);
+    thermostat.set_operating_mode(OperatingMode::FanOnly).unwrap();
+    interface.seconds.set(2700);
+    thermostat.set_current_temperature(22.0).unwrap();
+    assert_eq!(thermostat.get_state(), ThermostatState::FanOnly);
+}
+
+#[test]
+fn thermo_ramps_set_points() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_safe_temperature(5.0).unwrap();
+    thermostat.set_minimum_set_temperature(12.0).unwrap();
+    assert_eq!(
+        thermostat.set_temperature_ramp_rate_celsius_per_minute(-1.0),
+        Err(Error::InvalidConfiguration)
+    );
+    thermostat
+        .set_temperature_ramp_rate_celsius_per_minute(0.5)
+        .unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_minimum_set_temperature(21.0).unwrap();
+    assert_eq!(thermostat.get_minimum_set_temperature(), 21.0);
+    assert_eq!(thermostat.get_effective_minimum_set_temperature(), 12.0);
+    thermostat.set_current_temperature(15.0).unwrap();
+    assert!(!interface.heat.get());
+    interface.seconds.set(1600);
+    thermostat.set_current_temperature(15.0).unwrap();
+    assert_eq!(thermostat.get_effective_minimum_set_temperature(), 17.0);
+    assert!(interface.heat.get());
+    interface.seconds.set(3400);
+    thermostat.set_current_temperature(16.0).unwrap();
+    assert_eq!(thermostat.get_effective_minimum_set_temperature(), 21.0);
+    assert_eq!(
+        thermostat.get_effective_maximum_set_temperature(),
+        thermostat.get_maximum_set_temperature()
+    );
+}
+
+#[test]
+fn thermo_learns_heat_lead_time() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_adaptive_start_enabled(true);
+    assert_eq!(thermostat.get_recommended_start_advance_secs(), 0);
+    for &(start, reached) in [(1000, 2200), (4000, 4600)].iter() {
+        interface.seconds.set(start);
+        thermostat.set_current_temperature(18.0).unwrap();
+        assert!(interface.heat.get());
+        interface.seconds.set(reached);
+        thermostat.set_current_temperature(20.0).unwrap();
+        thermostat.set_current_temperature(20.5).unwrap();
+    }
+    // 1200 seconds, then a quarter of the way to 600 seconds
+    assert_eq!(thermostat.get_recommended_start_advance_secs(), 1050);
+    thermostat.set_adaptive_start_enabled(false);
+    assert_eq!(thermostat.get_recommended_start_advance_secs(), 0);
+}
+
+#[test]
+fn thermo_follows_weekly_schedule() {
+    let interface = AlwaysWorksInterface::default();
+    let sensor = FixedSensor(Cell::new(21.0));
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_measurement_provider(&sensor);
+    let slot = |time_of_day_secs, min_set_temp| ScheduleSlot {
+        day_of_week: 0,
+        time_of_day_secs,
+        operating_mode: OperatingMode::HeatToSetPoint,
+        min_set_temp,
+        max_set_temp: 24.0,
+    };
+    let mut schedule = Schedule::new();
+    schedule.add_slot(slot(22 * 3600, 17.0)).unwrap();
+    schedule.add_slot(slot(6 * 3600, 20.0)).unwrap();
+    assert_eq!(
+        schedule.add_slot(ScheduleSlot {
+            day_of_week: 7,
+            ..slot(0, 20.0)
+        }),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(
+        schedule.add_slot(slot(6 * 3600, 19.0)),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(schedule.slots()[0].time_of_day_secs, 6 * 3600);
+    thermostat.set_schedule(schedule);
+    interface.seconds.set(8 * 3600);
+    thermostat.tick().unwrap();
+    assert_eq!(
+        thermostat.get_operating_mode(),
+        OperatingMode::HeatToSetPoint
+    );
+    assert_eq!(thermostat.get_minimum_set_temperature(), 20.0);
+    assert_eq!(thermostat.get_next_schedule_change_secs(), Some(14 * 3600));
+    interface.seconds.set(23 * 3600);
+    thermostat.tick().unwrap();
+    assert_eq!(thermostat.get_minimum_set_temperature(), 17.0);
+    // changes between slots last until the next slot
+    thermostat.set_minimum_set_temperature(19.0).unwrap();
+    interface.seconds.set(29 * 3600);
+    thermostat.tick().unwrap();
+    assert_eq!(thermostat.get_minimum_set_temperature(), 19.0);
+    assert_eq!(
+        thermostat.get_next_schedule_change_secs(),
+        Some(7 * 24 * 3600 + 6 * 3600 - 29 * 3600)
+    );
+    thermostat.clear_schedule();
+    assert_eq!(thermostat.get_next_schedule_change_secs(), None);
+    // a slot that cannot be applied is reported, leaving every setting alone, and readings go on
+    let mut schedule = Schedule::new();
+    schedule
+        .add_slot(ScheduleSlot {
+            max_set_temp: 60.0,
+            ..slot(0, 20.0)
+        })
+        .unwrap();
+    thermostat.set_schedule(schedule);
+    sensor.0.set(15.0);
+    assert!(thermostat.tick().is_ok());
+    assert_eq!(
+        thermostat.get_last_error(),
+        Some(Error::SetPointOutOfSafeRange)
+    );
+    assert_eq!(thermostat.get_minimum_set_temperature(), 19.0);
+    assert_eq!(thermostat.get_maximum_set_temperature(), 24.0);
+}
+
+#[test]
+fn thermo_overrides_schedule_until_expiry() {
+    let interface = AlwaysWorksInterface::default();
+    let sensor = FixedSensor(Cell::new(21.0));
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_measurement_provider(&sensor);
+    let mut schedule = Schedule::new();
+    schedule
+        .add_slot(ScheduleSlot {
+            day_of_week: 0,
+            time_of_day_secs: 0,
+            operating_mode: OperatingMode::HeatToSetPoint,
+            min_set_temp: 17.0,
+            max_set_temp: 24.0,
+        })
+        .unwrap();
+    thermostat.set_schedule(schedule);
+    interface.seconds.set(1000);
+    thermostat.tick().unwrap();
+    assert_eq!(thermostat.get_override_remaining_secs(), None);
+    assert_eq!(
+        thermostat.set_override(OperatingMode::MaintainRange, 22.0, 21.0, 3600),
+        Err(Error::InvalidConfiguration)
+    );
+    thermostat
+        .set_override(OperatingMode::HeatToSetPoint, 21.0, 24.0, 3600)
+        .unwrap();
+    thermostat.set_operating_mode(OperatingMode::MaintainRange).unwrap();
+    interface.seconds.set(2000);
+    thermostat.tick().unwrap();
+    assert_eq!(thermostat.get_minimum_set_temperature(), 21.0);
+    assert_eq!(
+        thermostat.get_operating_mode(),
+        OperatingMode::MaintainRange
+    );
+    assert_eq!(thermostat.get_override_remaining_secs(), Some(2600));
+    interface.seconds.set(4600);
+    thermostat.tick().unwrap();
+    assert_eq!(thermostat.get_override_remaining_secs(), None);
+    assert_eq!(thermostat.get_minimum_set_temperature(), 17.0);
+    assert_eq!(
+        thermostat.get_operating_mode(),
+        OperatingMode::HeatToSetPoint
+    );
+    thermostat
+        .set_override(OperatingMode::HeatToSetPoint, 20.0, 24.0, 3600)
+        .unwrap();
+    thermostat.clear_override();
+    assert_eq!(thermostat.get_override_remaining_secs(), None);
+    thermostat.tick().unwrap();
+    assert_eq!(thermostat.get_minimum_set_temperature(), 17.0);
+}
+
+#[test]
+fn thermo_overrides_to_range_above_current_one() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_minimum_set_temperature(18.0).unwrap();
+    thermostat.set_maximum_set_temperature(22.0).unwrap();
+    thermostat.set_operating_mode(OperatingMode::MaintainRange).unwrap();
+    interface.seconds.set(1000);
+    thermostat
+        .set_override(OperatingMode::MaintainRange, 24.0, 26.0, 600)
+        .unwrap();
+    assert_eq!(thermostat.get_minimum_set_temperature(), 24.0);
+    assert_eq!(thermostat.get_maximum_set_temperature(), 26.0);
+    assert_eq!(thermostat.get_override_remaining_secs(), Some(600));
+    // a rejected override changes nothing
+    assert_eq!(
+        thermostat.set_override(OperatingMode::HeatToSetPoint, 16.0, 40.0, 600),
+        Err(Error::SetPointOutOfSafeRange)
+    );
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::MaintainRange);
+    assert_eq!(thermostat.get_minimum_set_temperature(), 24.0);
+}
+
+#[test]
+fn thermo_uses_unoccupied_set_points() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_maximum_set_temperature(24.0).unwrap();
+    assert_eq!(
+        thermostat.set_unoccupied_minimum_set_temperature(10.0),
+        Err(Error::SetPointOutOfSafeRange)
+    );
+    assert_eq!(
+        thermostat.set_unoccupied_maximum_set_temperature(15.5),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(
+        thermostat.set_unoccupied_maximum_set_temperature(16.0),
+        Err(Error::InvalidConfiguration)
+    );
+    // the hysteresis must fit between the unoccupied set points too
+    thermostat.set_unoccupied_maximum_set_temperature(18.0).unwrap();
+    assert_eq!(thermostat.set_hysteresis(2.5), Err(Error::InvalidConfiguration));
+    thermostat.set_unoccupied_minimum_set_temperature(17.0).unwrap();
+    thermostat.set_unoccupied_maximum_set_temperature(27.0).unwrap();
+    assert_eq!(thermostat.get_unoccupied_minimum_set_temperature(), 17.0);
+    assert_eq!(thermostat.get_unoccupied_maximum_set_temperature(), 27.0);
+    thermostat.set_operating_mode(OperatingMode::MaintainRange).unwrap();
+    // occupied is a no-op outside Unoccupied
+    thermostat.set_occupancy(true).unwrap();
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::MaintainRange);
+    thermostat.set_occupancy(false).unwrap();
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::Unoccupied);
+    thermostat.set_occupancy(false).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert!(!interface.heat.get());
+    assert_eq!(thermostat.get_minimum_set_temperature(), 20.0);
+    interface.seconds.set(1500);
+    thermostat.set_current_temperature(17.5).unwrap();
+    assert_eq!(
+        thermostat.get_next_event_estimate(),
+        Ok(NextEventEstimate {
+            estimated_secs_from_now: Some(750),
+            event_type: Some(ExpectedEventType::HeatWillStart),
+        })
+    );
+    interface.seconds.set(2000);
+    assert_eq!(
+        thermostat.set_current_temperature(16.0),
+        Ok(Action::MultipleChanges(2))
+    );
+    assert!(interface.heat.get());
+    thermostat.set_occupancy(true).unwrap();
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::MaintainRange);
+}
+
+#[test]
+fn thermo_vacation_mode_restores_settings() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_minimum_safe_temperature(5.0).unwrap();
+    thermostat.set_maximum_safe_temperature(40.0).unwrap();
+    thermostat.set_minimum_set_temperature(20.5).unwrap();
+    thermostat.set_maximum_set_temperature(23.5).unwrap();
+    thermostat.set_operating_mode(OperatingMode::HeatToSetPoint).unwrap();
+    assert_eq!(
+        thermostat.set_operating_mode(OperatingMode::Vacation),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(
+        thermostat.enter_vacation_mode(35.0, 10.0),
+        Err(Error::InvalidConfiguration)
+    );
+    thermostat.enter_vacation_mode(10.0, 35.0).unwrap();
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::Vacation);
+    assert_eq!(
+        thermostat.set_operating_mode(OperatingMode::MaintainRange),
+        Err(Error::InvalidConfiguration)
+    );
+    thermostat.set_minimum_set_temperature(12.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(15.0).unwrap();
+    assert!(!interface.heat.get());
+    interface.seconds.set(2000);
+    let _ = thermostat.set_current_temperature(11.0);
+    assert!(interface.heat.get());
+    // the settings from before vacation are persisted, so they restore on another thermostat
+    let config = thermostat.get_config();
+    assert_eq!(config.operating_mode, OperatingMode::HeatToSetPoint);
+    assert_eq!(config.minimum_set_temperature, 20.5);
+    let other_interface = AlwaysWorksInterface::default();
+    let mut restored = Thermostat::new(&other_interface);
+    restored.apply_config(&config).unwrap();
+    assert_eq!(restored.get_config(), config);
+    // settings that no longer fit are refused, staying on vacation
+    thermostat.set_hysteresis(3.0).unwrap();
+    assert_eq!(
+        thermostat.exit_vacation_mode(),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::Vacation);
+    thermostat.set_hysteresis(0.0).unwrap();
+    thermostat.exit_vacation_mode().unwrap();
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::HeatToSetPoint);
+    assert_eq!(thermostat.get_minimum_set_temperature(), 20.5);
+    assert_eq!(thermostat.get_maximum_set_temperature(), 23.5);
+    thermostat.exit_vacation_mode().unwrap();
+    assert_eq!(thermostat.get_operating_mode(), OperatingMode::HeatToSetPoint);
+}
+
+#[test]
+fn thermo_alerts_on_filter_runtime() {
+    #[derive(Default)]
+    struct FilterHandler {
+        alerts: Cell<u32>,
+    }
+    impl ThermostatEventHandler for FilterHandler {
+        fn on_filter_alert(&self) {
+            self.alerts.set(self.alerts.get() + 1);
+        }
+    }
+    let interface = AlwaysWorksInterface::default();
+    let handler = FilterHandler::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_event_handler(&handler);
+    thermostat.set_filter_alert_secs(1000);
+    thermostat.set_operating_mode(OperatingMode::FanOnly).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(22.0).unwrap();
+    interface.seconds.set(1600);
+    assert_eq!(thermostat.get_filter_runtime_secs(), 600);
+    assert!(!thermostat.is_filter_alert_active());
+    interface.seconds.set(2100);
+    thermostat.set_current_temperature(22.0).unwrap();
+    assert!(thermostat.is_filter_alert_active());
+    assert_eq!(handler.alerts.get(), 1);
+    interface.seconds.set(2200);
+    thermostat.set_current_temperature(22.0).unwrap();
+    assert_eq!(handler.alerts.get(), 1);
+    thermostat.reset_filter_runtime();
+    assert_eq!(thermostat.get_filter_runtime_secs(), 0);
+    assert!(!thermostat.is_filter_alert_active());
+    interface.seconds.set(2500);
+    assert_eq!(thermostat.get_filter_runtime_secs(), 300);
+}
+
+#[test]
+fn thermo_notifies_event_handler() {
+    #[derive(Default)]
+    struct CountingHandler {
+        heat_started: Cell<u32>,
+        heat_stopped: Cell<u32>,
+        fan_started: Cell<u32>,
+        breaches: Cell<u32>,
+    }
+    impl ThermostatEventHandler for CountingHandler {
+        fn on_heat_started(&self) {
+            self.heat_started.set(self.heat_started.get() + 1);
+        }
+        fn on_heat_stopped(&self) {
+            self.heat_stopped.set(self.heat_stopped.get() + 1);
+        }
+        fn on_fan_started(&self) {
+            self.fan_started.set(self.fan_started.get() + 1);
+        }
+        fn on_safety_limit_breached(&self, _temperature: f64) {
+            self.breaches.set(self.breaches.get() + 1);
+        }
+    }
+    let interface = AlwaysWorksInterface::default();
+    let handler = CountingHandler::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_event_handler(&handler);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    thermostat.set_current_temperature(18.5).unwrap();
+    assert_eq!(handler.heat_started.get(), 1);
+    assert_eq!(handler.fan_started.get(), 1);
+    assert_eq!(handler.breaches.get(), 0);
+    interface.seconds.set(1700);
+    thermostat.set_current_temperature(22.0).unwrap();
+    assert_eq!(handler.heat_stopped.get(), 1);
+    let _ = thermostat.set_current_temperature(12.0);
+    assert_eq!(handler.breaches.get(), 1);
+    let _ = thermostat.set_current_temperature(11.0);
+    assert_eq!(handler.breaches.get(), 1); // still the same breach
+    interface.seconds.set(3000);
+    let _ = thermostat.set_current_temperature(20.0);
+    let _ = thermostat.set_current_temperature(12.0);
+    assert_eq!(handler.breaches.get(), 2);
+}
+
+#[test]
+fn thermo_reports_actions() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    interface.seconds.set(1000);
+    assert_eq!(
+        thermostat.set_current_temperature(18.0),
+        Ok(Action::MultipleChanges(2))
+    );
+    assert_eq!(thermostat.set_current_temperature(18.5), Ok(Action::NoChange));
+    interface.seconds.set(1700);
+    assert_eq!(
+        thermostat.set_current_temperature(22.0),
+        Ok(Action::MultipleChanges(2))
+    );
+    interface.seconds.set(2700);
+    thermostat.set_operating_mode(OperatingMode::FanOnly).unwrap();
+    assert_eq!(
+        thermostat.set_current_temperature(22.0),
+        Ok(Action::FanStarted)
+    );
+}
+
+#[test]
+fn thermo_heat_pump_shifts_reversing_valve() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_operating_mode(OperatingMode::HeatPump).unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_maximum_set_temperature(24.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(26.0).unwrap_err();
+    assert!(interface.valve.get());
+    assert!(!interface.cool.get());
+    assert_eq!(
+        thermostat.get_last_error_detail(),
+        Some(ConstraintError(Error::ReversingValveShiftConstraint, Some(30)))
+    );
+    assert_eq!(thermostat.get_state(), ThermostatState::CoolConstrained);
+    interface.seconds.set(1030);
+    thermostat.set_current_temperature(26.0).unwrap();
+    assert!(interface.cool.get());
+    interface.seconds.set(5000);
+    thermostat.set_current_temperature(22.0).unwrap();
+    interface.seconds.set(9000);
+    thermostat.set_current_temperature(18.0).unwrap_err();
+    assert!(!interface.valve.get());
+    assert!(!interface.heat.get());
+    interface.seconds.set(9030);
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert!(interface.heat.get());
+}
+
+#[test]
+fn thermo_fan_only_conditions_only_outside_safe_range() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_operating_mode(OperatingMode::FanOnly).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(22.0).unwrap();
+    assert!(interface.fan.get());
+    assert!(!interface.heat.get());
+    assert!(!interface.cool.get());
+    interface.seconds.set(44300);
+    assert_eq!(
+        thermostat.set_current_temperature(22.0),
+        Err(Error::FanMaxRunTimeConstraint)
+    );
+    assert!(!interface.fan.get());
+    interface.seconds.set(45000);
+    thermostat.set_current_temperature(10.0).unwrap();
+    assert!(interface.heat.get());
+    assert!(interface.fan.get());
+}
+
+#[test]
+fn thermo_dehumidifies_above_target_humidity() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::CoolToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_maximum_set_temperature(24.0).unwrap();
+    assert_eq!(
+        thermostat.set_current_humidity(101.0),
+        Err(Error::HumidityOutOfRange)
+    );
+    assert_eq!(
+        thermostat.set_target_humidity(-1.0),
+        Err(Error::HumidityOutOfRange)
+    );
+    thermostat.set_target_humidity(45.0).unwrap();
+    thermostat.set_humidity_deadband(6.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(22.0).unwrap();
+    thermostat.set_current_humidity(60.0).unwrap();
+    assert!(!interface.cool.get());
+    thermostat.set_humidity_control_enabled(true);
+    thermostat.set_current_humidity(60.0).unwrap();
+    assert!(interface.cool.get());
+    assert_eq!(
+        thermostat.get_last_transition().map(|t| t.trigger),
+        Some(TransitionTrigger::HumidityReading(60.0))
+    );
+    interface.seconds.set(1700);
+    thermostat.set_current_humidity(44.0).unwrap();
+    assert!(interface.cool.get());
+    thermostat.set_current_humidity(41.0).unwrap();
+    assert!(!interface.cool.get());
+}
+
+#[test]
+fn thermo_drives_humidifier_and_dehumidifier() {
+    let interface = AlwaysWorksInterface::default();
+    interface.dehumidifier_fitted.set(true);
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::CoolToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_maximum_set_temperature(24.0).unwrap();
+    thermostat.set_minimum_humidifier_run_secs(600);
+    assert_eq!(thermostat.get_minimum_humidifier_run_secs(), 600);
+    thermostat.set_minimum_dehumidifier_off_secs(900);
+    assert_eq!(thermostat.get_minimum_dehumidifier_off_secs(), 900);
+    thermostat.set_humidity_control_enabled(true);
+    thermostat.set_target_humidity(45.0).unwrap();
+    thermostat.set_humidity_deadband(6.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(22.0).unwrap();
+    thermostat.set_current_humidity(30.0).unwrap();
+    assert!(interface.humidifier.get());
+    assert!(!interface.dehumidifier.get());
+    interface.seconds.set(1300);
+    assert_eq!(
+        thermostat.set_current_humidity(50.0),
+        Err(Error::HumidifierMinRunTimeConstraint)
+    );
+    assert!(interface.humidifier.get());
+    interface.seconds.set(1600);
+    thermostat.set_current_humidity(50.0).unwrap();
+    assert!(!interface.humidifier.get());
+    assert!(interface.dehumidifier.get());
+    // a dedicated dehumidifier dehumidifies instead of cooling
+    assert!(!interface.cool.get());
+    interface.seconds.set(1900);
+    thermostat.set_current_humidity(42.0).unwrap();
+    assert!(!interface.dehumidifier.get());
+    assert!(!interface.humidifier.get());
+    interface.seconds.set(2000);
+    assert_eq!(
+        thermostat.set_current_humidity(50.0),
+        Err(Error::DehumidifierMinOffTimeConstraint)
+    );
+    thermostat.set_operating_mode(OperatingMode::Disabled).unwrap();
+    interface.seconds.set(3000);
+    thermostat.set_current_humidity(30.0).unwrap();
+    assert!(!interface.humidifier.get());
+    assert!(!interface.dehumidifier.get());
+}
+
+#[test]
+fn thermo_anticipates_temperature_trend() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_maximum_set_temperature(24.0).unwrap();
+    thermostat.set_thermal_mass(ThermalMassProfile::Low).unwrap();
+    thermostat.set_trend_anticipation_gain(0.5).unwrap();
+    thermostat.set_minimum_heat_off_secs(600).unwrap();
+    assert_eq!(
+        thermostat.set_temperature_trend_window(17),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(
+        thermostat.set_trend_anticipation_gain(-1.0),
+        Err(Error::InvalidConfiguration)
+    );
+    thermostat.set_temperature_trend_window(3).unwrap();
+    assert_eq!(thermostat.get_temperature_trend_celsius_per_minute(), 0.0);
+    for (secs, temperature) in [(100, 23.0), (160, 22.75), (220, 22.0)].iter() {
+        interface.seconds.set(*secs);
+        thermostat.set_current_temperature(*temperature).unwrap();
+    }
+    assert_eq!(thermostat.get_temperature_trend_celsius_per_minute(), -0.5);
+    assert!(!interface.heat.get());
+    // an early start held back by the minimum off time is not a constraint violation
+    thermostat.set_trend_anticipation_enabled(true);
+    assert_eq!(
+        thermostat.set_current_temperature(22.0),
+        Ok(Action::NoChange)
+    );
+    assert_eq!(thermostat.get_last_error(), None);
+    assert!(!interface.heat.get());
+    interface.seconds.set(700);
+    thermostat.set_current_temperature(21.0).unwrap();
+    assert!(!interface.heat.get());
+    thermostat.set_trend_anticipation_gain(1.0).unwrap();
+    thermostat.set_current_temperature(21.0).unwrap();
+    assert!(interface.heat.get());
+}
+
+#[test]
+fn thermo_filters_temperature_readings() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert_eq!(thermostat.get_temperature_filter_depth(), 1);
+    assert_eq!(
+        thermostat.set_temperature_filter_depth(0),
+        Err(Error::InvalidConfiguration)
+    );
+    assert_eq!(
+        thermostat.set_temperature_filter_depth(65),
+        Err(Error::InvalidConfiguration)
+    );
+    thermostat.set_current_temperature(21.0).unwrap();
+    assert_eq!(thermostat.get_current_temperature(), 21.0);
+    thermostat.set_temperature_filter_depth(4).unwrap();
+    for temperature in [20.0, 20.0, 20.0, 32.0].iter() {
+        thermostat.set_current_temperature(*temperature).unwrap();
+    }
+    assert_eq!(thermostat.get_current_temperature(), 23.0);
+    assert_eq!(thermostat.get_last_raw_temperature(), 32.0);
+    thermostat.set_current_temperature(24.0).unwrap();
+    assert_eq!(thermostat.get_current_temperature(), 24.0);
+}
+
+struct FixedSensor(Cell<f64>);
+
+impl MeasurementProvider for FixedSensor {
+    fn measure(&self) -> Result<f64, Error> {
+        Ok(self.0.get())
+    }
+}
+
+#[test]
+fn thermo_ticks_measurement_provider() {
+    let interface = AlwaysWorksInterface::default();
+    let sensor = FixedSensor(Cell::new(25.0));
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::CoolToSetPoint)
+        .unwrap();
+    thermostat.set_maximum_set_temperature(24.0).unwrap();
+    assert_eq!(thermostat.tick(), Err(Error::NoMeasurementProvider));
+    thermostat.set_measurement_provider(&sensor);
+    interface.seconds.set(1000);
+    assert_eq!(thermostat.tick(), Ok(Action::MultipleChanges(2)));
+    assert_eq!(thermostat.get_current_temperature(), 25.0);
+    assert!(interface.cool.get());
+}
+
+#[derive(Default)]
+struct DamperRecorder {
+    open: RefCell<Vec<bool>>,
+}
+
+impl ZoneDamperInterface for DamperRecorder {
+    fn open_damper(&self, zone_index: usize) -> Result<(), Error> {
+        self.open.borrow_mut()[zone_index] = true;
+        Ok(())
+    }
+    fn close_damper(&self, zone_index: usize) -> Result<(), Error> {
+        self.open.borrow_mut()[zone_index] = false;
+        Ok(())
+    }
+}
+
+#[test]
+fn thermo_zone_controller_arbitrates_zones() {
+    let interfaces = [
+        AlwaysWorksInterface::default(),
+        AlwaysWorksInterface::default(),
+        AlwaysWorksInterface::default(),
+    ];
+    let mut zone_0 = DynThermostat::new(&interfaces[0]);
+    let mut zone_1 = DynThermostat::new(&interfaces[1]);
+    let mut zone_2 = DynThermostat::new(&interfaces[2]);
+    for zone in [&mut zone_0, &mut zone_1, &mut zone_2].iter_mut() {
+        zone.set_operating_mode(OperatingMode::MaintainRange).unwrap();
+        zone.set_minimum_set_temperature(20.0).unwrap();
+        zone.set_maximum_set_temperature(24.0).unwrap();
+    }
+    for interface in interfaces.iter() {
+        interface.seconds.set(1000);
+    }
+    let dampers = DamperRecorder {
+        open: RefCell::new(vec![false; 3]),
+    };
+    let mut controller = ZoneController::new(
+        [&mut zone_0, &mut zone_1, &mut zone_2],
+        &dampers,
+        ZonePriority::FirstCall,
+    );
+    for (zone_index, temperature) in [18.0, 26.0, 22.0].iter().enumerate() {
+        let zone = controller.get_zone_mut(zone_index).unwrap();
+        zone.set_current_temperature(*temperature).unwrap();
+    }
+    controller.tick().unwrap();
+    assert_eq!(controller.get_active_zone(), Some(0));
+    assert_eq!(controller.get_active_call(), Some(ZoneCall::Heat));
+    assert_eq!(*dampers.open.borrow(), vec![true, false, false]);
+    controller.set_priority(ZonePriority::MostDemand);
+    let zone = controller.get_zone_mut(1).unwrap();
+    zone.set_current_temperature(30.0).unwrap();
+    controller.tick().unwrap();
+    assert_eq!(controller.get_active_zone(), Some(1));
+    assert_eq!(controller.get_active_call(), Some(ZoneCall::Cool));
+    assert_eq!(*dampers.open.borrow(), vec![false, true, false]);
+    assert_eq!(
+        controller.set_zone_enabled(3, false),
+        Err(Error::InvalidZone)
+    );
+    controller.set_zone_enabled(1, false).unwrap();
+    assert!(!controller.is_zone_enabled(1));
+    controller.tick().unwrap();
+    assert_eq!(controller.get_active_zone(), Some(0));
+    assert_eq!(*dampers.open.borrow(), vec![true, false, false]);
+}
+
+#[test]
+fn thermo_zone_controller_serves_zones_in_turn() {
+    let interfaces = [
+        AlwaysWorksInterface::default(),
+        AlwaysWorksInterface::default(),
+        AlwaysWorksInterface::default(),
+    ];
+    let mut zone_0 = Thermostat::new_static(&interfaces[0]);
+    let mut zone_1 = Thermostat::new_static(&interfaces[1]);
+    let mut zone_2 = Thermostat::new_static(&interfaces[2]);
+    for zone in [&mut zone_0, &mut zone_1, &mut zone_2].iter_mut() {
+        zone.set_operating_mode(OperatingMode::MaintainRange).unwrap();
+        zone.set_minimum_set_temperature(20.0).unwrap();
+        zone.set_maximum_set_temperature(24.0).unwrap();
+    }
+    let dampers = DamperRecorder {
+        open: RefCell::new(vec![false; 3]),
+    };
+    let mut controller = ZoneController::new(
+        [&mut zone_0, &mut zone_1, &mut zone_2],
+        &dampers,
+        ZonePriority::RoundRobin,
+    );
+    let mut read = |seconds, readings: &[(usize, f64)]| {
+        for interface in interfaces.iter() {
+            interface.seconds.set(seconds);
+        }
+        for &(zone_index, temperature) in readings {
+            let zone = controller.get_zone_mut(zone_index).unwrap();
+            zone.set_current_temperature(temperature).unwrap();
+        }
+        controller.tick().unwrap();
+        controller.get_active_zone()
+    };
+    assert_eq!(read(1000, &[(0, 18.0), (1, 18.0), (2, 18.0)]), Some(0));
+    // the next calling zone is served once the served zone is satisfied
+    assert_eq!(read(2000, &[(0, 22.0)]), Some(1));
+    assert_eq!(read(3000, &[(0, 18.0), (1, 22.0)]), Some(2));
+    // and the turn wraps around to the first zone
+    assert_eq!(read(4000, &[(2, 22.0)]), Some(0));
+    assert_eq!(*dampers.open.borrow(), vec![true, false, false]);
+}
+
+#[test]
+fn thermo_tracks_humidity_trend() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert_eq!(thermostat.get_humidity_trend(), 0.0);
+    assert_eq!(
+        thermostat.set_humidity_trend_window(1),
+        Err(Error::InvalidConfiguration)
+    );
+    thermostat.set_humidity_trend_window(3).unwrap();
+    for humidity in [40.0, 42.0, 50.0, 54.0].iter() {
+        thermostat.set_current_humidity(*humidity).unwrap();
+    }
+    assert_eq!(thermostat.get_humidity_trend(), 6.0);
+    thermostat.set_humidity_trend_window(2).unwrap();
+    assert_eq!(thermostat.get_humidity_trend(), 4.0);
+}
+
+#[test]
+fn thermo_state_transitions_are_correct() {
+    fn fan_only(_temperature: f64, _humidity: f64) -> ControlDecision {
+        ControlDecision {
+            call_fan: true,
+            ..ControlDecision::default()
+        }
+    }
+    // (heat, cool, fan, aux heat) below the safe range, between the set points, and above the
+    // safe range
+    const HEAT: (bool, bool, bool, bool) = (true, false, true, false);
+    const COOL: (bool, bool, bool, bool) = (false, true, true, false);
+    const FAN: (bool, bool, bool, bool) = (false, false, true, false);
+    const AUX: (bool, bool, bool, bool) = (false, false, true, true);
+    const OFF: (bool, bool, bool, bool) = (false, false, false, false);
+    let expectations = [
+        (OperatingMode::MaintainRange, [HEAT, OFF, COOL]),
+        (OperatingMode::CoolToSetPoint, [HEAT, OFF, COOL]),
+        (OperatingMode::HeatToSetPoint, [HEAT, OFF, COOL]),
+        (OperatingMode::Disabled, [HEAT, OFF, COOL]),
+        (OperatingMode::DisabledUnsafe, [OFF, OFF, OFF]),
+        (OperatingMode::Custom, [HEAT, FAN, COOL]),
+        (OperatingMode::Differential, [HEAT, OFF, COOL]),
+        (OperatingMode::Scheduling, [HEAT, OFF, COOL]),
+        (OperatingMode::RecirculationOnly, [HEAT, OFF, COOL]),
+        (OperatingMode::EmergencyHeat, [AUX, OFF, COOL]),
+        (OperatingMode::AutoChangeover, [HEAT, COOL, COOL]),
+        (OperatingMode::FanOnly, [HEAT, FAN, COOL]),
+        // cooling waits for the reversing valve to shift
+        (OperatingMode::HeatPump, [HEAT, OFF, OFF]),
+        (OperatingMode::Unoccupied, [HEAT, OFF, COOL]),
+    ];
+    for &(mode, outputs) in expectations.iter() {
+        for (&temperature, &expected) in [10.0, 22.0, 35.0].iter().zip(outputs.iter()) {
+            let interface = AlwaysWorksInterface::default();
+            let mut thermostat = Thermostat::new(&interface);
+            thermostat.set_minimum_set_temperature(20.0).unwrap();
+            thermostat.set_maximum_set_temperature(24.0).unwrap();
+            thermostat.set_custom_control_fn(fan_only);
+            thermostat.set_operating_mode(mode).unwrap();
+            interface.seconds.set(1000);
+            let _ = thermostat.set_current_temperature(temperature);
+            let actual = (
+                interface.heat.get(),
+                interface.cool.get(),
+                interface.fan.get(),
+                interface.aux.get(),
+            );
+            assert_eq!(actual, expected, "{:?} at {}", mode, temperature);
+        }
+    }
+}
+
+#[test]
+fn thermo_future_completes_on_reading_or_constraint_clearance() {
+    use std::future::Future;
+    use std::task::{Context, Poll, Waker};
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    let mut cx = Context::from_waker(Waker::noop());
+    interface.seconds.set(1000);
+    interface.reading.set(Some(18.0));
+    let mut future = thermostat.next_decision();
+    match std::pin::Pin::new(&mut future).poll(&mut cx) {
+        Poll::Ready(Ok(status)) => assert!(status.outputs.heat),
+        _ => panic!("expected a decision"),
+    }
+    interface.seconds.set(1100);
+    let _ = thermostat.set_current_temperature(21.0);
+    // the fan's minimum run time clears first, but heat must still run until 1600
+    assert_eq!(thermostat.next_constraint_clearance_secs(), Some(200));
+    let mut future = thermostat.next_decision();
+    assert!(std::pin::Pin::new(&mut future).poll(&mut cx).is_pending());
+    interface.seconds.set(1300);
+    match std::pin::Pin::new(&mut future).poll(&mut cx) {
+        Poll::Ready(Err(error)) => assert_eq!(error, Error::HeatMinRunTimeConstraint),
+        _ => panic!("expected a decision"),
+    }
+    let mut future = thermostat.next_decision();
+    interface.seconds.set(1600);
+    match std::pin::Pin::new(&mut future).poll(&mut cx) {
+        Poll::Ready(Ok(status)) => assert!(!status.outputs.heat),
+        _ => panic!("expected a decision"),
+    }
+    // with no reading and nothing counting down, the current temperature is re-evaluated at once
+    interface.seconds.set(5000);
+    assert_eq!(thermostat.next_constraint_clearance_secs(), None);
+    let mut future = thermostat.next_decision();
+    assert!(std::pin::Pin::new(&mut future).poll(&mut cx).is_ready());
+}
+
+#[test]
+fn thermo_reports_temperature_age() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    assert_eq!(thermostat.get_temperature_age_secs(), Ok(None));
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(21.0).unwrap();
+    thermostat.mark_temperature_reading_time().unwrap();
+    interface.seconds.set(1045);
+    assert_eq!(thermostat.get_temperature_age_secs(), Ok(Some(45)));
+}
+
+#[test]
+fn thermo_enumerates_error_variants() {
+    let variants = Error::all_variants();
+    assert_eq!(variants.len(), Error::count());
+    assert_eq!(variants[0], Error::HandlerFailed);
+    for (index, variant) in variants.iter().enumerate() {
+        assert!(!variants[..index].contains(variant));
+    }
+}
+
+#[test]
+fn thermo_estimates_next_event() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    thermostat.set_maximum_set_temperature(24.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(22.0).unwrap();
+    interface.seconds.set(1100);
+    thermostat.set_current_temperature(21.0).unwrap();
+    // falling 0.01 degrees per second, so 125 s until half the default deadband below the
+    // minimum set temperature
+    assert_eq!(
+        thermostat.get_next_event_estimate(),
+        Ok(NextEventEstimate {
+            estimated_secs_from_now: Some(125),
+            event_type: Some(ExpectedEventType::HeatWillStart),
+        })
+    );
+    interface.seconds.set(1200);
+    thermostat.set_current_temperature(19.0).unwrap();
+    interface.seconds.set(1300);
+    thermostat.set_current_temperature(19.5).unwrap();
+    // rising, but heat must run at least 600 s from 1200
+    assert_eq!(
+        thermostat.get_next_event_estimate(),
+        Ok(NextEventEstimate {
+            estimated_secs_from_now: Some(500),
+            event_type: Some(ExpectedEventType::HeatWillStop),
+        })
+    );
+}
+
+#[test]
+fn thermo_locks_safe_temperatures() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat.set_maximum_safe_temperature(28.0).unwrap();
+    thermostat.lock_safe_temperatures().unwrap();
+    assert_eq!(
+        thermostat.set_maximum_safe_temperature(35.0),
+        Err(Error::SafeTemperatureLocked)
+    );
+    assert_eq!(
+        thermostat.set_minimum_safe_temperature(10.0),
+        Err(Error::SafeTemperatureLocked)
+    );
+    assert_eq!(
+        thermostat.apply_temperature_profile(10.0, 35.0, 20.0, 24.0),
+        Err(Error::SafeTemperatureLocked)
+    );
+    thermostat
+        .apply_temperature_profile(15.0, 28.0, 20.0, 24.0)
+        .unwrap();
+    assert_eq!(thermostat.get_maximum_safe_temperature(), 28.0);
+}
+
+#[test]
+fn thermo_heat_respects_maximum_run_time() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::MaintainRange)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(18.0).unwrap();
+    interface.seconds.set(4599);
+    thermostat.set_current_temperature(18.0).unwrap();
+    assert!(interface.heat.get());
+    interface.seconds.set(4600);
+    assert_eq!(
+        thermostat.set_current_temperature(18.0),
+        Err(Error::HeatMaxRunTimeConstraint)
+    );
+    assert!(!interface.heat.get());
+}
+
+#[test]
+fn thermo_heat_deadband_prevents_cycling_at_set_point() {
+    let interface = AlwaysWorksInterface::default();
+    let mut thermostat = Thermostat::new(&interface);
+    thermostat
+        .set_operating_mode(OperatingMode::HeatToSetPoint)
+        .unwrap();
+    thermostat.set_minimum_set_temperature(20.0).unwrap();
+    assert_eq!(
+        thermostat.set_heat_deadband(-1.0),
+        Err(Error::InvalidDeadband)
+    );
+    thermostat.set_heat_deadband(1.0).unwrap();
+    assert_eq!(thermostat.get_heat_deadband(), 1.0);
+    interface.seconds.set(1000);
+    thermostat.set_current_temperature(19.6).unwrap();
+    assert!(!interface.heat.get());
+    thermostat.set_current_temperature(19.4).unwrap();
+    assert!(interface.heat.get());
+    interface.seconds.set(2000);
+    thermostat.set_current_temperature(20.5).unwrap();
+    assert!(interface.heat.get());
+    thermostat.set_current_temperature(20.6).unwrap();
+    assert!(!interface.heat.get());
+}